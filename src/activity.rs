@@ -0,0 +1,73 @@
+use crate::procfs;
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often files are looked at again.
+const PERIOD: Duration = Duration::from_secs(2);
+
+/// Watches files for writes in the background by stat'ing them every couple
+/// of seconds, which needs no privileges unlike fanotify. Stops when dropped.
+pub struct Watcher {
+    pub rx: Receiver<HashMap<PathBuf, i64>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Watches `files`, plus whatever processes have open for writing under
+/// `root` at the time. After each round, sends the files written to since
+/// the last one with how fast they grew in bytes per second, which is zero
+/// or less for files rewritten in place or truncated.
+pub fn watch(files: Vec<PathBuf>, root: PathBuf) -> Watcher {
+    let (tx, rx) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    thread::spawn(move || {
+        let mut last: HashMap<PathBuf, (u64, i64)> = HashMap::new();
+        let mut at = Instant::now();
+        while !stopped.load(Ordering::Relaxed) {
+            let mut watched = files.clone();
+            let open = procfs::open_files(&root).into_iter();
+            watched.extend(open.filter(|f| f.writing).map(|f| f.path));
+            let now: HashMap<PathBuf, (u64, i64)> = watched
+                .into_iter()
+                .filter_map(|p| Some((p.clone(), stat(&p)?)))
+                .collect();
+            let elapsed = at.elapsed().as_secs_f64();
+            at = Instant::now();
+
+            let active = now
+                .iter()
+                .filter_map(|(p, &(size, mtime))| {
+                    let &(was, was_mtime) = last.get(p)?;
+                    let rate = (size as f64 - was as f64) / elapsed;
+                    (size != was || mtime != was_mtime).then(|| (p.clone(), rate as i64))
+                })
+                .collect();
+            if tx.send(active).is_err() {
+                break;
+            }
+            last = now;
+            thread::sleep(PERIOD);
+        }
+    });
+    Watcher { rx, stop }
+}
+
+/// Size and modification time in nanoseconds.
+fn stat(path: &Path) -> Option<(u64, i64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime = metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec();
+    Some((metadata.len(), mtime))
+}
@@ -0,0 +1,83 @@
+//! Files left behind by crashes and debugging sessions, which tend to pile
+//! up unnoticed and are rarely wanted once someone has looked at them.
+
+use crate::tree::{Info, Tree};
+use std::path::Path;
+
+/// Packet captures smaller than this are probably being kept on purpose.
+pub const CAPTURE_THRESHOLD: u64 = 100 << 20;
+
+/// What kind of artifact `i` looks like, judging by its name.
+pub fn kind(i: &Info) -> Option<&'static str> {
+    if i.is_dir {
+        return None;
+    }
+    let name = i.path.file_name()?.to_str()?;
+    let numbered = |prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|pid| !pid.is_empty() && pid.bytes().all(|c| c.is_ascii_digit()))
+    };
+    let extension = name.rsplit_once('.').map(|(_, e)| e);
+    Some(match extension {
+        _ if name == "core" || numbered("core.") || numbered("vgcore.") => "core dump",
+        _ if name.starts_with("hs_err_pid") && name.ends_with(".log") => "JVM crash log",
+        _ if name.starts_with("java_pid") && name.ends_with(".hprof") => "heap dump",
+        Some("dmp" | "mdmp") => "minidump",
+        Some("pcap" | "pcapng") if i.size >= CAPTURE_THRESHOLD => "packet capture",
+        _ => return None,
+    })
+}
+
+pub struct Artifact {
+    pub info: Info,
+    pub kind: &'static str,
+}
+
+/// The artifacts under `dir`, biggest first.
+pub fn find(tree: &Tree, dir: &Path) -> Vec<Artifact> {
+    let mut found: Vec<_> = tree
+        .subtree(dir)
+        .iter()
+        .filter_map(|i| {
+            Some(Artifact {
+                kind: kind(i)?,
+                info: i.clone(),
+            })
+        })
+        .collect();
+    found.sort_by_key(|a| std::cmp::Reverse(a.info.size));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn kind_of(name: &str, size: u64) -> Option<&'static str> {
+        kind(&Info {
+            path: PathBuf::from("/r").join(name),
+            depth: 2,
+            size,
+            disk: 0,
+            is_dir: false,
+            mtime: 0,
+            uid: 0,
+        })
+    }
+
+    #[test]
+    fn recognises_artifacts_by_name() {
+        assert_eq!(kind_of("core", 1), Some("core dump"));
+        assert_eq!(kind_of("core.1234", 1), Some("core dump"));
+        assert_eq!(kind_of("core.rs", 1), None);
+        assert_eq!(kind_of("hs_err_pid77.log", 1), Some("JVM crash log"));
+        assert_eq!(kind_of("java_pid77.hprof", 1), Some("heap dump"));
+        assert_eq!(kind_of("crash.dmp", 1), Some("minidump"));
+        assert_eq!(kind_of("eth0.pcap", 1), None);
+        assert_eq!(
+            kind_of("eth0.pcap", CAPTURE_THRESHOLD),
+            Some("packet capture")
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use crate::dirs;
+use adansonia::civil_from_days;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Append-only record of every mutating action taken from the browser, so
+/// "what did I delete yesterday?" has an answer. One tab-separated line per
+/// action: UTC timestamp, action, size in bytes (or -), path.
+pub struct AuditLog {
+    path: Option<PathBuf>,
+}
+
+impl AuditLog {
+    /// Logs to `path`, or to the state directory if not given.
+    pub fn new(path: Option<PathBuf>) -> AuditLog {
+        AuditLog {
+            path: path.or_else(|| dirs::state_dir().map(|dir| dir.join("audit.log"))),
+        }
+    }
+
+    pub fn record(&self, action: &str, path: &Path, size: Option<u64>) -> io::Result<()> {
+        let Some(log) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = log.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(log)?;
+        // other sessions may be logging at the same time.
+        dirs::lock(&file)?;
+        writeln!(
+            file,
+            "{}\t{}\t{}\t{}",
+            timestamp(SystemTime::now()),
+            action,
+            size.map_or("-".to_string(), |s| s.to_string()),
+            path.display()
+        )
+    }
+}
+
+/// Formats a time as RFC 3339 in UTC, e.g. `2024-12-22T13:37:00Z`.
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
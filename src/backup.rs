@@ -0,0 +1,123 @@
+use adansonia::tree::Tree;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A backup repository, by the tool that manages it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Restic,
+    Borg,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Restic => "restic",
+            Kind::Borg => "borg",
+        }
+    }
+}
+
+/// What a repository holds: everything backed up as it was before
+/// deduplication and compression, and the data actually stored for it.
+pub struct Usage {
+    pub logical: u64,
+    pub stored: u64,
+}
+
+/// The directories under `dir` that look like restic or borg repositories,
+/// going by the files each keeps at the top of one.
+pub fn find(tree: &Tree, dir: &Path) -> Vec<(PathBuf, Kind)> {
+    tree.subtree(dir)
+        .iter()
+        .filter(|i| !i.is_dir && i.path.file_name() == Some(OsStr::new("config")))
+        .filter_map(|config| {
+            let repo = config.path.parent()?;
+            let names: HashSet<&OsStr> = tree
+                .children(repo)
+                .filter_map(|c| c.path.file_name())
+                .collect();
+            let has = |name: &str| names.contains(OsStr::new(name));
+            let kind = if ["data", "index", "keys", "snapshots"]
+                .iter()
+                .all(|n| has(n))
+            {
+                Kind::Restic
+            } else if has("README") && has("data") {
+                Kind::Borg
+            } else {
+                return None;
+            };
+            Some((repo.to_path_buf(), kind))
+        })
+        .collect()
+}
+
+/// Asks the repository's tool how much is backed up in it. Passwords have
+/// to come from the environment, e.g. RESTIC_PASSWORD or BORG_PASSPHRASE,
+/// since there's nowhere to prompt for them.
+pub fn usage(repo: &Path, kind: Kind) -> io::Result<Usage> {
+    match kind {
+        Kind::Restic => {
+            let stats = |mode| {
+                let json = run(Command::new("restic")
+                    .args(["--json", "--no-lock", "--repo"])
+                    .arg(repo)
+                    .args(["stats", "--mode", mode]))?;
+                number(&json, "total_size").ok_or_else(|| unexpected("restic"))
+            };
+            Ok(Usage {
+                logical: stats("restore-size")?,
+                stored: stats("raw-data")?,
+            })
+        }
+        Kind::Borg => {
+            let json = run(Command::new("borg").args(["info", "--json"]).arg(repo))?;
+            let found = |key| number(&json, key).ok_or_else(|| unexpected("borg"));
+            Ok(Usage {
+                logical: found("total_size")?,
+                stored: found("unique_csize")?,
+            })
+        }
+    }
+}
+
+/// Runs a command without a terminal, returning its output, or its first
+/// line of complaint if it fails.
+fn run(command: &mut Command) -> io::Result<String> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::other("not installed"),
+            _ => e,
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let line = stderr
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("failed");
+        return Err(io::Error::other(line.trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn unexpected(tool: &str) -> io::Error {
+    io::Error::other(format!("unexpected output from {tool}"))
+}
+
+/// The first number given for `key` in some JSON, without a full parser:
+/// the fields wanted here are unique enough.
+fn number(json: &str, key: &str) -> Option<u64> {
+    let quoted = format!("\"{key}\"");
+    let rest = &json[json.find(&quoted)? + quoted.len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
@@ -0,0 +1,168 @@
+//! Timings of past scans, kept per set of paths scanned so `scan
+//! --benchmark` can say how a run compares with the one before it, and
+//! changes that slow the scanner down show up as they're made.
+
+use crate::civil_from_days;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// One timed scan. A line of its own in the file runs are kept in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    pub when: u64, // seconds since the epoch
+    pub elapsed: Duration,
+    pub cpu: Duration, // user and system time together
+    pub calls: u64,    // made to the filesystem, see LocalSource::calls()
+    pub entries: u64,
+    pub threads: usize,
+    pub peak: u64, // resident memory at its highest, in bytes
+    pub paths: Vec<PathBuf>,
+}
+
+impl Run {
+    /// The run as a tab-separated line: when, elapsed and CPU time in
+    /// microseconds, calls, entries, threads, peak memory and then each
+    /// path.
+    fn line(&self) -> String {
+        let mut line = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.when,
+            self.elapsed.as_micros(),
+            self.cpu.as_micros(),
+            self.calls,
+            self.entries,
+            self.threads,
+            self.peak
+        );
+        for path in &self.paths {
+            line += &format!("\t{}", path.display());
+        }
+        line
+    }
+
+    fn parse(line: &str) -> Option<Run> {
+        let mut fields = line.split('\t');
+        let mut number = || fields.next()?.parse::<u64>().ok();
+        let (when, elapsed, cpu) = (number()?, number()?, number()?);
+        let (calls, entries, threads) = (number()?, number()?, number()?);
+        let peak = number()?;
+        Some(Run {
+            when,
+            elapsed: Duration::from_micros(elapsed),
+            cpu: Duration::from_micros(cpu),
+            calls,
+            entries,
+            threads: threads as usize,
+            peak,
+            paths: fields.map(PathBuf::from).collect(),
+        })
+    }
+}
+
+/// The last run kept in `file` that scanned the same paths as `paths`, if
+/// there's been one.
+pub fn previous(file: &Path, paths: &[PathBuf]) -> io::Result<Option<Run>> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut runs = text.lines().rev().filter_map(Run::parse);
+    Ok(runs.find(|run| run.paths == paths))
+}
+
+/// Adds `run` to the end of `file`, for the next one to be compared with.
+pub fn record(file: &Path, run: &Run) -> io::Result<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(file, "{}", run.line())
+}
+
+/// How `now` did next to `before`, e.g. "since the last run, on 2024-12-22:
+/// 12% faster, 5% less CPU time, 3% fewer filesystem calls, 8% less peak
+/// memory", with a word of warning if they didn't do the same work.
+pub fn compare(before: &Run, now: &Run) -> String {
+    let by = |f: fn(&Run) -> f64, down, up, same| change(f(before), f(now), down, up, same);
+    let elapsed = by(|r| r.elapsed.as_secs_f64(), "faster", "slower", "as fast");
+    let cpu = by(|r| r.cpu.as_secs_f64(), "less", "more", "the same");
+    let calls = by(|r| r.calls as f64, "fewer", "more", "as many");
+    let peak = by(|r| r.peak as f64, "less", "more", "the same");
+    let entries = by(|r| r.entries as f64, "fewer", "more", "");
+    let (year, month, day) = civil_from_days((before.when / 86400) as i64);
+    let mut comparison = format!(
+        "since the last run, on {year:04}-{month:02}-{day:02}: {elapsed}, {cpu} CPU time, \
+         {calls} filesystem calls, {peak} peak memory"
+    );
+    // not like for like, so worth a mention.
+    if before.threads != now.threads {
+        comparison += &format!("; it had {} threads", before.threads);
+    }
+    if !entries.is_empty() {
+        comparison += &format!("; {entries} entries now");
+    }
+    comparison
+}
+
+/// The change from `before` to `now` as a percentage of `before`, with
+/// `down` or `up` for which way it went, or `same` if it's under 1%.
+fn change(before: f64, now: f64, down: &str, up: &str, same: &str) -> String {
+    if before == 0.0 {
+        return match now == 0.0 {
+            true => same.to_string(),
+            false => up.to_string(),
+        };
+    }
+    let percent = (now - before) / before * 100.0;
+    match percent.round() as i64 {
+        0 => same.to_string(),
+        p if p < 0 => format!("{}% {down}", -p),
+        p => format!("{p}% {up}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(elapsed: u64, calls: u64, entries: u64) -> Run {
+        Run {
+            when: 1734874620, // 2024-12-22
+            elapsed: Duration::from_millis(elapsed),
+            cpu: Duration::from_millis(elapsed / 2),
+            calls,
+            entries,
+            threads: 8,
+            peak: calls << 20,
+            paths: vec![PathBuf::from("/home"), PathBuf::from("/srv/a b")],
+        }
+    }
+
+    #[test]
+    fn runs_survive_a_round_trip() {
+        let run = run(1500, 300, 100);
+        assert_eq!(Run::parse(&run.line()), Some(run));
+        assert_eq!(Run::parse("1\t2\tnot a number"), None);
+    }
+
+    #[test]
+    fn comparisons_say_which_way_things_went() {
+        let before = run(1000, 400, 100);
+        assert_eq!(
+            compare(&before, &run(880, 388, 100)),
+            "since the last run, on 2024-12-22: \
+             12% faster, 12% less CPU time, 3% fewer filesystem calls, 3% less peak memory"
+        );
+        let mut now = run(1500, 400, 110);
+        now.threads = 16;
+        assert_eq!(
+            compare(&before, &now),
+            "since the last run, on 2024-12-22: \
+             50% slower, 50% more CPU time, as many filesystem calls, the same peak memory; \
+             it had 8 threads; 10% more entries now"
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Confining the whole process to a share of the CPU with a cgroup v2, where
+//! we're allowed to make one: as root, or under a delegated cgroup such as a
+//! systemd service's with Delegate=yes.
+
+use crate::mounts;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Moves this process, with all its threads, into a new cgroup below its
+/// own that gets at most `percent` of one core, e.g. 200 for two. Fails
+/// without cgroup v2, where the cpu controller can't be enabled, or where
+/// the cgroup isn't ours to change. Returns the new cgroup's directory.
+pub fn confine(percent: u32) -> io::Result<PathBuf> {
+    let mounts = mounts::of_types(&["cgroup2".to_string()])?;
+    let Some(root) = mounts.iter().min() else {
+        return Err(io::Error::other("cgroup v2 isn't mounted"));
+    };
+    // v2 is the one hierarchy listed as 0::/path.
+    let own = fs::read_to_string("/proc/self/cgroup")?;
+    let own = own
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| io::Error::other("not in a cgroup v2"))?;
+    let parent = root.join(own.trim_start_matches('/'));
+    tidy(&parent);
+    let control = parent.join("cgroup.subtree_control");
+    if !fs::read_to_string(&control)?
+        .split_whitespace()
+        .any(|c| c == "cpu")
+    {
+        // refused while the parent itself holds processes, unless it's the root.
+        fs::write(&control, "+cpu")?;
+    }
+    let dir = parent.join(format!("adansonia-{}", process::id()));
+    fs::create_dir(&dir)?;
+    let moved = fs::write(
+        dir.join("cpu.max"),
+        format!("{} 100000", u64::from(percent) * 1000), // quota and period in µs
+    )
+    .and_then(|()| fs::write(dir.join("cgroup.procs"), process::id().to_string()));
+    if let Err(e) = moved {
+        let _ = fs::remove_dir(&dir);
+        return Err(e);
+    }
+    Ok(dir)
+}
+
+/// Removes the cgroups of earlier runs, which outlive them. Those still
+/// running can't be removed and are left alone.
+fn tidy(parent: &Path) {
+    for entry in fs::read_dir(parent).into_iter().flatten().flatten() {
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with("adansonia-")
+        {
+            let _ = fs::remove_dir(entry.path());
+        }
+    }
+}
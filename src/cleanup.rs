@@ -0,0 +1,92 @@
+use crate::audit;
+use crate::Info;
+use bytesize::ByteSize;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Writes a shell script that removes `entries`, for environments where
+/// deletions have to be reviewed before they're run. Entries nested inside
+/// another entry are dropped since removing the parent covers them. Returns
+/// how many bytes the script would free.
+pub fn write_script(script: &Path, entries: &[Info]) -> io::Result<u64> {
+    let mut entries: Vec<&Info> = entries.iter().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup_by(|b, a| b.path.starts_with(&a.path));
+    let total = entries.iter().map(|i| i.size).sum();
+
+    let mut out = vec![];
+    writeln!(out, "#!/bin/sh")?;
+    writeln!(
+        out,
+        "# generated by adansonia at {}",
+        audit::timestamp(SystemTime::now())
+    )?;
+    writeln!(
+        out,
+        "# removes {} entries, freeing about {}. review before running.",
+        entries.len(),
+        ByteSize(total)
+    )?;
+    writeln!(out, "set -e")?;
+    for i in entries {
+        out.extend_from_slice(if i.is_dir {
+            b"rm -rf -- "
+        } else {
+            b"rm -f -- "
+        });
+        out.extend(quote(i.path.as_os_str()));
+        writeln!(out, " # {}", ByteSize(i.size))?;
+    }
+
+    fs::write(script, out)?;
+    fs::set_permissions(script, fs::Permissions::from_mode(0o755))?;
+    Ok(total)
+}
+
+/// Writes marked paths to `file`, one per line, so someone else can review
+/// them with `--marks` later.
+pub fn save_marks(file: &Path, marks: &HashSet<PathBuf>) -> io::Result<()> {
+    let mut marks: Vec<&PathBuf> = marks.iter().collect();
+    marks.sort();
+    let mut out = vec![];
+    writeln!(
+        out,
+        "# marked in adansonia at {}",
+        audit::timestamp(SystemTime::now())
+    )?;
+    for path in marks {
+        out.extend_from_slice(path.as_os_str().as_bytes());
+        out.push(b'\n');
+    }
+    fs::write(file, out)
+}
+
+/// Reads paths written by save_marks, skipping blank lines and comments.
+pub fn load_marks(file: &Path) -> io::Result<Vec<PathBuf>> {
+    let text = fs::read(file)?;
+    Ok(text
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .map(|line| PathBuf::from(OsStr::from_bytes(line)))
+        .collect())
+}
+
+/// Single-quotes a path for sh, byte for byte so non-UTF-8 names survive.
+fn quote(s: &OsStr) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for &b in s.as_bytes() {
+        if b == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(b);
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
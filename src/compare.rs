@@ -0,0 +1,154 @@
+use crate::tree::Info;
+use std::cmp::{Ordering, Reverse};
+use std::path::{Path, PathBuf};
+
+/// An entry that differs between two directories, by its path relative to
+/// both. A side is None where the entry isn't there.
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub left: Option<u64>,
+    pub right: Option<u64>,
+}
+
+impl Difference {
+    /// Bytes gained going from left to right, negative if lost.
+    pub fn change(&self) -> i64 {
+        self.right.unwrap_or(0) as i64 - self.left.unwrap_or(0) as i64
+    }
+}
+
+fn rel<'a>(x: &'a Info, base: &Path) -> &'a Path {
+    x.path.strip_prefix(base).unwrap()
+}
+
+/// What's only in `left`, only in `right`, and in both at different sizes,
+/// with both as returned by Tree::subtree(). Below something that's on one
+/// side only, nothing more is listed. An entry that's a file on one side and
+/// a directory on the other counts as one of each. The biggest changes come
+/// first, ties in path order.
+pub fn compare(left: &[Info], right: &[Info]) -> Vec<Difference> {
+    let (Some(l0), Some(r0)) = (left.first(), right.first()) else {
+        return vec![];
+    };
+    // the rest of a subtree, to skip past an entry only one side has.
+    let below = |side: &[Info], i: usize| {
+        side[i + 1..].partition_point(|x| x.path.starts_with(&side[i].path))
+    };
+
+    let mut found = vec![];
+    let (mut i, mut j) = (1, 1);
+    loop {
+        let order = match (left.get(i), right.get(j)) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(l), Some(r)) => match rel(l, &l0.path).cmp(rel(r, &r0.path)) {
+                Ordering::Equal if l.is_dir != r.is_dir => Ordering::Less,
+                order => order,
+            },
+        };
+        match order {
+            Ordering::Less => {
+                let l = &left[i];
+                found.push(Difference {
+                    path: rel(l, &l0.path).to_path_buf(),
+                    is_dir: l.is_dir,
+                    left: Some(l.size),
+                    right: None,
+                });
+                i += 1 + below(left, i);
+            }
+            Ordering::Greater => {
+                let r = &right[j];
+                found.push(Difference {
+                    path: rel(r, &r0.path).to_path_buf(),
+                    is_dir: r.is_dir,
+                    left: None,
+                    right: Some(r.size),
+                });
+                j += 1 + below(right, j);
+            }
+            Ordering::Equal => {
+                let (l, r) = (&left[i], &right[j]);
+                if l.size != r.size {
+                    found.push(Difference {
+                        path: rel(l, &l0.path).to_path_buf(),
+                        is_dir: l.is_dir,
+                        left: Some(l.size),
+                        right: Some(r.size),
+                    });
+                }
+                (i, j) = (i + 1, j + 1);
+            }
+        }
+    }
+    found.sort_by_key(|d| Reverse(d.change().unsigned_abs()));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(path: &str, size: u64, is_dir: bool) -> Info {
+        let path = PathBuf::from(path);
+        Info {
+            depth: path.components().count(),
+            path,
+            size,
+            disk: 0,
+            is_dir,
+            mtime: 0,
+            uid: 0,
+        }
+    }
+
+    fn difference(path: &str, is_dir: bool, left: Option<u64>, right: Option<u64>) -> Difference {
+        Difference {
+            path: PathBuf::from(path),
+            is_dir,
+            left,
+            right,
+        }
+    }
+
+    #[test]
+    fn lists_what_was_added_removed_and_resized() {
+        let left = [
+            info("/b/2023", 160, true),
+            info("/b/2023/gone", 100, true),
+            info("/b/2023/gone/x", 100, false),
+            info("/b/2023/grew", 20, false),
+            info("/b/2023/kind", 30, false),
+            info("/b/2023/same", 10, false),
+        ];
+        let right = [
+            info("/b/2024", 315, true),
+            info("/b/2024/grew", 25, false),
+            info("/b/2024/kind", 80, true),
+            info("/b/2024/kind/y", 80, false),
+            info("/b/2024/new", 200, true),
+            info("/b/2024/new/z", 200, false),
+            info("/b/2024/same", 10, false),
+        ];
+        assert_eq!(
+            compare(&left, &right),
+            [
+                difference("new", true, None, Some(200)),
+                difference("gone", true, Some(100), None),
+                difference("kind", true, None, Some(80)),
+                difference("kind", false, Some(30), None),
+                difference("grew", false, Some(20), Some(25)),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_nothing_between_equal_directories() {
+        let left = [info("/a", 5, true), info("/a/f", 5, false)];
+        let right = [info("/b", 5, true), info("/b/f", 5, false)];
+        assert_eq!(compare(&left, &right), []);
+    }
+}
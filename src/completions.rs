@@ -0,0 +1,315 @@
+//! Shell completion scripts, written from the same definitions clap parses
+//! the command line with, so they keep up as flags are added.
+
+use clap::{Arg, Command, ValueEnum, ValueHint};
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// The completion script for `shell`, for the command line `cmd` describes.
+pub fn script(shell: Shell, cmd: &Command) -> String {
+    let mut cmd = cmd.clone();
+    cmd.build(); // adds --help and --version
+    match shell {
+        Shell::Bash => bash(&cmd),
+        Shell::Zsh => zsh(&cmd),
+        Shell::Fish => fish(&cmd),
+    }
+}
+
+fn options(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments()
+        .filter(|a| !a.is_positional() && !a.is_hide_set())
+}
+
+fn commands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands().filter(|c| !c.is_hide_set())
+}
+
+/// Every spelling of an option, e.g. ["--output-file", "-o"].
+fn flags(arg: &Arg) -> Vec<String> {
+    let long = arg.get_long().map(|l| format!("--{l}"));
+    let short = arg.get_short().map(|s| format!("-{s}"));
+    long.into_iter().chain(short).collect()
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+fn values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_string())
+        .collect()
+}
+
+fn is_path(arg: &Arg) -> bool {
+    matches!(
+        arg.get_value_hint(),
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath
+    )
+}
+
+/// Files worth offering for `arg` when not just any will do: databases
+/// written by export --format sqlite, for query.
+fn pattern(arg: &Arg) -> Option<&'static str> {
+    (arg.get_id() == "database").then_some("*.db")
+}
+
+/// Lists the profiles in the config file, for --profile; in bash and zsh,
+/// and in fish.
+const PROFILES: &str = r#"sed -n 's/^[[:space:]]*\[\(.*\)\][[:space:]]*$/\1/p' "${XDG_CONFIG_HOME:-$HOME/.config}/adansonia/config" 2>/dev/null"#;
+const FISH_PROFILES: &str = r#"sed -n "s/^[[:space:]]*\[\(.*\)\][[:space:]]*\$/\1/p" (set -q XDG_CONFIG_HOME; and echo $XDG_CONFIG_HOME; or echo ~/.config)/adansonia/config 2>/dev/null"#;
+
+fn is_profile(arg: &Arg) -> bool {
+    arg.get_id() == "profile"
+}
+
+/// The first line of an argument's or command's help, for shells that
+/// show descriptions.
+fn summary(help: Option<&clap::builder::StyledStr>) -> String {
+    let help = help.map(|h| h.to_string()).unwrap_or_default();
+    help.lines().next().unwrap_or_default().to_string()
+}
+
+/// `s` quoted for any of the shells here.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn bash(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let subcommands: Vec<&str> = commands(cmd).map(|c| c.get_name()).collect();
+    // each command, with '' for when none has been given.
+    let every: Vec<(String, &Command)> = std::iter::once(("''".to_string(), cmd))
+        .chain(commands(cmd).map(|c| (c.get_name().to_string(), c)))
+        .collect();
+    let mut out = format!("_{name}() {{\n");
+    out.push_str("    local cur=${COMP_WORDS[COMP_CWORD]} prev=${COMP_WORDS[COMP_CWORD-1]}\n");
+    out.push_str("    local command= word\n");
+    out.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+    let _ = writeln!(
+        out,
+        "        case $word in {}) command=$word; break ;; esac",
+        subcommands.join("|")
+    );
+    out.push_str("    done\n");
+
+    // the values of options that take one.
+    out.push_str("    case $command:$prev in\n");
+    for (sub, c) in &every {
+        let sub = sub.trim_matches('\'');
+        for arg in options(c).filter(|a| takes_value(a)) {
+            let cases: Vec<String> = flags(arg).iter().map(|f| format!("{sub}:{f}")).collect();
+            let values = values(arg);
+            let reply = if is_profile(arg) {
+                format!("($(compgen -W \"$({PROFILES})\" -- \"$cur\"))")
+            } else if !values.is_empty() {
+                format!("($(compgen -W {} -- \"$cur\"))", quote(&values.join(" ")))
+            } else if is_path(arg) {
+                "($(compgen -f -- \"$cur\"))".to_string()
+            } else {
+                "()".to_string() // anything goes
+            };
+            let _ = writeln!(
+                out,
+                "        {}) COMPREPLY={reply}; return ;;",
+                cases.join("|")
+            );
+        }
+    }
+    out.push_str("    esac\n");
+
+    out.push_str("    if [[ $cur == -* ]]; then\n        case $command in\n");
+    for (sub, c) in &every {
+        let opts: Vec<String> = options(c).flat_map(flags).collect();
+        let _ = writeln!(
+            out,
+            "            {sub}) COMPREPLY=($(compgen -W {} -- \"$cur\")) ;;",
+            quote(&opts.join(" "))
+        );
+    }
+    out.push_str("        esac\n        return\n    fi\n");
+
+    // otherwise subcommands, and paths where they're wanted.
+    out.push_str("    case $command in\n");
+    for (sub, c) in &every {
+        let files = c.get_positionals().find(|a| is_path(a));
+        let mut words: Vec<String> = c.get_positionals().flat_map(values).collect();
+        if sub == "''" {
+            words.extend(subcommands.iter().map(|s| s.to_string()));
+        }
+        let mut reply = vec![];
+        if !words.is_empty() {
+            reply.push(format!(
+                "compgen -W {} -- \"$cur\"",
+                quote(&words.join(" "))
+            ));
+        }
+        match files.and_then(pattern) {
+            Some(pattern) => reply.push(format!(
+                "compgen -d -- \"$cur\"; compgen -f -X {} -- \"$cur\"",
+                quote(&format!("!{pattern}"))
+            )),
+            None if files.is_some() => reply.push("compgen -f -- \"$cur\"".to_string()),
+            None => {}
+        }
+        let reply = match reply.is_empty() {
+            true => "()".to_string(),
+            false => format!("($({}))", reply.join("; ")),
+        };
+        let _ = writeln!(out, "        {sub}) COMPREPLY={reply} ;;");
+    }
+    out.push_str("    esac\n}\n");
+    let _ = writeln!(out, "complete -o filenames -F _{name} {name}");
+    out
+}
+
+fn zsh(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let spec = |c: &Command| -> Vec<String> {
+        let mut specs = vec![];
+        for arg in options(c) {
+            let help = summary(arg.get_help())
+                .replace('\\', r"\\")
+                .replace('[', r"\[")
+                .replace(']', r"\]")
+                .replace(':', r"\:");
+            let repeat = matches!(
+                arg.get_action(),
+                clap::ArgAction::Append | clap::ArgAction::Count
+            );
+            let value = match (takes_value(arg), values(arg), is_path(arg)) {
+                (false, _, _) => String::new(),
+                _ if is_profile(arg) => format!(":profile:_{name}_profiles"),
+                (true, values, _) if !values.is_empty() => format!(":value:({})", values.join(" ")),
+                (true, _, true) => ":path:_files".to_string(),
+                (true, _, false) => ":value: ".to_string(),
+            };
+            for flag in flags(arg) {
+                let star = if repeat { "*" } else { "" };
+                specs.push(quote(&format!("{star}{flag}[{help}]{value}")));
+            }
+        }
+        for arg in c.get_positionals().filter(|a| !a.is_hide_set()) {
+            let id = arg.get_id().as_str();
+            let values = values(arg);
+            let action = match (pattern(arg), is_path(arg)) {
+                _ if !values.is_empty() => format!("({})", values.join(" ")),
+                (Some(pattern), _) => format!("_files -g \"{pattern}\""),
+                (None, true) => "_files".to_string(),
+                (None, false) => " ".to_string(),
+            };
+            let many = if arg.get_num_args().is_some_and(|n| n.max_values() > 1) {
+                "*"
+            } else {
+                ""
+            };
+            specs.push(quote(&format!("{many}:{id}:{action}")));
+        }
+        specs
+    };
+    let mut out = format!("#compdef {name}\n\n_{name}_profiles() {{\n    local -a profiles\n");
+    let _ = writeln!(out, "    profiles=(${{(f)\"$({PROFILES})\"}})");
+    out.push_str("    _describe profile profiles\n}\n\n");
+    let _ = write!(out, "_{name}() {{\n    local -a commands\n    commands=(\n");
+    for c in commands(cmd) {
+        let about = summary(c.get_about()).replace(':', r"\:");
+        let _ = writeln!(
+            out,
+            "        {}",
+            quote(&format!("{}:{about}", c.get_name()))
+        );
+    }
+    out.push_str("    )\n");
+    out.push_str("    if (( CURRENT > 2 )) && (( ${commands[(I)${words[2]}:*]} )); then\n");
+    out.push_str(
+        "        local command=${words[2]}\n        shift words\n        (( CURRENT-- ))\n",
+    );
+    out.push_str("        case $command in\n");
+    for c in commands(cmd).filter(|c| !spec(c).is_empty()) {
+        let _ = writeln!(
+            out,
+            "            {}) _arguments -s {} ;;",
+            c.get_name(),
+            spec(c).join(" ")
+        );
+    }
+    out.push_str("        esac\n        return\n    fi\n");
+    // paths to scan and subcommands share the first position.
+    let top: Vec<String> = spec(cmd)
+        .into_iter()
+        .filter(|s| !s.starts_with("'*:") && !s.starts_with("':"))
+        .collect();
+    let _ = writeln!(
+        out,
+        "    local state\n    _arguments -s {} '*:path:->paths' && return",
+        top.join(" ")
+    );
+    out.push_str("    (( CURRENT == 2 )) && _describe -t commands command commands\n");
+    out.push_str("    _files\n}\n\n");
+    let _ = writeln!(out, "_{name} \"$@\"");
+    out
+}
+
+fn fish(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let mut out = format!("complete -c {name} -f\n");
+    let mut complete = |condition: &str, c: &Command| {
+        for arg in options(c) {
+            let mut line = format!("complete -c {name} -n {}", quote(condition));
+            if let Some(long) = arg.get_long() {
+                let _ = write!(line, " -l {long}");
+            }
+            if let Some(short) = arg.get_short() {
+                let _ = write!(line, " -s {short}");
+            }
+            if takes_value(arg) {
+                let values = values(arg);
+                if is_profile(arg) {
+                    let _ = write!(line, " -x -a {}", quote(&format!("({FISH_PROFILES})")));
+                } else if !values.is_empty() {
+                    let _ = write!(line, " -x -a {}", quote(&values.join(" ")));
+                } else if is_path(arg) {
+                    line.push_str(" -r -F");
+                } else {
+                    line.push_str(" -x");
+                }
+            }
+            let _ = writeln!(out, "{line} -d {}", quote(&summary(arg.get_help())));
+        }
+        for arg in c.get_positionals() {
+            let values = values(arg);
+            let files = match pattern(arg) {
+                _ if !values.is_empty() => format!("-a {}", quote(&values.join(" "))),
+                Some(pattern) => format!(
+                    "-a {}",
+                    quote(&format!("(__fish_complete_suffix {})", &pattern[1..]))
+                ),
+                None if is_path(arg) => "-F".to_string(),
+                None => continue,
+            };
+            let _ = writeln!(out, "complete -c {name} -n {} {files}", quote(condition));
+        }
+    };
+    complete("__fish_use_subcommand", cmd);
+    for c in commands(cmd) {
+        complete(&format!("__fish_seen_subcommand_from {}", c.get_name()), c);
+    }
+    for c in commands(cmd) {
+        let _ = writeln!(
+            out,
+            "complete -c {name} -n __fish_use_subcommand -a {} -d {}",
+            c.get_name(),
+            quote(&summary(c.get_about()))
+        );
+    }
+    out
+}
@@ -0,0 +1,115 @@
+use adansonia::tree::Info;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+// files smaller than this aren't worth sampling; a directory's small files are
+// assumed to compress like its big ones.
+const MIN_SIZE: u64 = 1 << 20;
+// at most this many of a directory's largest files are sampled, each in a few
+// evenly spaced chunks.
+const FILES: usize = 16;
+const CHUNKS: u64 = 4;
+const CHUNK: usize = 64 << 10;
+
+/// The files of a subtree worth sampling, largest first.
+pub fn candidates(subtree: &[Info]) -> Vec<(PathBuf, u64)> {
+    let mut files: Vec<_> = subtree
+        .iter()
+        .filter(|x| !x.is_dir && x.size >= MIN_SIZE)
+        .map(|x| (x.path.clone(), x.size))
+        .collect();
+    files.sort_by_key(|&(_, size)| std::cmp::Reverse(size));
+    files.truncate(FILES);
+    files
+}
+
+/// Estimates the fraction of bytes that compression would save across
+/// `files`, weighting each by its size. None if nothing could be read.
+pub fn estimate(files: &[(PathBuf, u64)]) -> Option<f64> {
+    let (mut saved, mut total) = (0.0, 0.0);
+    for (path, size) in files {
+        let Ok(data) = sample(path, *size) else {
+            continue;
+        };
+        if data.is_empty() {
+            continue;
+        }
+        saved += (1.0 - ratio(&data)).max(0.0) * *size as f64;
+        total += *size as f64;
+    }
+    (total > 0.0).then(|| saved / total)
+}
+
+fn sample(path: &Path, size: u64) -> io::Result<Vec<u8>> {
+    let file = File::open(path)?;
+    let mut data = vec![];
+    for i in 0..CHUNKS {
+        let mut chunk = vec![0; CHUNK];
+        let n = file.read_at(&mut chunk, size / CHUNKS * i)?;
+        data.extend_from_slice(&chunk[..n]);
+    }
+    Ok(data)
+}
+
+/// Compressed size over original size, from a fast zstd pass if zstd is
+/// installed, otherwise from the entropy of the bytes.
+fn ratio(data: &[u8]) -> f64 {
+    zstd(data).unwrap_or_else(|_| entropy(data) / 8.0)
+}
+
+fn zstd(data: &[u8]) -> io::Result<f64> {
+    let mut child = Command::new("zstd")
+        .args(["-1", "-q", "-c"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let input = data.to_vec();
+    // feed it from another thread so a full stdout pipe can't deadlock us.
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    if !output.status.success() {
+        return Err(io::Error::other("zstd failed"));
+    }
+    Ok(output.stdout.len() as f64 / data.len() as f64)
+}
+
+/// Compresses `path` in place with gzip, as logrotate would, returning the
+/// compressed file's path.
+pub fn gzip(path: &Path) -> io::Result<PathBuf> {
+    let output = Command::new("gzip")
+        .arg("--")
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(".gz");
+    Ok(compressed.into())
+}
+
+/// Shannon entropy in bits per byte.
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let n = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / n;
+            -p * p.log2()
+        })
+        .sum()
+}
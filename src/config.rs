@@ -0,0 +1,79 @@
+//! Named profiles of options, kept in a config file so scans of very
+//! different places don't need their flags typed out each time:
+//!
+//! ```text
+//! # the filer is slow and has snapshots everywhere
+//! [nas]
+//! threads = 4
+//! gentle = true
+//! exclude = .snapshot/
+//! exclude = /backup
+//! ```
+//!
+//! Each key is a long option without its dashes, and is given once per
+//! value for options that can be repeated. `true` turns a flag on and
+//! `false` leaves it off.
+
+use std::collections::BTreeMap;
+
+/// The profiles in a config file, by name, each as the command line
+/// arguments it stands for.
+pub fn parse(text: &str) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let mut profiles: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut current = None;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim().to_string();
+            profiles.entry(name.clone()).or_default();
+            current = Some(name);
+            continue;
+        }
+        let Some(name) = &current else {
+            return Err(format!(
+                "line {}: options need a [profile] above them",
+                n + 1
+            ));
+        };
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected option = value", n + 1));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let args = profiles.get_mut(name).unwrap();
+        match value {
+            "true" => args.push(format!("--{key}")),
+            "false" => {}
+            _ => args.push(format!("--{key}={value}")),
+        }
+    }
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_become_arguments() {
+        let profiles = parse(
+            "# comment\n[nas]\nthreads = 4\ngentle = true\nxattrs = false\n\
+             exclude = .snapshot/\nexclude = /backup\n\n[laptop]\nexclude-caches=true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            profiles["nas"],
+            [
+                "--threads=4",
+                "--gentle",
+                "--exclude=.snapshot/",
+                "--exclude=/backup"
+            ]
+        );
+        assert_eq!(profiles["laptop"], ["--exclude-caches"]);
+        assert!(parse("threads = 4\n").is_err());
+        assert!(parse("[nas]\nthreads\n").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use adansonia::scan::Control;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A control socket being served, removed again when dropped.
+pub struct Listening(PathBuf);
+
+impl Drop for Listening {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Serves `control` on a Unix socket at `path`. Each line a client sends is a
+/// JSON object naming a command:
+///
+///     {"command": "status"}   reply with the scan's progress
+///     {"command": "watch"}    reply with progress every 100ms until it's done
+///     {"command": "pause"}    pause, resume or cancel the scan, then reply
+///     {"command": "resume"}   with its progress
+///     {"command": "cancel"}
+///
+/// Progress is one line like
+/// `{"state": "running", "found": 1200, "errors": 0, "elapsed_ms": 350}`,
+/// where the state is one of running, paused, cancelled or done.
+pub fn serve(path: &Path, control: Control) -> io::Result<Listening> {
+    // a socket left behind by an earlier run would make bind fail.
+    if path
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_socket())
+    {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let started = Instant::now();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let control = control.clone();
+            thread::spawn(move || client(stream, &control, started));
+        }
+    });
+    Ok(Listening(path.to_path_buf()))
+}
+
+fn client(stream: UnixStream, control: &Control, started: Instant) -> io::Result<()> {
+    let mut out = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match command(&line).as_deref() {
+            Some("status") => {}
+            Some("pause") => control.pause(),
+            Some("resume") => control.resume(),
+            Some("cancel") => control.cancel(),
+            Some("watch") => {
+                while !control.is_done() {
+                    writeln!(out, "{}", progress(control, started))?;
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+            Some(other) => {
+                writeln!(out, r#"{{"error": "unknown command {}"}}"#, escape(other))?;
+                continue;
+            }
+            None => {
+                writeln!(out, r#"{{"error": "expected {{\"command\": ...}}"}}"#)?;
+                continue;
+            }
+        }
+        writeln!(out, "{}", progress(control, started))?;
+    }
+    Ok(())
+}
+
+fn progress(control: &Control, started: Instant) -> String {
+    let state = match () {
+        _ if control.is_done() => "done",
+        _ if control.is_cancelled() => "cancelled",
+        _ if control.is_paused() => "paused",
+        _ => "running",
+    };
+    format!(
+        r#"{{"state": "{}", "found": {}, "errors": {}, "elapsed_ms": {}}}"#,
+        state,
+        control.found(),
+        control.errors(),
+        started.elapsed().as_millis()
+    )
+}
+
+/// The "command" string of a JSON object, without a full JSON parser: the
+/// protocol never needs more than this one field.
+fn command(line: &str) -> Option<String> {
+    let rest = &line[line.find(r#""command""#)? + r#""command""#.len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let rest = rest.strip_prefix('"')?;
+    Some(rest[..rest.find('"')?].to_string())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
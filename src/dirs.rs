@@ -0,0 +1,72 @@
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Where history and other state that should survive between sessions lives,
+/// following the XDG base directory spec.
+///
+/// Under `sudo` without -H, HOME is still the invoking user's, and state
+/// written there as root would leave them files they can't update. So
+/// a base directory belonging to someone else is passed over for our own
+/// home from the password database.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .filter(|base| ours(base))
+        .or_else(|| own_home().map(|home| home.join(".local/state")))?;
+    Some(base.join("adansonia"))
+}
+
+/// The home trash can of the freedesktop.org trash spec, which things
+/// deleted from the same filesystem as it are moved to. Passed over like
+/// state_dir()'s when it belongs to someone else.
+pub fn trash_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .filter(|base| ours(base))
+        .or_else(|| own_home().map(|home| home.join(".local/share")))?;
+    Some(base.join("Trash"))
+}
+
+/// Where the config file is, following the XDG base directory spec.
+pub fn config_file() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("adansonia/config"))
+}
+
+/// Whether the closest existing ancestor of `path` is owned by us.
+fn ours(path: &Path) -> bool {
+    let me = unsafe { libc::geteuid() };
+    path.ancestors()
+        .find_map(|p| fs::metadata(p).ok())
+        .is_some_and(|m| m.uid() == me)
+}
+
+/// The effective user's home directory, from /etc/passwd.
+fn own_home() -> Option<PathBuf> {
+    let me = unsafe { libc::geteuid() }.to_string();
+    let passwd = fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields[..] {
+            [_, _, uid, _, _, home, ..] if uid == me => Some(PathBuf::from(home)),
+            _ => None,
+        }
+    })
+}
+
+/// Takes an exclusive lock on `file`, waiting for other sessions to let
+/// go of it. It's released when the file is closed.
+pub fn lock(file: &File) -> io::Result<()> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
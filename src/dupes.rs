@@ -0,0 +1,59 @@
+use rayon::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+// how much of each file is hashed to weed out most same-size files cheaply.
+const PREFIX: u64 = 64 << 10;
+
+/// Groups of files with identical contents. Files are first grouped by size,
+/// then by a hash of their first block, and only then hashed in full, so most
+/// files are never read at all. Files that can't be read are left out.
+pub fn find(files: Vec<(PathBuf, u64)>) -> Vec<Vec<PathBuf>> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in files.into_iter().filter(|&(_, size)| size > 0) {
+        by_size.entry(size).or_default().push(path);
+    }
+    by_size
+        .into_par_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map_iter(|(size, paths)| {
+            let candidates = refine(paths, |p| hash(p, PREFIX));
+            if size <= PREFIX {
+                candidates // the prefix was the whole file.
+            } else {
+                candidates
+                    .into_iter()
+                    .flat_map(|group| refine(group, |p| hash(p, u64::MAX)))
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+/// Splits `paths` by `key`, keeping only groups of two or more.
+fn refine(paths: Vec<PathBuf>, key: impl Fn(&Path) -> io::Result<u64>) -> Vec<Vec<PathBuf>> {
+    let mut groups: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for path in paths {
+        if let Ok(k) = key(&path) {
+            groups.entry(k).or_default().push(path);
+        }
+    }
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+fn hash(path: &Path, limit: u64) -> io::Result<u64> {
+    let mut file = File::open(path)?.take(limit);
+    let mut hasher = DefaultHasher::new();
+    let mut buf = vec![0; 64 << 10];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&buf[..n]);
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can stop adansonia. Problems with individual entries during
+/// a scan aren't errors here; they're counted and reported alongside the
+/// results instead.
+#[derive(Debug)]
+pub enum Error {
+    /// The directory to scan is missing or can't be read.
+    Root(PathBuf, io::Error),
+    /// Setting up, drawing to or reading from the terminal failed.
+    Terminal(io::Error),
+    /// Any other I/O failure.
+    Io(io::Error),
+    /// Something the caller asked to have checked wasn't so, like a
+    /// --fail-if limit or a manifest's checksums.
+    Check(String),
+}
+
+/// Exit statuses, which scripts can rely on: everything went fine,
+pub const EXIT_OK: i32 = 0;
+/// some entries couldn't be read, so the results may be short,
+pub const EXIT_SCAN_ERRORS: i32 = 1;
+/// a check failed, see Error::Check,
+pub const EXIT_CHECK_FAILED: i32 = 2;
+/// or adansonia couldn't do what it was asked at all.
+pub const EXIT_FATAL: i32 = 3;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Root(path, e) => write!(f, "can't scan {}: {e}", path.display()),
+            Error::Terminal(e) => write!(f, "terminal error: {e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Check(what) => write!(f, "{what}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Root(_, e) | Error::Terminal(e) | Error::Io(e) => Some(e),
+            Error::Check(_) => None,
+        }
+    }
+}
+
+impl Error {
+    /// What to exit with when this stops adansonia.
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            Error::Check(_) => EXIT_CHECK_FAILED,
+            _ => EXIT_FATAL,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
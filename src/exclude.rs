@@ -0,0 +1,193 @@
+//! Exclude lists in the format of rsync's `--exclude-from`, which tar's
+//! lists mostly are too, so existing backup configuration can be reused.
+
+use std::path::Path;
+
+/// Rules read from exclude files, checked in order with the first match
+/// deciding.
+#[derive(Clone, Debug, Default)]
+pub struct Excludes {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    pattern: String,
+    include: bool,    // a "+ " rule, which keeps what later rules would exclude
+    dir_only: bool,   // the pattern ended in a slash
+    anchored: bool,   // it started with one, so it matches from the root only
+    whole_path: bool, // it has a slash or ** in it, so it matches more than the name
+}
+
+impl Excludes {
+    /// Parses an exclude file. Each line is a pattern, optionally prefixed
+    /// with "- " to exclude or "+ " to include; blank lines and lines
+    /// starting with '#' or ';' are ignored. Patterns work like rsync's:
+    /// `*` matches within a name, `**` across slashes, `?` and `[...]` a
+    /// single character, a leading slash anchors the pattern to the root
+    /// being scanned and a trailing one restricts it to directories.
+    pub fn parse(text: &str) -> Excludes {
+        let mut rules = vec![];
+        for line in text.lines() {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            let (include, pattern) = match line.split_at_checked(2) {
+                Some(("+ ", rest)) => (true, rest),
+                Some(("- ", rest)) => (false, rest),
+                _ => (false, line),
+            };
+            let (dir_only, pattern) = match pattern.strip_suffix('/') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, pattern),
+            };
+            let (anchored, pattern) = match pattern.strip_prefix('/') {
+                Some(rest) => (true, rest),
+                None => (false, pattern),
+            };
+            // "dir/***" is the directory and everything in it.
+            let patterns = match pattern.strip_suffix("/***") {
+                Some(dir) => vec![dir.to_string(), format!("{dir}/**")],
+                None => vec![pattern.to_string()],
+            };
+            for pattern in patterns {
+                rules.push(Rule {
+                    whole_path: pattern.contains('/') || pattern.contains("**"),
+                    pattern,
+                    include,
+                    dir_only,
+                    anchored,
+                });
+            }
+        }
+        Excludes { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether `path`, relative to the root being scanned, is excluded.
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        let path = path.to_string_lossy();
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let matched = if rule.anchored {
+                wildmatch(rule.pattern.as_bytes(), path.as_bytes())
+            } else if rule.whole_path {
+                // unanchored, it can match any trailing run of components.
+                let starts = path.match_indices('/').map(|(i, _)| i + 1);
+                std::iter::once(0)
+                    .chain(starts)
+                    .any(|i| wildmatch(rule.pattern.as_bytes(), path[i..].as_bytes()))
+            } else {
+                wildmatch(rule.pattern.as_bytes(), name.as_bytes())
+            };
+            if matched {
+                return !rule.include;
+            }
+        }
+        false
+    }
+}
+
+/// Matches `text` against a shell-style pattern, where `*` and `?` don't
+/// match a slash but `**` does.
+pub(crate) fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| wildmatch(rest, &text[i..])),
+        [b'*', rest @ ..] => {
+            let end = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=end).any(|i| wildmatch(rest, &text[i..]))
+        }
+        [b'?', rest @ ..] => matches!(text, [c, ..] if *c != b'/') && wildmatch(rest, &text[1..]),
+        [b'[', rest @ ..] => match (class(rest, text.first().copied()), text) {
+            (Some((true, len)), [_, text @ ..]) => wildmatch(&rest[len..], text),
+            (Some(_), _) => false,
+            // an unclosed bracket is just a bracket.
+            (None, [b'[', text @ ..]) => wildmatch(rest, text),
+            (None, _) => false,
+        },
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => {
+            matches!(text, [t, ..] if t == c) && wildmatch(rest, &text[1..])
+        }
+    }
+}
+
+/// Whether `c` is in the bracket expression at the start of `pattern`, just
+/// after its '[', and the length of the expression up to and including the
+/// closing ']'. None if it's never closed.
+fn class(pattern: &[u8], c: Option<u8>) -> Option<(bool, usize)> {
+    let (negated, mut i) = match pattern.first() {
+        Some(b'!' | b'^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut found = false;
+    let start = i;
+    while i < pattern.len() {
+        match pattern[i..] {
+            [b']', ..] if i > start => {
+                let found = found != negated && c.is_some_and(|c| c != b'/');
+                return Some((found, i + 1));
+            }
+            [lo, b'-', hi, ..] if hi != b']' => {
+                found |= c.is_some_and(|c| (lo..=hi).contains(&c));
+                i += 3;
+            }
+            [x, ..] => {
+                found |= c == Some(x);
+                i += 1;
+            }
+            [] => unreachable!(),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excluded(rules: &str, path: &str, is_dir: bool) -> bool {
+        Excludes::parse(rules).excludes(Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildmatch(b"*.o", b"main.o"));
+        assert!(!wildmatch(b"*.o", b"src/main.o"));
+        assert!(wildmatch(b"src/**.o", b"src/a/b/main.o"));
+        assert!(wildmatch(b"v?.[0-9]", b"v1.7"));
+        assert!(!wildmatch(b"v?.[!0-9]", b"v1.7"));
+        assert!(wildmatch(b"\\*", b"*"));
+        assert!(!wildmatch(b"\\*", b"x"));
+        assert!(wildmatch(b"a[b", b"a[b"));
+    }
+
+    #[test]
+    fn names_paths_anchors_and_directories() {
+        // plain names match at any depth.
+        assert!(excluded("*.tmp", "a/b/c.tmp", false));
+        // a slash makes it match trailing components, a leading one the root.
+        assert!(excluded("build/out", "x/build/out", true));
+        assert!(!excluded("build/out", "x/rebuild/out", true));
+        assert!(excluded("/build", "build", true));
+        assert!(!excluded("/build", "x/build", true));
+        // a trailing slash is for directories only.
+        assert!(excluded("cache/", "a/cache", true));
+        assert!(!excluded("cache/", "a/cache", false));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = "# keep sources\n; and comments\n\n+ *.rs\n- *\n";
+        assert!(!excluded(rules, "main.rs", false));
+        assert!(excluded(rules, "main.o", false));
+        assert!(excluded("- /data/***", "data/x/y", false));
+        assert!(excluded("- /data/***", "data", true));
+    }
+}
@@ -0,0 +1,227 @@
+//! Fuzzy matching of paths in the style of fzf, for finding an entry
+//! anywhere in a scan from a few letters of its name, and searching with
+//! it in the background so typing stays responsive over millions of them.
+
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// What's been typed, ready to score candidates against. Like fzf's smart
+/// case, it's only case sensitive if it has capitals in it.
+pub struct Pattern {
+    chars: Vec<char>,
+    sensitive: bool,
+}
+
+impl Pattern {
+    pub fn new(text: &str) -> Pattern {
+        let sensitive = text.chars().any(char::is_uppercase);
+        let mut pattern = Pattern {
+            chars: vec![],
+            sensitive,
+        };
+        pattern.chars = text.chars().map(|c| pattern.fold(c)).collect();
+        pattern
+    }
+
+    fn fold(&self, c: char) -> char {
+        match self.sensitive {
+            true => c,
+            false if c.is_ascii() => c.to_ascii_lowercase(),
+            false => c.to_lowercase().next().unwrap_or(c),
+        }
+    }
+
+    /// How well `candidate` matches, higher being better, or None if the
+    /// pattern's characters aren't all in it in order. Matches at the start
+    /// of a name or word, runs of consecutive characters and matches within
+    /// the last component score higher; gaps score lower.
+    pub fn score(&self, candidate: &str) -> Option<i64> {
+        if self.chars.is_empty() {
+            return Some(0);
+        }
+        // the first place the whole pattern has been seen by...
+        let mut p = 0;
+        let mut end = None;
+        for (i, c) in candidate.char_indices() {
+            if self.fold(c) == self.chars[p] {
+                p += 1;
+                if p == self.chars.len() {
+                    end = Some(i + c.len_utf8());
+                    break;
+                }
+            }
+        }
+        let end = end?;
+        // ...and the shortest stretch ending there that holds it.
+        let mut p = self.chars.len();
+        let mut start = 0;
+        for (i, c) in candidate[..end].char_indices().rev() {
+            if self.fold(c) == self.chars[p - 1] {
+                p -= 1;
+                if p == 0 {
+                    start = i;
+                    break;
+                }
+            }
+        }
+
+        let mut score = 0;
+        let mut prev = candidate[..start].chars().next_back();
+        let (mut p, mut consecutive, mut gap) = (0, false, false);
+        for c in candidate[start..end].chars() {
+            if p < self.chars.len() && self.fold(c) == self.chars[p] {
+                score += 16;
+                score += match prev {
+                    None | Some('/') => 10,
+                    Some('-' | '_' | '.' | ' ') => 8,
+                    Some(b) if b.is_lowercase() && c.is_uppercase() => 7,
+                    _ => 0,
+                };
+                if consecutive {
+                    score += 4;
+                }
+                (p, consecutive, gap) = (p + 1, true, false);
+            } else {
+                score -= if gap { 1 } else { 3 };
+                (consecutive, gap) = (false, true);
+            }
+            prev = Some(c);
+        }
+        if !candidate[start..].contains('/') {
+            score += 12;
+        }
+        Some(score)
+    }
+}
+
+/// The outcome of a search.
+pub struct Matches {
+    pub pattern: String,
+    /// Every candidate that matched, by index, in order. A search for a
+    /// longer pattern only needs to look at these.
+    pub all: Arc<Vec<usize>>,
+    /// The best of them, best first, up to the limit asked for.
+    pub best: Vec<usize>,
+}
+
+/// A search running on another thread, given up on if dropped before it's
+/// done.
+pub struct Search {
+    cancelled: Arc<AtomicBool>,
+    rx: mpsc::Receiver<Matches>,
+}
+
+impl Search {
+    /// Starts looking for `pattern` in `candidates`, or only those of them
+    /// listed in `within`, keeping the best `limit`. Ties go to the shorter
+    /// candidate.
+    pub fn start(
+        pattern: &str,
+        candidates: Arc<Vec<PathBuf>>,
+        within: Option<Arc<Vec<usize>>>,
+        limit: usize,
+    ) -> Search {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let (text, stop) = (pattern.to_string(), cancelled.clone());
+        thread::spawn(move || {
+            let pattern = Pattern::new(&text);
+            let scored = |i: usize| {
+                if stop.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let candidate = candidates[i].to_string_lossy();
+                let score = pattern.score(&candidate)?;
+                Some((score, candidate.len(), i))
+            };
+            let mut scored: Vec<(i64, usize, usize)> = match &within {
+                Some(within) => within.par_iter().filter_map(|&i| scored(i)).collect(),
+                None => (0..candidates.len())
+                    .into_par_iter()
+                    .filter_map(scored)
+                    .collect(),
+            };
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let all = Arc::new(scored.iter().map(|&(_, _, i)| i).collect());
+            let key = |&(score, len, i): &(i64, usize, usize)| (Reverse(score), len, i);
+            if scored.len() > limit && limit > 0 {
+                scored.select_nth_unstable_by_key(limit - 1, key);
+            }
+            scored.truncate(limit);
+            scored.sort_unstable_by_key(key);
+            let best = scored.into_iter().map(|(_, _, i)| i).collect();
+            let _ = tx.send(Matches {
+                pattern: text,
+                all,
+                best,
+            });
+        });
+        Search { cancelled, rx }
+    }
+
+    /// The matches, once the search is done.
+    pub fn done(&self) -> Option<Matches> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Waits for the search to finish.
+    pub fn wait(&self) -> Option<Matches> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_word_starts_score_higher() {
+        let pattern = Pattern::new("main");
+        let name = pattern.score("src/main.rs").unwrap();
+        let scattered = pattern.score("src/mail/index.rs").unwrap();
+        let directory = pattern.score("main/src/lib.rs").unwrap();
+        assert!(name > scattered, "{name} {scattered}");
+        assert!(name > directory, "{name} {directory}");
+        assert_eq!(pattern.score("src/lib.rs"), None);
+        assert_eq!(Pattern::new("").score("anything"), Some(0));
+
+        // smart case: capitals have to match, lowercase matches either.
+        assert!(Pattern::new("readme").score("README.md").is_some());
+        assert!(Pattern::new("ReadMe").score("README.md").is_none());
+        assert!(Pattern::new("\u{e9}t\u{e9}")
+            .score("\u{c9}T\u{c9}.txt")
+            .is_some());
+    }
+
+    #[test]
+    fn searches_keep_the_best_and_can_be_narrowed() {
+        let candidates: Vec<PathBuf> = ["a/cargo.toml", "cargo.lock", "src/cache.rs", "docs"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let candidates = Arc::new(candidates);
+        let matches = Search::start("carg", candidates.clone(), None, 1)
+            .wait()
+            .unwrap();
+        assert_eq!(*matches.all, [0, 1]);
+        assert_eq!(matches.best, [1]);
+
+        let narrowed = Search::start("cargot", candidates, Some(matches.all), 10)
+            .wait()
+            .unwrap();
+        assert_eq!(narrowed.pattern, "cargot");
+        assert_eq!(narrowed.best, [0]);
+    }
+}
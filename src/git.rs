@@ -0,0 +1,84 @@
+use adansonia::tree::Tree;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// past these, git's own housekeeping is overdue. `git gc --auto` waits for
+// 6700 loose objects and 50 packs, which is a lot of wasted space already.
+const LOOSE: usize = 1000;
+const PACKS: usize = 10;
+
+/// A git repository found in the scan.
+pub struct Repo {
+    pub path: PathBuf, // the working tree, holding .git
+    pub work: u64,     // the size of everything but .git
+    pub git: u64,
+    pub packs: usize,
+    pub loose: usize, // objects not yet packed
+}
+
+impl Repo {
+    /// The maintenance worth running, if any.
+    pub fn advice(&self) -> Option<&'static str> {
+        (self.loose >= LOOSE || self.packs >= PACKS).then_some("git gc")
+    }
+}
+
+/// The git repositories under `dir`, those with the biggest .git first.
+pub fn find(tree: &Tree, dir: &Path) -> Vec<Repo> {
+    let mut repos: Vec<Repo> = tree
+        .subtree(dir)
+        .iter()
+        .filter(|i| i.is_dir && i.path.file_name() == Some(OsStr::new(".git")))
+        .map(|git| {
+            let objects = git.path.join("objects");
+            let packs = tree
+                .children(&objects.join("pack"))
+                .filter(|i| i.path.extension() == Some(OsStr::new("pack")))
+                .count();
+            // loose objects live in directories named by their first byte.
+            let loose = tree
+                .children(&objects)
+                .filter(|i| i.is_dir && is_fan_out(&i.path))
+                .map(|i| tree.children(&i.path).count())
+                .sum();
+            let path = git.path.parent().unwrap().to_path_buf();
+            Repo {
+                work: tree.size(&path).saturating_sub(git.size),
+                git: git.size,
+                path,
+                packs,
+                loose,
+            }
+        })
+        .collect();
+    repos.sort_by_key(|r| std::cmp::Reverse(r.git));
+    repos
+}
+
+fn is_fan_out(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().as_encoded_bytes();
+    name.len() == 2 && name.iter().all(u8::is_ascii_hexdigit)
+}
+
+/// Runs `command`, e.g. "git gc", in the repository, returning the first
+/// line of complaint if it fails.
+pub fn run(repo: &Path, command: &str) -> io::Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(command.split_whitespace().skip(1))
+        .arg("--quiet")
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let line = stderr
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("failed");
+        return Err(io::Error::other(line.trim().to_string()));
+    }
+    Ok(())
+}
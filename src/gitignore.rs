@@ -0,0 +1,205 @@
+//! The ignore files of version control, read directory by directory as a
+//! scan goes so that what a repository ignores, build output and the like,
+//! can be left out of it.
+
+use crate::exclude::wildmatch;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The patterns from one ignore file, relative to the directory it's in.
+/// Unlike exclude lists, the last pattern that matches decides.
+#[derive(Debug, Default)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    patterns: Vec<String>, // alternatives, for each way a "/**/" can match
+    negated: bool,         // a "!" rule, which keeps what earlier ones ignore
+    dir_only: bool,        // the pattern ended in a slash
+    placement: Placement,
+}
+
+#[derive(Debug, PartialEq)]
+enum Placement {
+    Name,     // no slash, so it matches a name at any depth
+    Anchored, // a slash, so it matches from the ignore file's directory
+    Anywhere, // a leading "**/", so it matches trailing components at any depth
+}
+
+impl Gitignore {
+    /// Parses an ignore file as gitignore(5) describes: one pattern per
+    /// line, '#' for comments, '!' to negate, a trailing slash for
+    /// directories only and a slash anywhere else to anchor the pattern.
+    pub fn parse(text: &str) -> Gitignore {
+        let mut rules = vec![];
+        for line in text.lines() {
+            let raw = line.strip_suffix('\r').unwrap_or(line);
+            // trailing spaces don't count unless escaped.
+            let mut line = raw.trim_end_matches(' ').to_string();
+            if line.ends_with('\\') && line.len() < raw.len() {
+                line.push(' ');
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.as_str()),
+            };
+            let (dir_only, pattern) = match pattern.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, pattern),
+            };
+            let (placement, pattern) = if let Some(rest) = pattern.strip_prefix("**/") {
+                match rest.contains('/') {
+                    true => (Placement::Anywhere, rest),
+                    false => (Placement::Name, rest),
+                }
+            } else if pattern.contains('/') {
+                (Placement::Anchored, pattern.trim_start_matches('/'))
+            } else {
+                (Placement::Name, pattern)
+            };
+            if pattern.is_empty() {
+                continue;
+            }
+            rules.push(Rule {
+                patterns: alternatives(pattern),
+                negated,
+                dir_only,
+                placement,
+            });
+        }
+        Gitignore { rules }
+    }
+
+    /// Whether `path`, relative to the ignore file's directory, is ignored
+    /// (true) or kept by a negated pattern (false). None if nothing matches.
+    pub fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let path = path.to_string_lossy();
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        let starts = std::iter::once(0).chain(path.match_indices('/').map(|(i, _)| i + 1));
+        let rule = self.rules.iter().rev().find(|rule| {
+            if rule.dir_only && !is_dir {
+                return false;
+            }
+            rule.patterns.iter().any(|p| match rule.placement {
+                Placement::Name => wildmatch(p.as_bytes(), name.as_bytes()),
+                Placement::Anchored => wildmatch(p.as_bytes(), path.as_bytes()),
+                Placement::Anywhere => starts
+                    .clone()
+                    .any(|i| wildmatch(p.as_bytes(), path[i..].as_bytes())),
+            })
+        })?;
+        Some(!rule.negated)
+    }
+}
+
+/// `pattern`, and the patterns it's equivalent to with each "/**/" in it
+/// matching no directories at all, which wildmatch() alone can't do.
+fn alternatives(pattern: &str) -> Vec<String> {
+    let mut patterns = vec![pattern.to_string()];
+    let mut i = 0;
+    while i < patterns.len() {
+        if let Some(at) = patterns[i].find("/**/") {
+            let p = &patterns[i];
+            patterns.push(format!("{}{}", &p[..at], &p[at + 3..]));
+        }
+        i += 1;
+    }
+    patterns
+}
+
+/// The ignore files in effect in a directory, outermost first, each with
+/// the directory it belongs to.
+#[derive(Clone, Debug, Default)]
+pub struct Ignores {
+    files: Vec<(PathBuf, Arc<Gitignore>)>,
+}
+
+impl Ignores {
+    /// The ignore files in effect in `dir`, given those of its parent: a
+    /// repository's .git/info/exclude, then .gitignore, then the .ignore
+    /// files other tools read, each overriding the ones before. A
+    /// repository nested in another starts afresh. None if `dir` has none
+    /// of its own, so the parent's still hold.
+    pub fn enter(&self, dir: &Path) -> Option<Ignores> {
+        let repository = dir.join(".git").exists();
+        let mut ignores = match repository {
+            true => Ignores::default(),
+            false => self.clone(),
+        };
+        let names = [".git/info/exclude", ".gitignore", ".ignore"];
+        for name in names {
+            if let Ok(text) = fs::read_to_string(dir.join(name)) {
+                let file = Arc::new(Gitignore::parse(&text));
+                ignores.files.push((dir.to_path_buf(), file));
+            }
+        }
+        (repository || ignores.files.len() > self.files.len()).then_some(ignores)
+    }
+
+    /// Those in effect in `dir`, starting from the top of the repository
+    /// it's in, for scans that start partway down one.
+    pub fn above(dir: &Path) -> Ignores {
+        let Some(top) = dir.ancestors().find(|d| d.join(".git").exists()) else {
+            return Ignores::default();
+        };
+        let mut ignores = Ignores::default();
+        let below: Vec<&Path> = dir.ancestors().take_while(|&d| d != top).collect();
+        for dir in std::iter::once(top).chain(below.into_iter().rev()) {
+            if let Some(entered) = ignores.enter(dir) {
+                ignores = entered;
+            }
+        }
+        ignores
+    }
+
+    /// Whether `path` is ignored, the innermost ignore file to say either
+    /// way deciding.
+    pub fn ignores(&self, path: &Path, is_dir: bool) -> bool {
+        self.files
+            .iter()
+            .rev()
+            .find_map(|(dir, file)| file.matches(path.strip_prefix(dir).ok()?, is_dir))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(rules: &str, path: &str, is_dir: bool) -> Option<bool> {
+        Gitignore::parse(rules).matches(Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn last_matching_pattern_wins() {
+        let rules = "# logs\n*.log\n!keep.log\n";
+        assert_eq!(ignored(rules, "a/debug.log", false), Some(true));
+        assert_eq!(ignored(rules, "a/keep.log", false), Some(false));
+        assert_eq!(ignored(rules, "a/main.rs", false), None);
+        assert_eq!(ignored("\\#notes\n", "#notes", false), Some(true));
+        assert_eq!(ignored("trailing\\ \n", "trailing ", false), Some(true));
+    }
+
+    #[test]
+    fn slashes_anchor_and_double_stars_span_directories() {
+        // a slash anywhere but the end anchors to the file's directory.
+        assert_eq!(ignored("/build\n", "build", true), Some(true));
+        assert_eq!(ignored("/build\n", "src/build", true), None);
+        assert_eq!(ignored("doc/*.html\n", "doc/index.html", false), Some(true));
+        assert_eq!(ignored("doc/*.html\n", "x/doc/index.html", false), None);
+        // only a trailing one, and it's for directories at any depth.
+        assert_eq!(ignored("out/\n", "a/out", true), Some(true));
+        assert_eq!(ignored("out/\n", "a/out", false), None);
+        assert_eq!(ignored("**/foo/bar\n", "a/b/foo/bar", false), Some(true));
+        assert_eq!(ignored("a/**/b\n", "a/b", false), Some(true));
+        assert_eq!(ignored("a/**/b\n", "a/x/y/b", false), Some(true));
+        assert_eq!(ignored("a/**\n", "a/x/y", false), Some(true));
+    }
+}
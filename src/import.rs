@@ -0,0 +1,421 @@
+//! Reading back scans exported as ncdu's JSON, whether ncdu wrote them with
+//! -o or adansonia did with `export --format ncdu`, so a scan taken on a
+//! server can be browsed somewhere else.
+
+use crate::tree::Info;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+
+/// What an export held.
+pub struct Imported {
+    /// Every entry with only its own size, as a scan finds them: directories
+    /// still need what's in them added on.
+    pub entries: Vec<Info>,
+    /// When the scan was taken, in seconds since the epoch, if it says.
+    pub scanned: Option<i64>,
+}
+
+/// One entry's fields, as far as they matter here.
+#[derive(Default)]
+struct Fields {
+    name: Vec<u8>,
+    asize: u64,
+    dsize: u64,
+    uid: u32,
+    mtime: i64,
+    dev: Option<u64>,
+    ino: Option<u64>,
+    hlnkc: bool,    // one of several hard links
+    excluded: bool, // left out, e.g. for being on another filesystem
+}
+
+/// Reads an export. Files with several hard links count once, the first
+/// time they come up, as they would in a scan; what ncdu left out, such as
+/// other filesystems, is left out here too.
+pub fn ncdu(text: &[u8]) -> io::Result<Imported> {
+    let mut p = Parser { text, at: 0 };
+    p.expect(b'[')?;
+    p.number()?; // the major version, which has only ever been 1
+    p.expect(b',')?;
+    p.number()?;
+    p.expect(b',')?;
+    let mut scanned = None;
+    p.object(|p, key| {
+        match key {
+            b"timestamp" => scanned = Some(p.number()?),
+            _ => p.skip()?,
+        }
+        Ok(())
+    })?;
+    p.expect(b',')?;
+
+    let mut entries = vec![];
+    let mut linked = HashSet::new(); // the (device, inode) of hard links seen
+
+    // the directories open, with the device each is on, which what's in
+    // them shares unless it says otherwise.
+    let mut open: Vec<(PathBuf, Option<u64>)> = vec![];
+    loop {
+        // an entry is an object, or for a directory an array of its object
+        // and then what's in it.
+        let is_dir = p.peek() == Some(b'[');
+        if is_dir {
+            p.at += 1;
+        }
+        let f = p.fields()?;
+        let name = OsString::from_vec(f.name);
+        let path = match open.last() {
+            // the top goes by its whole path.
+            None if name.is_empty() => return Err(p.error("the top entry has no name")),
+            None => PathBuf::from(name),
+            Some(_) if name.is_empty() || name == "." || name == ".." => {
+                return Err(p.error(&format!("a bad name, {name:?}")))
+            }
+            Some(_) if name.as_encoded_bytes().contains(&b'/') => {
+                return Err(p.error(&format!("a bad name, {name:?}")))
+            }
+            Some((dir, _)) => dir.join(name),
+        };
+        let dev = f.dev.or(open.last().and_then(|&(_, dev)| dev));
+        let counted = f.hlnkc && f.ino.is_some_and(|ino| !linked.insert((dev, ino)));
+        if !f.excluded || open.is_empty() {
+            entries.push(Info {
+                depth: path.components().count(),
+                path: path.clone(),
+                size: if counted { 0 } else { f.asize },
+                disk: if counted { 0 } else { f.dsize },
+                is_dir,
+                mtime: f.mtime,
+                uid: f.uid,
+            });
+        }
+        if is_dir {
+            open.push((path, dev));
+        }
+
+        // then on to the next entry, after closing any directories done.
+        loop {
+            if open.is_empty() {
+                p.expect(b']')?;
+                p.end()?;
+                return Ok(Imported { entries, scanned });
+            }
+            match p.peek() {
+                Some(b',') => break,
+                Some(b']') => open.pop(),
+                _ => return Err(p.error("expected , or ]")),
+            };
+            p.at += 1;
+        }
+        p.at += 1;
+    }
+}
+
+/// Just enough of a JSON parser for ncdu's exports, keeping strings as
+/// bytes since ncdu writes names as they are, UTF-8 or not.
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, what: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{what} at byte {}", self.at),
+        )
+    }
+
+    /// The next byte that isn't whitespace, without taking it.
+    fn peek(&mut self) -> Option<u8> {
+        while self.text.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+        self.text.get(self.at).copied()
+    }
+
+    fn expect(&mut self, b: u8) -> io::Result<()> {
+        match self.peek() {
+            Some(c) if c == b => {
+                self.at += 1;
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected {}", b as char))),
+        }
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("more after the end")),
+        }
+    }
+
+    /// A number, as a whole one: nothing ncdu writes has a fraction.
+    fn number(&mut self) -> io::Result<i64> {
+        self.peek();
+        let start = self.at;
+        while matches!(
+            self.text.get(self.at),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.at += 1;
+        }
+        let token = std::str::from_utf8(&self.text[start..self.at]).unwrap();
+        let number = token.parse::<i64>().ok();
+        let number = number.or_else(|| token.parse::<f64>().ok().map(|f| f as i64));
+        number.ok_or_else(|| {
+            self.at = start;
+            self.error("expected a number")
+        })
+    }
+
+    fn word(&mut self) -> &[u8] {
+        self.peek();
+        let start = self.at;
+        while self.text.get(self.at).is_some_and(u8::is_ascii_alphabetic) {
+            self.at += 1;
+        }
+        &self.text[start..self.at]
+    }
+
+    fn boolean(&mut self) -> io::Result<bool> {
+        match self.word() {
+            b"true" => Ok(true),
+            b"false" => Ok(false),
+            _ => Err(self.error("expected true or false")),
+        }
+    }
+
+    fn string(&mut self) -> io::Result<Vec<u8>> {
+        self.expect(b'"')?;
+        let mut out = vec![];
+        loop {
+            let b = *self
+                .text
+                .get(self.at)
+                .ok_or_else(|| self.error("a string without an end"))?;
+            self.at += 1;
+            let escaped = match b {
+                b'"' => return Ok(out),
+                b'\\' => self.text.get(self.at).copied(),
+                b => {
+                    out.push(b);
+                    continue;
+                }
+            };
+            self.at += 1;
+            match escaped {
+                Some(b @ (b'"' | b'\\' | b'/')) => out.push(b),
+                Some(b'b') => out.push(8),
+                Some(b'f') => out.push(12),
+                Some(b'n') => out.push(b'\n'),
+                Some(b'r') => out.push(b'\r'),
+                Some(b't') => out.push(b'\t'),
+                Some(b'u') => {
+                    let c = self.unicode()?;
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => return Err(self.error("a bad escape")),
+            }
+        }
+    }
+
+    /// The character of a \u escape, after the \u, joining the halves of
+    /// a surrogate pair.
+    fn unicode(&mut self) -> io::Result<char> {
+        let hex = |p: &Self, at: usize| {
+            let digits = p.text.get(at..at + 4)?;
+            u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+        };
+        let high = hex(self, self.at).ok_or_else(|| self.error("a bad \\u escape"))?;
+        self.at += 4;
+        if (0xd800..0xdc00).contains(&high) && self.text[self.at..].starts_with(b"\\u") {
+            if let Some(low @ 0xdc00..0xe000) = hex(self, self.at + 2) {
+                self.at += 6;
+                let c = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                return Ok(char::from_u32(c).unwrap());
+            }
+        }
+        Ok(char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    /// Goes through an object, handing each key to `field` to take its
+    /// value.
+    fn object(
+        &mut self,
+        mut field: impl FnMut(&mut Self, &[u8]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.expect(b'{')?;
+        if self.peek() == Some(b'}') {
+            self.at += 1;
+            return Ok(());
+        }
+        loop {
+            let key = self.string()?;
+            self.expect(b':')?;
+            field(self, &key)?;
+            match self.peek() {
+                Some(b',') => self.at += 1,
+                Some(b'}') => {
+                    self.at += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error("expected , or }")),
+            }
+        }
+    }
+
+    /// Passes over a value that doesn't matter.
+    fn skip(&mut self) -> io::Result<()> {
+        match self.peek() {
+            Some(b'"') => self.string().map(drop),
+            Some(b'{') => self.object(|p, _| p.skip()),
+            Some(b'[') => {
+                self.at += 1;
+                if self.peek() == Some(b']') {
+                    self.at += 1;
+                    return Ok(());
+                }
+                loop {
+                    self.skip()?;
+                    match self.peek() {
+                        Some(b',') => self.at += 1,
+                        Some(b']') => {
+                            self.at += 1;
+                            return Ok(());
+                        }
+                        _ => return Err(self.error("expected , or ]")),
+                    }
+                }
+            }
+            Some(b't' | b'f' | b'n') => match self.word() {
+                b"true" | b"false" | b"null" => Ok(()),
+                _ => Err(self.error("expected a value")),
+            },
+            _ => self.number().map(drop),
+        }
+    }
+
+    fn fields(&mut self) -> io::Result<Fields> {
+        let mut f = Fields::default();
+        self.object(|p, key| {
+            match key {
+                b"name" => f.name = p.string()?,
+                b"asize" => f.asize = p.number()?.max(0) as u64,
+                b"dsize" => f.dsize = p.number()?.max(0) as u64,
+                b"uid" => f.uid = p.number()? as u32,
+                b"mtime" => f.mtime = p.number()?,
+                b"dev" => f.dev = Some(p.number()? as u64),
+                b"ino" => f.ino = Some(p.number()? as u64),
+                b"hlnkc" => f.hlnkc = p.boolean()?,
+                // it says why, e.g. "otherfs" or "pattern".
+                b"excluded" => {
+                    p.skip()?;
+                    f.excluded = true;
+                }
+                _ => p.skip()?,
+            }
+            Ok(())
+        })?;
+        Ok(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Tree;
+
+    fn sizes(entries: Vec<Info>) -> Vec<(String, u64, u64)> {
+        let mut tree = Tree { data: entries };
+        tree.preprocess();
+        let entries = tree.data.into_iter();
+        entries
+            .map(|x| (x.path.to_string_lossy().into_owned(), x.size, x.disk))
+            .collect()
+    }
+
+    #[test]
+    fn reads_what_ncdu_writes() {
+        let export = [
+            r#"[1,2,{"progname":"ncdu","progver":"2.3","timestamp":1734874620,"x":[{}]},
+[{"name":"/srv","asize":4096,"dsize":4096,"dev":2049,"ino":2},
+{"name":"a\u00e9\ud83d\ude00.log","asize":100,"dsize":4096,"ino":12},
+[{"name":"b","asize":10,"dsize":0,"ino":13,"mtime":1700000000,"uid":1000},
+{"name":"link","asize":50,"dsize":4096,"ino":14,"hlnkc":true,"nlink":2}],
+{"name":"link2","asize":50,"dsize":4096,"ino":14,"hlnkc":true,"nlink":2},
+{"name":"mnt","excluded":"otherfs"},
+{"name":"raw"#
+                .as_bytes(),
+            b"\xff", // not UTF-8
+            br#"\"","asize":1,"dsize":0,"read_error":false}]]
+"#,
+        ]
+        .concat();
+        let imported = ncdu(&export).unwrap();
+        assert_eq!(imported.scanned, Some(1734874620));
+        let b = imported
+            .entries
+            .iter()
+            .find(|x| x.path.ends_with("b"))
+            .unwrap();
+        assert_eq!(
+            (b.is_dir, b.depth, b.mtime, b.uid),
+            (true, 3, 1700000000, 1000)
+        );
+        assert_eq!(
+            sizes(imported.entries),
+            [
+                ("/srv".to_string(), 4257, 12288),
+                ("/srv/a\u{e9}\u{1f600}.log".to_string(), 100, 4096),
+                ("/srv/b".to_string(), 60, 4096),
+                ("/srv/b/link".to_string(), 50, 4096),
+                // the second link to the same inode counts for nothing.
+                ("/srv/link2".to_string(), 0, 0),
+                ("/srv/raw\u{fffd}\"".to_string(), 1, 0),
+            ]
+        );
+
+        assert!(ncdu(b"[1,2,{},[{\"name\":\"/srv\"}").is_err());
+        assert!(ncdu(b"[1,2,{},[{\"name\":\"/srv\"},{\"name\":\"../etc\"}]]").is_err());
+        assert!(ncdu(b"[1,2,{},[{\"name\":\"/srv\"}]] x").is_err());
+    }
+
+    #[test]
+    fn reads_back_what_export_writes() {
+        let mut tree = Tree {
+            data: [
+                ("/s", true),
+                ("/s/d", true),
+                ("/s/d/f", false),
+                ("/s/g", false),
+            ]
+            .iter()
+            .map(|&(path, is_dir)| Info {
+                path: PathBuf::from(path),
+                depth: path.matches('/').count() + 1,
+                size: 10,
+                disk: 4096,
+                is_dir,
+                mtime: 1734874620,
+                uid: 1000,
+            })
+            .collect(),
+        };
+        tree.preprocess();
+        let mut out = vec![];
+        crate::output::ncdu(&mut out, &tree.data, 1734874620).unwrap();
+        let imported = ncdu(&out).unwrap();
+        assert_eq!(imported.scanned, Some(1734874620));
+        assert!(imported.entries.iter().all(|x| x.uid == 1000));
+        let before = tree.data.into_iter();
+        let before: Vec<_> = before
+            .map(|x| (x.path.to_string_lossy().into_owned(), x.size, x.disk))
+            .collect();
+        assert_eq!(sizes(imported.entries), before);
+    }
+}
@@ -0,0 +1,51 @@
+pub mod artifacts;
+pub mod benchmark;
+pub mod compare;
+pub mod config;
+pub mod error;
+pub mod exclude;
+pub mod fuzzy;
+pub mod gitignore;
+pub mod import;
+pub mod logs;
+pub mod macros;
+pub mod mail;
+pub mod manifest;
+pub mod output;
+pub mod parquet;
+pub mod regex;
+pub mod scan;
+pub mod spill;
+pub mod sqlite;
+pub mod stats;
+pub mod summary;
+pub mod threshold;
+pub mod tree;
+pub mod vm;
+pub mod xattr;
+
+/// Converts days since the epoch to a (year, month, day) date, see
+/// http://howardhinnant.github.io/date_algorithms.html
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + (month <= 2) as i64, month, day)
+}
+
+/// Formats a number with thousands separators, e.g. `1,234,567`.
+pub fn commaify<T: ToString>(i: T) -> String {
+    i.to_string()
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        .map(std::str::from_utf8)
+        .collect::<Result<Vec<&str>, _>>()
+        .unwrap()
+        .join(",")
+}
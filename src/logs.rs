@@ -0,0 +1,138 @@
+//! Spotting logs that need rotating or compressing: live logs that have
+//! grown large, and rotated ones that were never compressed.
+
+use crate::tree::{Info, Tree};
+use std::path::Path;
+
+/// Live logs past this should have been rotated.
+pub const ROTATE_AT: u64 = 100 << 20;
+/// Rotated logs smaller than this aren't worth compressing.
+pub const COMPRESS_AT: u64 = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Advice {
+    Rotate,   // a live log, too big already
+    Compress, // rotated, so nothing writes to it any more, but left as text
+}
+
+/// A log that needs something done about it.
+pub struct Log {
+    pub info: Info,
+    pub advice: Advice,
+    pub growth: f64, // bytes per second
+}
+
+/// Whether `path` looks like a log: named like one, or anywhere under a
+/// directory called log or logs.
+pub fn is_log(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.ends_with(".log")
+        || name.contains(".log.")
+        || path
+            .ancestors()
+            .skip(1)
+            .any(|a| a.ends_with("log") || a.ends_with("logs"))
+}
+
+/// Whether a log's name says it's been rotated, as logrotate and most
+/// daemons name them: "x.log.1", "x.log-20240101" or "x.log.old".
+pub fn is_rotated(name: &str) -> bool {
+    let numbered = |suffix: &str| !suffix.is_empty() && suffix.bytes().all(|c| c.is_ascii_digit());
+    let dated = name
+        .rsplit_once('-')
+        .is_some_and(|(_, date)| date.len() == 8 && numbered(date));
+    let (_, extension) = name.rsplit_once('.').unwrap_or_default();
+    dated || numbered(extension) || extension == "old"
+}
+
+pub fn is_compressed(name: &str) -> bool {
+    let (_, extension) = name.rsplit_once('.').unwrap_or_default();
+    matches!(
+        extension,
+        "gz" | "xz" | "zst" | "bz2" | "lz4" | "zip" | "z" | "Z"
+    )
+}
+
+/// What should be done about `i`, if it's a log and anything should.
+/// `growth` is how fast it's been growing, in bytes per second: a live
+/// log that will pass ROTATE_AT within a day needs rotating too.
+pub fn advise(i: &Info, growth: f64) -> Option<Advice> {
+    let name = i.path.file_name()?.to_string_lossy();
+    if i.is_dir || is_compressed(&name) || !is_log(&i.path) {
+        return None;
+    }
+    if is_rotated(&name) {
+        return (i.size >= COMPRESS_AT).then_some(Advice::Compress);
+    }
+    let tomorrow = i.size as f64 + growth.max(0.0) * 86400.0;
+    (tomorrow >= ROTATE_AT as f64).then_some(Advice::Rotate)
+}
+
+/// The logs under `dir` that might need something done, before looking
+/// at how they're growing, biggest first.
+pub fn candidates(tree: &Tree, dir: &Path) -> Vec<Info> {
+    let mut found: Vec<_> = tree
+        .subtree(dir)
+        .iter()
+        .filter(|i| {
+            let name = i.path.file_name().unwrap_or_default().to_string_lossy();
+            !i.is_dir && !is_compressed(&name) && is_log(&i.path)
+        })
+        .cloned()
+        .collect();
+    found.sort_by_key(|i| std::cmp::Reverse(i.size));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn advice(path: &str, size: u64, growth: f64) -> Option<Advice> {
+        let info = Info {
+            path: PathBuf::from(path),
+            depth: 0,
+            size,
+            disk: 0,
+            is_dir: false,
+            mtime: 0,
+            uid: 0,
+        };
+        advise(&info, growth)
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_log(Path::new("/srv/app.log")));
+        assert!(is_log(Path::new("/var/log/syslog")));
+        assert!(!is_log(Path::new("/srv/catalog")));
+        assert!(is_rotated("syslog.1"));
+        assert!(is_rotated("app.log-20240101"));
+        assert!(is_rotated("app.log.old"));
+        assert!(!is_rotated("app.log"));
+        assert!(!is_rotated("my-app.log"));
+        assert!(is_compressed("syslog.2.gz"));
+    }
+
+    #[test]
+    fn rotates_big_or_fast_growing_logs_and_compresses_rotated_ones() {
+        assert_eq!(
+            advice("/var/log/syslog", ROTATE_AT, 0.0),
+            Some(Advice::Rotate)
+        );
+        assert_eq!(advice("/var/log/syslog", 1 << 20, 0.0), None);
+        // a megabyte a minute fills up within the day.
+        assert_eq!(
+            advice("/var/log/syslog", 1 << 20, 17476.0),
+            Some(Advice::Rotate)
+        );
+        assert_eq!(
+            advice("/var/log/syslog.1", COMPRESS_AT, 0.0),
+            Some(Advice::Compress)
+        );
+        assert_eq!(advice("/var/log/syslog.1", 10, 0.0), None);
+        assert_eq!(advice("/var/log/syslog.2.gz", ROTATE_AT, 0.0), None);
+        assert_eq!(advice("/srv/data.bin", ROTATE_AT, 0.0), None);
+    }
+}
@@ -0,0 +1,118 @@
+//! Keyboard macros, like q and @ in vim: Q and a register record the keys
+//! that follow until the next Q, and @ and the register play them back.
+
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct Macros {
+    saved: HashMap<char, Vec<KeyEvent>>,
+    recording: Option<(char, Vec<KeyEvent>)>,
+    register: Option<char>, // Q or @, waiting for a register
+    replayed: Option<char>, // for @@
+}
+
+/// What became of a key given to Macros::handle.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// Not a macro key, for whatever else is listening.
+    Pass,
+    /// Taken, with something to say about it or not.
+    Taken(Option<String>),
+    /// Taken, and these keys are to be handled as if typed.
+    Replay(Vec<KeyEvent>),
+}
+
+impl Macros {
+    /// The register being recorded into.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|&(r, _)| r)
+    }
+
+    /// Takes `key` if it starts, ends or replays a macro. Q and @ only do so
+    /// when `listening`, not while something else wants them typed, such
+    /// as a prompt, or needs them answered, such as a confirmation. Keys
+    /// passed on are recorded.
+    pub fn handle(&mut self, key: KeyEvent, listening: bool) -> Outcome {
+        if let Some(by) = self.register.take() {
+            let KeyCode::Char(r) = key.code else {
+                return Outcome::Taken(None);
+            };
+            if by == 'Q' {
+                self.recording = Some((r, vec![]));
+                return Outcome::Taken(None);
+            }
+            let r = if r == '@' {
+                self.replayed.unwrap_or(r)
+            } else {
+                r
+            };
+            return match self.saved.get(&r) {
+                Some(keys) => {
+                    self.replayed = Some(r);
+                    Outcome::Replay(keys.clone())
+                }
+                None => Outcome::Taken(Some(format!("nothing recorded in @{r}"))),
+            };
+        }
+        match key.code {
+            KeyCode::Char('Q') if listening && self.recording.is_some() => {
+                let (r, keys) = self.recording.take().unwrap();
+                let message = format!("recorded {} keys in @{r}", keys.len());
+                self.saved.insert(r, keys);
+                Outcome::Taken(Some(message))
+            }
+            KeyCode::Char(c @ ('Q' | '@')) if listening => {
+                self.register = Some(c);
+                Outcome::Taken(None)
+            }
+            _ => {
+                if let Some((_, keys)) = &mut self.recording {
+                    keys.push(key);
+                }
+                Outcome::Pass
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::from(KeyCode::Char(c))
+    }
+
+    #[test]
+    fn records_and_replays() {
+        let mut m = Macros::default();
+        assert_eq!(m.handle(key('Q'), true), Outcome::Taken(None));
+        assert_eq!(m.handle(key('a'), true), Outcome::Taken(None));
+        assert_eq!(m.recording(), Some('a'));
+        assert_eq!(m.handle(key('j'), true), Outcome::Pass);
+        let recorded = Outcome::Taken(Some("recorded 1 keys in @a".to_string()));
+        assert_eq!(m.handle(key('Q'), true), recorded);
+        assert_eq!(m.handle(key('@'), true), Outcome::Taken(None));
+        assert_eq!(m.handle(key('a'), true), Outcome::Replay(vec![key('j')]));
+        assert_eq!(m.handle(key('@'), true), Outcome::Taken(None));
+        assert_eq!(m.handle(key('@'), true), Outcome::Replay(vec![key('j')]));
+    }
+
+    #[test]
+    fn leaves_q_and_at_to_whatever_is_typed_into() {
+        let mut m = Macros::default();
+        // user@host typed into the finder while recording.
+        m.handle(key('Q'), true);
+        m.handle(key('a'), true);
+        for c in "user@host".chars() {
+            assert_eq!(m.handle(key(c), false), Outcome::Pass);
+        }
+        assert_eq!(m.handle(key('Q'), false), Outcome::Pass);
+        assert_eq!(m.recording(), Some('a'));
+        m.handle(key('Q'), true);
+        assert_eq!(m.handle(key('@'), true), Outcome::Taken(None));
+        let typed: Vec<_> = "user@hostQ".chars().map(key).collect();
+        assert_eq!(m.handle(key('a'), true), Outcome::Replay(typed));
+    }
+}
@@ -0,0 +1,179 @@
+//! Finding mailboxes in a scan, so mail can be totalled per mailbox rather
+//! than per message file.
+
+use crate::tree::{Info, Tree};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Maildir, // a directory with cur, new and tmp, a file per message
+    Mbox,    // one file, each message starting with a "From " line
+}
+
+#[derive(Debug)]
+pub struct Mailbox {
+    pub path: PathBuf,
+    pub format: Format,
+    pub size: u64,
+    pub messages: Option<usize>, // not known for an mbox until it's been read
+}
+
+/// The mailboxes under `dir`, biggest first. Maildirs are recognised from
+/// the scan alone; files are taken for mboxes when they're somewhere mail
+/// is usually kept (a directory called mail or Mail, a ".mbox" extension or
+/// a Thunderbird ".msf" index beside them) and start like one, which means
+/// opening them. Messages in an mbox are counted later, by `count`.
+pub fn find(tree: &Tree, dir: &Path) -> Vec<Mailbox> {
+    let mut found = vec![];
+    for i in tree.subtree(dir) {
+        if i.is_dir {
+            if let Some(messages) = maildir(tree, &i.path) {
+                // Maildir++ keeps folders inside the inbox, so only count what's its own.
+                let size = ["cur", "new", "tmp"]
+                    .iter()
+                    .map(|d| tree.size(&i.path.join(d)))
+                    .sum();
+                found.push(Mailbox {
+                    path: i.path.clone(),
+                    format: Format::Maildir,
+                    size,
+                    messages: Some(messages),
+                });
+            }
+        } else if i.size > 0 && looks_like_mbox(tree, i) && starts_like_mbox(&i.path) {
+            found.push(Mailbox {
+                path: i.path.clone(),
+                format: Format::Mbox,
+                size: i.size,
+                messages: None,
+            });
+        }
+    }
+    found.sort_by_key(|m| std::cmp::Reverse(m.size));
+    found
+}
+
+/// The number of messages in `dir`, if it's a maildir.
+fn maildir(tree: &Tree, dir: &Path) -> Option<usize> {
+    let is_dir = |sub| tree.find(&dir.join(sub)).is_some_and(|i| i.is_dir);
+    if !["cur", "new", "tmp"].into_iter().all(is_dir) {
+        return None;
+    }
+    // tmp only holds messages while they're being delivered.
+    let messages = ["cur", "new"]
+        .iter()
+        .map(|sub| tree.children(&dir.join(sub)).filter(|i| !i.is_dir).count());
+    Some(messages.sum())
+}
+
+fn looks_like_mbox(tree: &Tree, i: &Info) -> bool {
+    let Some(parent) = i.path.parent() else {
+        return false;
+    };
+    let in_maildir = ["cur", "new", "tmp"].iter().any(|d| parent.ends_with(d));
+    let mail_dir = parent
+        .ancestors()
+        .any(|a| matches!(a.file_name().and_then(OsStr::to_str), Some("mail" | "Mail")));
+    let mut index = i.path.clone().into_os_string();
+    index.push(".msf");
+    !in_maildir
+        && (mail_dir
+            || i.path.extension() == Some(OsStr::new("mbox"))
+            || tree.find(Path::new(&index)).is_some())
+}
+
+fn starts_like_mbox(path: &Path) -> bool {
+    let mut start = [0; 5];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut start))
+        .is_ok()
+        && &start == b"From "
+}
+
+/// Counts the messages in an mbox by its "From " lines. Lines like that in
+/// message bodies are escaped as ">From " when messages are added.
+pub fn count(path: &Path) -> io::Result<usize> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut line = vec![];
+    let mut messages = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(messages);
+        }
+        messages += line.starts_with(b"From ") as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maildirs_and_mboxes() {
+        let dir = std::env::temp_dir().join(format!("adansonia-mail-{}", std::process::id()));
+        let mbox = dir.join("Mail/Sent");
+        std::fs::create_dir_all(mbox.parent().unwrap()).unwrap();
+        std::fs::write(&mbox, "From a\nhi\n>From b\n\nFrom c\nbye\n").unwrap();
+        let info = |path: PathBuf, size, is_dir| Info {
+            depth: path.components().count(),
+            path,
+            size,
+            disk: 0,
+            is_dir,
+            mtime: 0,
+            uid: 0,
+        };
+        let inbox = dir.join("Maildir");
+        let mut data = vec![
+            info(dir.clone(), 0, true),
+            info(dir.join("Mail"), 0, true),
+            info(mbox.clone(), 30, false),
+        ];
+        // an inbox with a folder inside it, Maildir++ style.
+        for maildir in [inbox.clone(), inbox.join(".Drafts")] {
+            data.push(info(maildir.clone(), 0, true));
+            for sub in ["cur", "new", "tmp"] {
+                data.push(info(maildir.join(sub), 0, true));
+            }
+            data.push(info(maildir.join("cur/1"), 100, false));
+            data.push(info(maildir.join("new/2"), 100, false));
+        }
+        let mut tree = Tree { data };
+        tree.preprocess();
+
+        let found = find(&tree, &dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let found: Vec<_> = found
+            .iter()
+            .map(|m| {
+                (
+                    m.path.strip_prefix(&dir).unwrap().to_str().unwrap(),
+                    m.format,
+                    m.size,
+                    m.messages,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("Maildir", Format::Maildir, 200, Some(2)),
+                ("Maildir/.Drafts", Format::Maildir, 200, Some(2)),
+                ("Mail/Sent", Format::Mbox, 30, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_from_lines() {
+        let path = std::env::temp_dir().join(format!("adansonia-mbox-{}", std::process::id()));
+        std::fs::write(&path, "From a\nhi\n>From b\n\nFrom c\nbye").unwrap();
+        let messages = count(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(messages.unwrap(), 2);
+    }
+}
@@ -1,381 +1,4700 @@
 use bytesize::ByteSize;
-use clap::Parser;
-use crossbeam_deque::{Steal, Worker};
-use crossterm::event::{self, Event, KeyCode, MouseEvent, MouseEventKind};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
 use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
 };
 use crossterm::ExecutableCommand;
 use ratatui::prelude::CrosstermBackend;
 use ratatui::style::{Color, Modifier};
-use ratatui::text::Span;
+use ratatui::text::{Line, Span};
 use ratatui::{
-    layout::Rect,
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize},
-    widgets::{Block, List, ListDirection, ListItem, ListState},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, Clear, List, ListDirection, ListItem, ListState, Row,
+        Table, Tabs,
+    },
 };
-use ratatui::{Frame, Terminal};
-use rayon::slice::ParallelSliceMut;
-use std::ffi::OsStr;
-use std::{env, io};
+use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io::{IsTerminal, Write};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
-use std::sync::mpsc;
-use std::time::Instant;
-use std::{fs, thread};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fs, io, panic};
+use std::{mem, thread};
 
-#[derive(Clone)]
-struct Info {
-    path: PathBuf,
-    depth: usize,
-    size: u64,
-    is_dir: bool,
+use adansonia::artifacts::{self, Artifact};
+use adansonia::benchmark;
+use adansonia::compare::{self, Difference};
+use adansonia::config;
+use adansonia::error::{Error, Result, EXIT_CHECK_FAILED, EXIT_FATAL, EXIT_OK, EXIT_SCAN_ERRORS};
+use adansonia::exclude::Excludes;
+use adansonia::fuzzy;
+use adansonia::import;
+use adansonia::logs::{self, Log};
+use adansonia::macros::{self, Macros};
+use adansonia::mail::{self, Mailbox};
+use adansonia::manifest;
+use adansonia::output::{self, Prune};
+use adansonia::parquet;
+use adansonia::regex;
+use adansonia::scan::{self, LocalSource, Options, ScanSource, WorkerStats};
+use adansonia::spill::Spill;
+use adansonia::sqlite;
+use adansonia::stats::{Stats, FAN_OUT_CLASSES, SIZE_CLASSES};
+use adansonia::summary;
+use adansonia::threshold::Threshold;
+use adansonia::tree::{Info, Tree};
+use adansonia::vm::{self, Image};
+use adansonia::xattr;
+use adansonia::{civil_from_days, commaify};
+use audit::AuditLog;
+use procfs::OpenFile;
+use prompt::{Outcome, Prompt};
+
+mod activity;
+mod audit;
+mod backup;
+mod cgroup;
+mod cleanup;
+mod completions;
+mod compress;
+mod control;
+mod dirs;
+mod dupes;
+mod fsstat;
+mod git;
+mod mounts;
+mod priority;
+mod procfs;
+mod prompt;
+mod push;
+mod removable;
+mod signing;
+mod trash;
+
+/// What the footer prompt is asking for.
+enum Input {
+    Filter,
+    Jump,
+    Script,
+    SaveMarks,
+    LoadMarks,
+    Search,
+}
+
+impl Input {
+    fn label(&self) -> &'static str {
+        match self {
+            Input::Filter => "filter",
+            Input::Jump => "jump to",
+            Input::Script => "write cleanup script to (cleanup.sh)",
+            Input::SaveMarks => "save marks to (marks.txt)",
+            Input::LoadMarks => "load marks from (marks.txt)",
+            Input::Search => "search by regex",
+        }
+    }
 }
 
-struct Tree {
-    data: Vec<Info>,
+/// A deletion waiting to be confirmed, with a key, or for a big one with a
+/// safeword typed out, so that a slip of the finger can't set it off.
+struct Confirm {
+    question: String, // ending in how to answer it
+    key: char,
+    typed: Option<(Prompt, Vec<String>)>, // what may be typed to confirm
 }
 
-impl Tree {
-    fn accumulate(self: &mut Self) {
-        let mut sums: [u64; 4096] = [0; 4096];
-        let mut prev_depth = 0;
-        for i in (0..self.data.len()).rev() {
-            let depth = self.data[i].depth;
-            if depth < prev_depth {
-                self.data[i].size += sums[prev_depth];
-                sums[prev_depth] = 0;
-            }
-            sums[depth] += self.data[i].size;
-            prev_depth = depth;
+impl Confirm {
+    /// Asks `question`, to be answered with `key`, or if `big` by typing yes
+    /// or `name`.
+    fn new(question: &str, key: char, name: Option<String>, big: bool) -> Confirm {
+        if !big {
+            return Confirm {
+                question: format!("{question} {key} to confirm"),
+                key,
+                typed: None,
+            };
+        }
+        let how = match &name {
+            Some(name) => format!("type yes or {name} to confirm"),
+            None => "type yes to confirm".to_string(),
+        };
+        let words = ["yes".to_string()].into_iter().chain(name).collect();
+        Confirm {
+            question: format!("{question} {how}"),
+            key,
+            typed: Some((Prompt::once(), words)),
         }
     }
 
-    fn preprocess(self: &mut Self) {
-        let now = Instant::now();
-        self.data.par_sort_unstable_by(|a, b| a.path.cmp(&b.path));
-        let elapsed = now.elapsed();
-        println!("data sorted in {:.2?}", elapsed);
+    /// The prompt for the safeword, if it's to be typed.
+    fn prompt(&self) -> Option<&Prompt> {
+        self.typed.as_ref().map(|(prompt, _)| prompt)
+    }
 
-        let now = Instant::now();
-        self.accumulate();
-        let elapsed = now.elapsed();
-        println!("data accumulated in {:.2?}", elapsed);
+    /// Whether `key` confirms the deletion or not, or None while a safeword
+    /// is still being typed.
+    fn answer(&mut self, key: KeyEvent) -> Option<bool> {
+        let Some((prompt, words)) = &mut self.typed else {
+            return Some(key.code == KeyCode::Char(self.key));
+        };
+        match prompt.handle(key) {
+            Outcome::Pending => None,
+            Outcome::Cancel => Some(false),
+            Outcome::Submit(text) => Some(words.contains(&text)),
+        }
     }
+}
 
-    fn get(self: &Self, p: &Path) -> Vec<Info> {
-        let start = self
-            .data
-            .binary_search_by(|x| x.path.cmp(&p.to_path_buf()))
-            .unwrap();
-        let end = self.data[start..].partition_point(|x| x.path.starts_with(&p.to_path_buf()));
+// how many of the best matches the fuzzy finder shows.
+const FINDER_ROWS: usize = 100;
 
-        let target = p.components().count() + 1;
-        let mut items: Vec<Info> = self.data[start..start + end]
-            .iter()
-            .filter(|x| x.depth == target)
-            .cloned()
-            .collect();
-        items.sort_by(|a, b| b.size.cmp(&a.size));
-        items
+/// The fuzzy finder opened with ctrl-f: what's been typed, every path in
+/// the scan as it was when the finder opened, relative to the top, and the
+/// search for what's typed.
+struct Finder {
+    prompt: Prompt,
+    state: ListState,
+    paths: Arc<Vec<PathBuf>>,
+    search: Option<fuzzy::Search>, // still going
+    matches: Option<fuzzy::Matches>,
+}
+
+impl Finder {
+    fn new(tree: &Tree, top: &Path) -> Finder {
+        let entries = tree.subtree(top).iter().skip(1);
+        let paths = entries.map(|i| i.path.strip_prefix(top).unwrap().to_path_buf());
+        Finder {
+            prompt: Prompt::new("find"),
+            state: ListState::default(),
+            paths: Arc::new(paths.collect()),
+            search: None,
+            matches: None,
+        }
+    }
+
+    /// Searches again for what's typed, only among the last matches if it
+    /// has grown from what they were for.
+    fn update(&mut self) {
+        let text = self.prompt.text();
+        if text.is_empty() {
+            (self.search, self.matches) = (None, None);
+            return;
+        }
+        let within = self.matches.as_ref();
+        let within = within.filter(|m| text.starts_with(&m.pattern));
+        let within = within.map(|m| m.all.clone());
+        let search = fuzzy::Search::start(&text, self.paths.clone(), within, FINDER_ROWS);
+        self.search = Some(search);
+    }
+}
+
+// how many of the largest matches a regex search shows.
+const SEARCH_ROWS: usize = 1000;
+
+/// What a regex search with ? found under the top: the largest matches,
+/// largest first, and how many there were in all.
+struct Results {
+    pattern: String,
+    state: ListState,
+    found: Vec<Info>,
+    total: usize,
+}
+
+impl Results {
+    /// Looks for `pattern` in the paths of `entries` on another thread,
+    /// sending what it finds to `tx`, unless it isn't a regex.
+    fn search(
+        pattern: String,
+        entries: Vec<Info>,
+        tx: mpsc::Sender<Results>,
+    ) -> std::result::Result<(), String> {
+        let regex = regex::Regex::new(&pattern)?;
+        thread::spawn(move || {
+            let mut found: Vec<Info> = entries
+                .into_par_iter()
+                .filter(|i| regex.is_match(&i.path.to_string_lossy()))
+                .collect();
+            let total = found.len();
+            found.par_sort_unstable_by_key(|i| std::cmp::Reverse(i.size));
+            found.truncate(SEARCH_ROWS);
+            let _ = tx.send(Results {
+                pattern,
+                state: ListState::default().with_selected(Some(0)),
+                found,
+                total,
+            });
+        });
+        Ok(())
+    }
+}
+
+// how many of the biggest differences a comparison shows.
+const COMPARE_ROWS: usize = 1000;
+
+/// Two directories side by side, with what differs between them.
+struct Comparison {
+    sides: [PathBuf; 2],
+    state: ListState,
+    differences: Vec<Difference>,
+    only: [usize; 2], // entries only on the left, and only on the right
+    changed: usize,
+}
+
+impl Comparison {
+    fn new(tree: &Tree, left: PathBuf, right: PathBuf) -> Comparison {
+        let mut differences = compare::compare(tree.subtree(&left), tree.subtree(&right));
+        let only = [
+            differences.iter().filter(|d| d.right.is_none()).count(),
+            differences.iter().filter(|d| d.left.is_none()).count(),
+        ];
+        let changed = differences.len() - only[0] - only[1];
+        differences.truncate(COMPARE_ROWS);
+        Comparison {
+            sides: [left, right],
+            state: ListState::default().with_selected(Some(0)),
+            differences,
+            only,
+            changed,
+        }
     }
 }
 
-fn commaify<T: ToString>(i: T) -> String {
-    return i
-        .to_string()
-        .as_bytes()
-        .rchunks(3)
-        .rev()
-        .map(std::str::from_utf8)
-        .collect::<Result<Vec<&str>, _>>()
-        .unwrap()
-        .join(",");
+/// Interprets a path typed by the user relative to `cwd`, expanding `~`.
+fn resolve(cwd: &Path, input: &str) -> Option<PathBuf> {
+    let path = match input.strip_prefix('~') {
+        Some(rest) => PathBuf::from(env::var_os("HOME")?).join(rest.trim_start_matches('/')),
+        None => cwd.join(input),
+    };
+    path.canonicalize().ok()
 }
 
-fn scan(root: &Path) -> Tree {
-    let now = Instant::now();
+/// Whether there's another key to handle, replayed or typed, before drawing
+/// again.
+fn more_keys(replay: &VecDeque<KeyEvent>) -> Result<bool> {
+    Ok(!replay.is_empty() || event::poll(Duration::ZERO).map_err(Error::Terminal)?)
+}
 
-    let root_metadata = root.metadata().unwrap();
-    let root_device = root_metadata.dev();
+// spawn a child without blocking the UI, reaping it in the background so it
+// doesn't linger as a zombie.
+fn spawn_detached(command: &mut Command) -> io::Result<()> {
+    let mut child = command.spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
 
-    let num_threads = 16;
-    let workers: Vec<_> = (0..num_threads)
-        .map(|_| Worker::<PathBuf>::new_lifo())
+/// Mount points skipped by any of `sources` that haven't been included since,
+/// leaving out bind mounts and those of excluded filesystem types.
+fn skipped(
+    sources: &[Arc<LocalSource>],
+    included: &HashSet<PathBuf>,
+    excluded: &HashSet<PathBuf>,
+) -> Vec<PathBuf> {
+    let mut mounts: Vec<PathBuf> = sources
+        .iter()
+        .flat_map(|s| s.skipped_mounts())
+        .filter(|m| !included.contains(m) && !excluded.contains(m))
         .collect();
-    let stealers: Vec<_> = workers.iter().map(|w| w.stealer()).collect();
+    mounts.sort();
+    mounts
+}
 
-    let (tx, rx) = mpsc::channel::<bool>();
-    let progress_handle = thread::spawn(move || {
-        let mut i = 0;
-        loop {
-            if !rx.recv().unwrap() {
-                break;
+/// Trades the sizes of freshly scanned entries for their disk usage.
+fn swap_sizes(entries: &mut [Info]) {
+    for i in entries {
+        mem::swap(&mut i.size, &mut i.disk);
+    }
+}
+
+/// The directories from `root` down to each of `paths`, which no scan covers.
+fn between(root: &Path, paths: &[PathBuf]) -> Vec<Info> {
+    paths
+        .iter()
+        .flat_map(|p| p.ancestors().skip(1))
+        .filter(|a| a.starts_with(root) && !paths.iter().any(|p| a.starts_with(p)))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|dir| Info {
+            path: dir.to_path_buf(),
+            depth: dir.components().count(),
+            size: 0,
+            disk: 0,
+            is_dir: true,
+            mtime: dir.metadata().map_or(0, |m| m.mtime()),
+            uid: dir.metadata().map_or(0, |m| m.uid()),
+        })
+        .collect()
+}
+
+/// Scans `path` and checks what's under it against `manifest`, failing if
+/// anything differs.
+fn verify(manifest: &Path, path: &Path) -> Result<()> {
+    let text = fs::read_to_string(manifest)?;
+    let root = path
+        .canonicalize()
+        .map_err(|e| Error::Root(path.to_path_buf(), e))?;
+    let source =
+        LocalSource::new(&root, Options::default()).map_err(|e| Error::Root(root.clone(), e))?;
+    let mut tree = scan::scan(Arc::new(source), &root)?.collect();
+    tree.preprocess();
+    let problems = manifest::verify(&text, &root, tree.subtree(&root))?;
+    for (path, problem) in &problems {
+        let path = path.strip_prefix(&root).unwrap_or(path).display();
+        match problem {
+            manifest::Problem::Changed => println!("changed: {path}"),
+            manifest::Problem::Missing => println!("missing: {path}"),
+            manifest::Problem::New => println!("new: {path}"),
+            manifest::Problem::Unreadable(e) => println!("unreadable: {path}: {e}"),
+        }
+    }
+    match problems.len() {
+        0 => {
+            let files = tree.subtree(&root).iter().filter(|i| !i.is_dir).count();
+            println!("all {} files match", commaify(files));
+            Ok(())
+        }
+        n => Err(Error::Check(format!(
+            "{} files don't match the manifest",
+            commaify(n)
+        ))),
+    }
+}
+
+/// Deletes the paths in the marks file `marks`, after asking unless `yes`,
+/// or writes a script that would. Marks inside other marks are covered by
+/// those, and ones already gone are passed over.
+fn clean(
+    marks: &Path,
+    script: Option<&Path>,
+    dry_run: bool,
+    yes: bool,
+    audit: AuditLog,
+) -> Result<()> {
+    let mut paths = cleanup::load_marks(marks).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("can't read marks from {}: {e}", marks.display()),
+        )
+    })?;
+    paths.sort();
+    paths.dedup_by(|p, kept| p.starts_with(kept));
+    let mut entries = vec![];
+    for path in paths {
+        if fs::symlink_metadata(&path).is_err() {
+            println!("already gone: {}", path.display());
+            continue;
+        }
+        let source = LocalSource::new(&path, Options::default())
+            .map_err(|e| Error::Root(path.clone(), e))?;
+        let mut tree = scan::scan(Arc::new(source), &path)?.collect();
+        tree.preprocess();
+        // what deleting frees is the disk space.
+        tree.swap_sizes();
+        entries.extend(tree.find(&path).cloned());
+    }
+    let total: u64 = entries.iter().map(|i| i.size).sum();
+    if let Some(script) = script {
+        cleanup::write_script(script, &entries).map_err(|e| {
+            io::Error::new(e.kind(), format!("can't write {}: {e}", script.display()))
+        })?;
+        println!(
+            "wrote {}, which would free {}",
+            script.display(),
+            ByteSize(total)
+        );
+        return Ok(());
+    }
+    let names = user_names();
+    entries.retain(|i| match removable::obstacle(&i.path, &names) {
+        Some(why) => {
+            println!("can't delete {}: {why}", i.path.display());
+            false
+        }
+        None => true,
+    });
+    let total: u64 = entries.iter().map(|i| i.size).sum();
+    if dry_run || !yes {
+        for i in &entries {
+            println!("{} ({})", i.path.display(), ByteSize(i.size));
+        }
+    }
+    if dry_run {
+        println!(
+            "would delete {} entries, freeing {}",
+            entries.len(),
+            ByteSize(total)
+        );
+        return Ok(());
+    }
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if !yes {
+        if !io::stdin().is_terminal() {
+            let e = "give --yes to delete without asking, or --dry-run to only list";
+            return Err(Error::Io(io::Error::other(e)));
+        }
+        print!(
+            "delete these {} entries, freeing {}? [y/N] ",
+            entries.len(),
+            ByteSize(total)
+        );
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+        }
+    }
+    let (mut freed, mut failed) = (0, 0);
+    for i in &entries {
+        match removable::delete(&i.path) {
+            Ok(()) => {
+                freed += i.size;
+                if let Err(e) = audit.record("delete", &i.path, Some(i.size)) {
+                    eprintln!("adansonia: couldn't write audit log: {e}");
+                }
             }
-            i += 100;
-            if i % 10_000 == 0 {
-                println!(" indexed {}\x1b[F", commaify(i));
+            Err(e) => {
+                failed += 1;
+                println!("couldn't delete {}: {e}", i.path.display());
             }
         }
-    });
+    }
+    println!(
+        "deleted {} entries, freeing {}",
+        entries.len() - failed,
+        ByteSize(freed)
+    );
+    match failed {
+        0 => Ok(()),
+        n => Err(Error::Io(io::Error::other(format!(
+            "couldn't delete {n} of them"
+        )))),
+    }
+}
 
-    workers[0].push(PathBuf::from(root));
-    let handles: Vec<_> = workers
-        .into_iter()
-        .enumerate()
-        .map(|(i, worker)| {
-            let progress_tx = tx.clone();
-            let mut stealers = stealers.clone();
-            stealers.remove(i); // remove our own stealer
-            stealers.rotate_right(i); // so no one stealer is swamped
-
-            thread::spawn(move || {
-                let mut result: Vec<Info> = vec![];
-
-                loop {
-                    let path = worker
-                        .pop() // try to take from local stack
-                        .or_else(|| {
-                            for s in &stealers {
-                                // loop until steal is not Steal::Retry
-                                while let Some(_) = match s.steal() {
-                                    Steal::Success(path) => return Some(path),
-                                    Steal::Empty => None,
-                                    Steal::Retry => Some(()),
-                                } {}
-                            }
-                            None // if all stealers are empty, then exit thread.
-                        });
+/// Scans `path` and pushes its summary to `endpoint`. Only a push the
+/// collector accepted becomes the one the next push's deltas are against.
+fn push(endpoint: &str, top: usize, path: &Path) -> Result<()> {
+    let root = path
+        .canonicalize()
+        .map_err(|e| Error::Root(path.to_path_buf(), e))?;
+    let mut record = push::Record::open(&root)?;
+    let source =
+        LocalSource::new(&root, Options::default()).map_err(|e| Error::Root(root.clone(), e))?;
+    let mut tree = scan::scan(Arc::new(source), &root)?.collect();
+    tree.preprocess();
+    let scanned = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let summary = summary::summarize(&tree, &root, &push::hostname(), scanned as i64, top);
+    push::send(endpoint, &summary.json(record.previous.as_ref()))?;
+    record.save(&summary.record())?;
+    Ok(())
+}
 
-                    if path.is_none() {
-                        break;
-                    }
-                    let path = path.unwrap();
+/// Reads the export in `file`, or standard input for -, to stand in for a
+/// scan.
+fn import(file: &Path) -> Result<import::Imported> {
+    let text = match file.as_os_str() == "-" {
+        true => {
+            let mut text = vec![];
+            io::Read::read_to_end(&mut io::stdin(), &mut text).map(|_| text)
+        }
+        false => fs::read(file),
+    };
+    let imported = text
+        .and_then(|text| import::ncdu(&text))
+        .map_err(|e| io::Error::new(e.kind(), format!("can't import {}: {e}", file.display())))?;
+    Ok(imported)
+}
 
-                    // sometimes fs::read_dir fails with permission error or whatever, in
-                    // which case we just ignore the error.
-                    let _ = fs::read_dir(path).map(|it| {
-                        for entry in it {
-                            let entry = entry.unwrap();
+/// The entries of a directory, as listed.
+#[derive(Default)]
+struct Listing {
+    items: Vec<Info>,
+    counts: HashMap<PathBuf, usize>, // how many entries are beneath each directory
+}
 
-                            // skip symlinks and files in different devices.
-                            let metadata = entry.metadata().unwrap();
-                            if metadata.is_symlink() || root_device != metadata.dev() {
-                                continue;
-                            }
+/// The entries of `dir`, with each directory replaced by the end of its
+/// chain of lone subdirectories if `collapse` is on, and less whatever's
+/// pretend deleted.
+fn listing(tree: &Tree, dir: &Path, collapse: bool, pretend: &HashMap<PathBuf, u64>) -> Listing {
+    let mut items = tree.get(dir);
+    if collapse {
+        for i in items.iter_mut().filter(|i| i.is_dir) {
+            *i = tree.chain_end(&i.path).unwrap_or(i).clone();
+        }
+    }
+    for i in &mut items {
+        i.size = i
+            .size
+            .saturating_sub(pretend.get(&i.path).copied().unwrap_or(0));
+    }
+    let counts = items
+        .iter()
+        .filter(|i| i.is_dir)
+        .map(|i| {
+            (
+                i.path.clone(),
+                tree.subtree(&i.path).len().saturating_sub(1),
+            )
+        })
+        .collect();
+    Listing { items, counts }
+}
 
-                            result.push(Info {
-                                path: entry.path().to_path_buf(),
-                                depth: entry.path().components().count(),
-                                size: if metadata.is_dir() {
-                                    0
-                                } else {
-                                    metadata.size()
-                                },
-                                is_dir: metadata.is_dir(),
-                            });
-                            if metadata.is_dir() {
-                                worker.push(entry.path().to_path_buf());
-                            }
+/// How much deleting the marked entries would take off each of their
+/// ancestors, and themselves, up to `root`.
+fn pretend(tree: &Tree, root: &Path, marks: &HashSet<PathBuf>) -> HashMap<PathBuf, u64> {
+    let mut pretend: HashMap<PathBuf, u64> = HashMap::new();
+    for mark in marks {
+        // a mark inside another is already counted with it.
+        if mark.ancestors().skip(1).any(|a| marks.contains(a)) {
+            continue;
+        }
+        let size = tree.size(mark);
+        for p in mark.ancestors().take_while(|p| p.starts_with(root)) {
+            *pretend.entry(p.to_path_buf()).or_default() += size;
+        }
+    }
+    pretend
+}
 
-                            // update progress bar every now and then
-                            if result.len() % 100 == 0 {
-                                progress_tx.send(true).unwrap();
-                            }
-                        }
-                    });
-                }
-                result
-            })
+/// The directory whose listing shows `dir`: its parent, or with `collapse`
+/// the top of the chain of lone directories it's at the end of.
+fn above(tree: &Tree, root: &Path, dir: &Path, collapse: bool) -> PathBuf {
+    let mut up = dir.parent().unwrap_or(dir).to_path_buf();
+    while collapse && up != root && tree.children(&up).count() == 1 {
+        up.pop();
+    }
+    up
+}
+
+/// User names by uid, from /etc/passwd.
+fn user_names() -> HashMap<u32, String> {
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
         })
-        .collect();
+        .collect()
+}
+
+/// The deepest directory that contains all of `paths` without being one of them.
+fn common_parent(paths: &[PathBuf]) -> PathBuf {
+    let mut parent = paths[0].clone();
+    for p in paths {
+        while (!p.starts_with(&parent) || *p == parent) && parent.pop() {}
+    }
+    parent
+}
+
+/// A line about a single file: its size and when it was last modified.
+fn details(path: &Path, size: u64) -> String {
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|t| format!(", modified {}", audit::timestamp(t)));
+    format!(
+        "{:?}: {} ({} bytes){}",
+        path.file_name().unwrap_or(path.as_os_str()),
+        ByteSize(size),
+        commaify(size),
+        modified.unwrap_or_default()
+    )
+}
+
+/// Everything worth knowing about one entry, a line each, for the popup
+/// shown with i: the details line, its owner, and its extended attributes.
+fn inspect(i: &Info) -> Vec<String> {
+    let owner = user_names().remove(&i.uid).unwrap_or(i.uid.to_string());
+    let mut lines = vec![details(&i.path, i.size), format!("owned by {owner}")];
+    match xattr::list(&i.path) {
+        Ok(attributes) if attributes.is_empty() => lines.push("no extended attributes".to_string()),
+        Ok(attributes) => {
+            lines.push(format!(
+                "extended attributes ({}, {}):",
+                attributes.len(),
+                ByteSize(xattr::size(&i.path))
+            ));
+            for (name, size) in attributes {
+                lines.push(format!("  {} ({size} bytes)", name.to_string_lossy()));
+            }
+        }
+        Err(e) => lines.push(format!("can't list extended attributes: {e}")),
+    }
+    lines
+}
 
-    let mut result = vec![Info {
-        path: root.to_path_buf(),
-        depth: root.components().count(),
-        size: root_metadata.size(),
-        is_dir: true,
-    }];
-    for handle in handles {
-        result.append(&mut handle.join().unwrap());
+/// If `cwd` has been removed from disk, moves up to the closest ancestor that
+/// still exists and drops what vanished from the tree. Returns a warning.
+fn leave_vanished(tree: &mut Tree, root: &Path, cwd: &mut PathBuf) -> Option<String> {
+    if cwd.is_dir() {
+        return None;
+    }
+    if !root.is_dir() {
+        return Some(format!("{root:?} no longer exists"));
+    }
+    let gone = cwd.clone();
+    let mut top = cwd.clone(); // the highest directory that vanished
+    while !cwd.is_dir() {
+        top = cwd.clone();
+        cwd.pop();
     }
+    tree.remove(&top);
+    Some(format!("{gone:?} no longer exists, moved up to {cwd:?}"))
+}
+
+/// Which charts the statistics view shows.
+#[derive(Clone, Copy, PartialEq)]
+enum Chart {
+    Shape,
+    Sizes,
+    Years,
+}
 
-    tx.send(false).unwrap();
-    progress_handle.join().unwrap();
+/// Draws statistics about a subtree as bar charts.
+fn render_stats(frame: &mut Frame, area: Rect, title: String, stats: &Stats, chart: Chart) {
+    let block = Block::bordered().title(title).style(Style::new().white());
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
 
-    let elapsed = now.elapsed();
-    println!(
-        "{} items indexed in {:.2?}",
-        commaify(result.len()),
-        elapsed
+    let classes = || {
+        let last = ByteSize(SIZE_CLASSES[SIZE_CLASSES.len() - 1]);
+        SIZE_CLASSES
+            .iter()
+            .map(|&b| format!("<{}", ByteSize(b).to_string_as(true)))
+            .chain([format!(">={}", last.to_string_as(true))])
+    };
+    let counts = |values: &[u64]| {
+        classes()
+            .zip(values.to_vec())
+            .map(|(label, n)| (label, n, commaify(n)))
+    };
+    match chart {
+        Chart::Sizes => {
+            let [files, bytes] = Layout::horizontal([Constraint::Ratio(1, 2); 2]).areas(inner);
+            render_chart(frame, files, "Files by size", counts(&stats.files));
+            let totals = classes()
+                .zip(stats.bytes.iter())
+                .map(|(label, &n)| (label, n, ByteSize(n).to_string()));
+            render_chart(frame, bytes, "Bytes by size", totals);
+        }
+        Chart::Years => {
+            // how much was last touched in or before each year.
+            let total: u64 = stats.years.iter().map(|&(_, n)| n).sum();
+            let mut so_far = 0;
+            let years = stats.years.iter().map(|&(year, n)| {
+                so_far += n;
+                let old = (so_far * 100).checked_div(total).unwrap_or(0);
+                let text = format!("{}, {old}% untouched after {year}", ByteSize(n));
+                (year.to_string(), n, text)
+            });
+            render_chart(frame, inner, "Bytes by last modification year", years);
+        }
+        Chart::Shape => {
+            let [depth, size, fan_out] =
+                Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(inner);
+            let depths = stats.depth.iter().enumerate();
+            let depths = depths.map(|(i, &n)| ((i + 1).to_string(), n, commaify(n)));
+            render_chart(frame, depth, "Files by depth", depths);
+            render_chart(frame, size, "Files by size", counts(&stats.files));
+
+            let bounds = FAN_OUT_CLASSES.iter().map(Some).chain([None]);
+            let fan_outs = [0]
+                .iter()
+                .chain(&FAN_OUT_CLASSES)
+                .zip(bounds)
+                .map(|(&low, high)| match high {
+                    Some(&high) if high - low == 1 => low.to_string(),
+                    Some(&high) => format!("{}-{}", commaify(low), commaify(high - 1)),
+                    None => format!(">={}", commaify(low)),
+                })
+                .zip(stats.fan_out.iter())
+                .map(|(label, &n)| (label, n, commaify(n)));
+            render_chart(frame, fan_out, "Directories by children", fan_outs);
+        }
+    }
+}
+
+/// Draws labelled horizontal bars, each with a value and the text shown on it.
+fn render_chart(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    bars: impl IntoIterator<Item = (String, u64, String)>,
+) {
+    let bars: Vec<Bar> = bars
+        .into_iter()
+        .map(|(label, n, text)| {
+            Bar::default()
+                .label(Line::from(label))
+                .value(n)
+                .text_value(text)
+        })
+        .collect();
+    let chart = BarChart::default()
+        .block(Block::bordered().title(title))
+        .direction(Direction::Horizontal)
+        .bar_width(1)
+        .bar_gap(0)
+        .bar_style(Style::default().fg(Color::Blue))
+        .value_style(Style::default().fg(Color::White))
+        .data(BarGroup::default().bars(&bars));
+    frame.render_widget(chart, area);
+}
+
+/// Draws a table of the scan's worker threads, to see how evenly the work
+/// was spread, with where memory has gone along the bottom.
+fn render_workers(
+    frame: &mut Frame,
+    area: Rect,
+    title: String,
+    workers: &[WorkerStats],
+    memory: String,
+) {
+    let header = Row::new(["worker", "listed", "steals", "stolen", "queue", "idle", ""])
+        .style(Style::new().bold());
+    let rows = workers.iter().enumerate().map(|(i, w)| {
+        Row::new([
+            i.to_string(),
+            commaify(w.listed),
+            commaify(w.steals),
+            commaify(w.stolen),
+            commaify(w.queue),
+            format!("{:.1?}", w.idle),
+            if w.finished { "done" } else { "" }.to_string(),
+        ])
+    });
+    let widths = [Constraint::Length(10); 7];
+    let table = Table::new(rows, widths).header(header).block(
+        Block::bordered()
+            .title(title)
+            .title_bottom(memory)
+            .style(Style::new().white()),
     );
-    return Tree { data: result };
+    frame.render_widget(table, area);
+}
+
+/// A `width` by `height` rectangle in the middle of `area`, shrunk to fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// A list with its selection highlighted, as in every popup.
+fn selectable<'a, T: Into<ListItem<'a>>>(rows: impl IntoIterator<Item = T>) -> List<'a> {
+    List::new(rows)
+        .highlight_style(Style::default().bg(Color::Yellow).fg(Color::Black))
+        .highlight_symbol("> ")
+}
+
+/// Draws `rows` as a popup in the middle of the screen, as tall as they are
+/// and `width` wide, or as wide as the longest of them needs.
+fn render_popup(
+    frame: &mut Frame,
+    block: Block,
+    rows: Vec<String>,
+    width: Option<u16>,
+    state: &mut ListState,
+) {
+    let width = width
+        .unwrap_or_else(|| rows.iter().map(|r| r.chars().count()).max().unwrap_or(0) as u16 + 6);
+    let area = centered(frame.area(), width.max(60), rows.len() as u16 + 2);
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(selectable(rows).block(block), area, state);
+}
+
+// how often the event loop wakes up to pick up background work when idle.
+const TICK: Duration = Duration::from_millis(100);
+// the shortest time between two frames, so holding down a key or spinning
+// the mouse wheel redraws at most 30 times a second.
+const FRAME: Duration = Duration::from_millis(33);
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Size,
+    Name,
+    Unique,   // size less the bytes that also exist elsewhere
+    Modified, // an entry's own mtime, not the newest beneath it
+    Items,    // entries beneath a directory
+}
+
+impl SortKey {
+    /// The key r goes to from this one. Unique sizes are left out, since
+    /// they need duplicates found first.
+    fn next(self) -> SortKey {
+        match self {
+            SortKey::Size => SortKey::Name,
+            SortKey::Name => SortKey::Modified,
+            SortKey::Modified => SortKey::Items,
+            SortKey::Items | SortKey::Unique => SortKey::Size,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortKey::Size => "size",
+            SortKey::Name => "name",
+            SortKey::Unique => "unique size",
+            SortKey::Modified => "modified time",
+            SortKey::Items => "items",
+        }
+    }
+
+    /// Width of the column a listing gets when sorted by this, between the
+    /// size and the name, not counting the space after it.
+    fn width(self) -> u16 {
+        match self {
+            SortKey::Modified => 10,
+            SortKey::Items => 9,
+            _ => 0,
+        }
+    }
+}
+
+/// How a listing is ordered. Sizes, times and counts naturally run largest
+/// first and names A to Z; `reverse` flips that.
+#[derive(Clone, Copy)]
+struct Sort {
+    key: SortKey,
+    reverse: bool,
+}
+
+impl Sort {
+    /// Sorts by `key`, or reverses the order if it's already sorted by it.
+    fn toggle(&mut self, key: SortKey) {
+        if self.key == key {
+            self.reverse = !self.reverse;
+        } else {
+            *self = Sort {
+                key,
+                reverse: false,
+            };
+        }
+    }
+
+    fn apply(
+        self,
+        items: &mut [Info],
+        duplicated: &HashMap<PathBuf, u64>,
+        counts: &HashMap<PathBuf, usize>,
+    ) {
+        match self.key {
+            SortKey::Size => items.sort_by_key(|x| std::cmp::Reverse(x.size)),
+            SortKey::Name => items.sort_by(|a, b| a.path.file_name().cmp(&b.path.file_name())),
+            SortKey::Unique => items.sort_by_key(|x| std::cmp::Reverse(unique(x, duplicated))),
+            SortKey::Modified => items.sort_by_key(|x| std::cmp::Reverse(x.mtime)),
+            SortKey::Items => items.sort_by_key(|x| std::cmp::Reverse(counts.get(&x.path))),
+        }
+        if self.reverse {
+            items.reverse();
+        }
+    }
+
+    /// What the listing's title says about the order, unless it's the usual
+    /// largest first.
+    fn describe(self) -> String {
+        match (self.key, self.reverse) {
+            (SortKey::Size, false) => String::new(),
+            (key, false) => format!(" [by {}]", key.name()),
+            (key, true) => format!(" [by {}, reversed]", key.name()),
+        }
+    }
+
+    /// Arrow shown next to the column title when sorting by `key`.
+    fn arrow(self, key: SortKey) -> &'static str {
+        let descending = (self.key != SortKey::Name) != self.reverse;
+        match () {
+            _ if self.key != key => "",
+            _ if descending => "▼",
+            _ => "▲",
+        }
+    }
+}
+
+/// How each row's share of the directory listed is drawn, like ncdu's
+/// graph column.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Graph {
+    /// No graph
+    None,
+    /// A bar, e.g. [███░░░░░░░]
+    Bar,
+    /// A percentage
+    Percent,
+    /// A percentage and a bar
+    Both,
+}
+
+// cells in a graph's bar.
+const BAR: usize = 10;
+
+impl Graph {
+    /// The style after this one, for going through them with a key.
+    fn next(self) -> Graph {
+        match self {
+            Graph::None => Graph::Bar,
+            Graph::Bar => Graph::Percent,
+            Graph::Percent => Graph::Both,
+            Graph::Both => Graph::None,
+        }
+    }
+
+    /// Columns taken in front of the size, with a space after the graph.
+    fn width(self) -> u16 {
+        match self {
+            Graph::None => 0,
+            Graph::Bar => BAR as u16 + 3,
+            Graph::Percent => 7,
+            Graph::Both => BAR as u16 + 10,
+        }
+    }
+
+    fn draw(self, share: f64) -> String {
+        let share = share.clamp(0.0, 1.0);
+        let cells = (share * BAR as f64).round() as usize;
+        let bar = format!("[{}{}] ", "█".repeat(cells), "░".repeat(BAR - cells));
+        let percent = format!("{:>5.1}% ", share * 100.0);
+        match self {
+            Graph::None => String::new(),
+            Graph::Bar => bar,
+            Graph::Percent => percent,
+            Graph::Both => percent + &bar,
+        }
+    }
+}
+
+/// The day of an mtime, as in 2024-03-05.
+fn day(mtime: i64) -> String {
+    let (year, month, day) = civil_from_days(mtime.div_euclid(86400));
+    format!("{year}-{month:02}-{day:02}")
+}
+
+/// What's left of an entry's size after taking away bytes that also exist
+/// somewhere else.
+fn unique(i: &Info, duplicated: &HashMap<PathBuf, u64>) -> u64 {
+    i.size
+        .saturating_sub(duplicated.get(&i.path).copied().unwrap_or(0))
+}
+
+/// How many bytes of each sparse file are holes, and of every directory
+/// holding them.
+fn holes(sources: &[Arc<LocalSource>]) -> HashMap<PathBuf, u64> {
+    let mut holes = HashMap::new();
+    for (file, n) in sources.iter().flat_map(|s| s.sparse()) {
+        for path in file.ancestors() {
+            *holes.entry(path.to_path_buf()).or_default() += n;
+        }
+    }
+    holes
+}
+
+/// The source that scanned `path`, the one with the longest root above it.
+/// Each only measures what's on its own filesystem.
+fn source_for<'a>(sources: &'a [Arc<LocalSource>], path: &Path) -> &'a LocalSource {
+    sources
+        .iter()
+        .filter(|s| path.starts_with(s.root()))
+        .max_by_key(|s| s.root().components().count())
+        .unwrap_or(&sources[0])
+}
+
+/// How the free space of the filesystem changed over a deletion, which can
+/// be less than what was deleted: other hard links, files held open,
+/// snapshots.
+fn free_went(before: Option<u64>, after: Option<u64>) -> String {
+    match (before, after) {
+        (Some(before), Some(after)) => format!(
+            " (filesystem free went from {} to {})",
+            ByteSize(before),
+            ByteSize(after)
+        ),
+        _ => String::new(),
+    }
+}
+
+/// Notes which of `paths` couldn't be deleted and why, forgetting what was
+/// noted about them before.
+fn check_deletable<'a>(
+    undeletable: &mut HashMap<PathBuf, String>,
+    paths: impl Iterator<Item = &'a PathBuf>,
+    names: &HashMap<u32, String>,
+) {
+    for path in paths {
+        match removable::obstacle(path, names) {
+            Some(why) => undeletable.insert(path.clone(), why),
+            None => undeletable.remove(path),
+        };
+    }
+}
+
+/// Per-entry state shown alongside the rows of a listing.
+struct Annotations {
+    marks: HashSet<PathBuf>,
+    changed: HashMap<PathBuf, Option<u64>>, // on-disk size when it no longer matches the scan
+    compressible: HashMap<PathBuf, f64>,    // estimated fraction compression would save
+    caches: HashSet<PathBuf>,               // tagged with CACHEDIR.TAG, so safe to delete
+    aliases: HashMap<PathBuf, PathBuf>,     // bind mounts, and where their contents are counted
+    active: HashMap<PathBuf, i64>,          // being written to, and the growth in bytes per second
+    backups: HashMap<PathBuf, (backup::Kind, io::Result<backup::Usage>)>, // what restic or borg says
+    holes: HashMap<PathBuf, u64>, // unallocated bytes in sparse files, and totalled up their ancestors
+    undeletable: HashMap<PathBuf, String>, // entries checked before deleting that couldn't be, and why
 }
 
 struct StatefulList {
     state: ListState,
-    area: Rect,
+    area: Rect,   // where the rows are drawn, inside the border and below the header
+    header: Rect, // the column titles
     items: Vec<Info>,
+    rows: Vec<String>, // formatted once per listing rather than every frame
+    offset: usize,     // index of the first visible row
+    dir: PathBuf,      // the directory listed
+    sort: Sort,
+    counts: HashMap<PathBuf, usize>, // entries beneath each directory listed
+    graph: Graph,
+    total: u64,             // the directory's size, which the graph shows shares of
+    filter: Option<String>, // only show entries whose name contains this
+    duplicated: HashMap<PathBuf, u64>, // bytes under each path with a copy elsewhere
+    images: HashMap<PathBuf, Option<Image>>, // headers read from disk images, once each
 }
 
 impl StatefulList {
-    fn new(items: Vec<Info>) -> StatefulList {
+    fn new(items: Listing) -> StatefulList {
         let mut state = ListState::default();
         state.select(Some(0));
-        StatefulList {
+        let mut list = StatefulList {
             state,
             area: Rect::default(),
-            items,
+            header: Rect::default(),
+            items: vec![],
+            rows: vec![],
+            offset: 0,
+            dir: PathBuf::new(),
+            sort: Sort {
+                key: SortKey::Size,
+                reverse: false,
+            },
+            counts: HashMap::new(),
+            graph: Graph::None,
+            total: 0,
+            filter: None,
+            duplicated: HashMap::new(),
+            images: HashMap::new(),
+        };
+        list.set_items(items);
+        list
+    }
+
+    /// Replaces the listing, keeping the same entry selected if it's still there.
+    fn set_items(&mut self, listing: Listing) {
+        let Listing { mut items, counts } = listing;
+        self.counts = counts;
+        if let Some(filter) = &self.filter {
+            let filter = filter.to_lowercase();
+            items.retain(|i| {
+                i.path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().to_lowercase().contains(&filter))
+            });
+        }
+        self.sort.apply(&mut items, &self.duplicated, &self.counts);
+        let selected = self
+            .state
+            .selected()
+            .and_then(|i| self.items.get(i))
+            .and_then(|i| items.iter().position(|x| x.path == i.path));
+        if let Some(i) = selected {
+            self.state.select(Some(i));
         }
+        for i in &items {
+            let image = i
+                .path
+                .extension()
+                .and_then(OsStr::to_str)
+                .is_some_and(|e| vm::EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+            if image && !i.is_dir && !self.images.contains_key(&i.path) {
+                let found = vm::inspect(&i.path).ok().flatten();
+                self.images.insert(i.path.clone(), found);
+            }
+        }
+        let images = self.has_images(&items);
+        self.rows = items
+            .iter()
+            .map(|i| {
+                let size = match self.sort.key {
+                    SortKey::Unique => unique(i, &self.duplicated),
+                    _ => i.size,
+                };
+                // collapsed chains show the way down, as in "a/b/c".
+                let name = i.path.strip_prefix(&self.dir).unwrap_or(&i.path);
+                // what the listing is sorted by, if it isn't shown already.
+                let column = match self.sort.key {
+                    SortKey::Modified => format!("{} ", day(i.mtime)),
+                    SortKey::Items => match self.counts.get(&i.path) {
+                        Some(n) => format!("{:>9} ", commaify(n)),
+                        None => format!("{:9} ", ""),
+                    },
+                    _ => String::new(),
+                };
+                if !images {
+                    return format!("{:>8} {column}{:?}", ByteSize(size), name);
+                }
+                let image = match self.images.get(&i.path) {
+                    Some(Some(image)) => format!(
+                        "{:>8} {:<5} {:<4}",
+                        ByteSize(image.virtual_size).to_string(),
+                        image.format,
+                        image.flag().unwrap_or_default()
+                    ),
+                    _ => String::new(),
+                };
+                format!("{:>8} {column}{image:<19} {:?}", ByteSize(size), name)
+            })
+            // .map(|i| format!("{:>16} {:?}", i.size, i.path.file_name().unwrap())) // for debugging
+            .collect();
+        self.items = items;
     }
 
-    fn render(self: &mut Self, frame: &mut Frame, status: String) {
-        self.area = frame.area();
-        let list = List::new(self.items.clone().into_iter().map(|i| {
-            ListItem::new(Span::styled(
-                format!("{:>8} {:?}", ByteSize(i.size), i.path.file_name().unwrap()),
-                // format!("{:>16} {:?}", i.size, i.path.file_name().unwrap()), // for debugging
-                Style::default().fg(if i.is_dir { Color::Blue } else { Color::White }),
-            ))
-        }))
-        .block(Block::bordered().title(status))
-        .style(Style::new().white())
-        .highlight_style(
-            Style::default()
-                .bg(Color::Yellow)
-                .fg(Color::Black)
-                .add_modifier(Modifier::BOLD),
-        )
-        .highlight_symbol("> ")
-        .repeat_highlight_symbol(true)
-        .direction(ListDirection::TopToBottom);
+    /// Whether any of `items` is a disk image, which gets its virtual size
+    /// shown in a column of its own.
+    fn has_images(&self, items: &[Info]) -> bool {
+        items
+            .iter()
+            .any(|i| matches!(self.images.get(&i.path), Some(Some(_))))
+    }
 
-        frame.render_stateful_widget(list, frame.area(), &mut self.state);
+    fn set_sort(&mut self, key: SortKey) {
+        self.sort.toggle(key);
+        let listing = Listing {
+            items: self.items.clone(),
+            counts: mem::take(&mut self.counts),
+        };
+        self.set_items(listing);
     }
-}
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    #[arg(default_value = ".")]
-    directory: PathBuf,
-    #[arg(long, short, action)]
-    benchmark: bool,
-}
+    /// Shows a different directory: drops the filter and selects the top entry.
+    fn enter(&mut self, dir: &Path, items: Listing) {
+        self.dir = dir.to_path_buf();
+        self.filter = None;
+        self.state.select(Some(0));
+        self.set_items(items);
+    }
 
-fn main() {
-    let args = Args::parse();
-    let mut cwd = args.directory.canonicalize().unwrap();
+    fn select_path(&mut self, p: &Path) {
+        if let Some(i) = self.items.iter().position(|x| x.path == p) {
+            self.state.select(Some(i));
+        }
+    }
 
-    let mut tree = scan(&cwd);
-    if args.benchmark {
-        exit(0);
+    /// The selected index, clamped to the listing. ListState lets selection run
+    /// past the end (e.g. select_last) until the next render.
+    fn selected(&self) -> Option<usize> {
+        let last = self.items.len().checked_sub(1)?;
+        self.state.selected().map(|i| i.min(last))
     }
-    tree.preprocess();
 
-    enable_raw_mode().unwrap();
-    let mut stdout = io::stdout();
-    stdout.execute(EnterAlternateScreen).unwrap();
-    crossterm::execute!(stdout, crossterm::event::EnableMouseCapture).unwrap();
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend).unwrap();
-
-    let mut depths = vec![0]; // to restore selection positions when moving back
-    let mut list: StatefulList = StatefulList::new(tree.get(&cwd));
-
-    let size = ByteSize(tree.data[tree.data.binary_search_by(|x| x.path.cmp(&cwd)).unwrap()].size);
-    loop {
-        terminal
-            .draw(|frame| {
-                list.render(
-                    frame,
-                    format!(
-                        "Files - {:?} {} ({})",
-                        cwd.file_name().unwrap_or(OsStr::new("/")),
-                        list.items.len(),
-                        size,
-                    ),
-                );
-            })
-            .expect("failed to draw frame");
-
-        let mut interact = || {
-            if let Some(selected) = list.state.selected() {
-                let i = &list.items[selected];
-                if i.is_dir {
-                    cwd = i.path.clone();
-                    depths.push(selected);
-                    list.items = tree.get(&cwd);
-                } else {
-                    Command::new("xdg-open")
-                        .arg(i.path.clone())
-                        .spawn()
-                        .unwrap();
-                }
+    /// Maps a terminal row to an index into `items`, if it lands on one.
+    fn index_at(&self, row: u16) -> Option<usize> {
+        if row < self.area.y || row >= self.area.y + self.area.height {
+            return None;
+        }
+        let index = self.offset + (row - self.area.y) as usize;
+        (index < self.items.len()).then_some(index)
+    }
+
+    /// Maps a terminal position to the column header under it, if any.
+    fn column_at(&self, column: u16, row: u16) -> Option<SortKey> {
+        if row != self.header.y || column < self.header.x {
+            return None;
+        }
+        // the highlight symbol takes two columns and the mark one, then the
+        // graph if any, and the size is eight wide. a column for the sort
+        // key may follow.
+        let size = self.header.x + 3 + self.graph.width() + 8;
+        Some(match self.sort.key.width() {
+            _ if column < size => SortKey::Size,
+            width if column <= size + width => self.sort.key,
+            _ => SortKey::Name,
+        })
+    }
+
+    fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        status: String,
+        message: Option<&str>,
+        free: Option<u64>,
+        notes: &Annotations,
+    ) {
+        let block = Block::bordered()
+            .title(status)
+            .title_bottom(message.unwrap_or_default())
+            .title_bottom(
+                Line::from(
+                    free.map(|f| format!("{} free", ByteSize(f)))
+                        .unwrap_or_default(),
+                )
+                .right_aligned(),
+            )
+            .style(Style::new().white());
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let [header, area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        self.header = header;
+        self.area = area;
+        frame.render_widget(
+            Line::from(format!(
+                "   {:graph$}{:>8} {}{}Name{}",
+                "",
+                match self.sort.key {
+                    SortKey::Unique => format!("{}Unique", self.sort.arrow(SortKey::Unique)),
+                    _ => format!("{}Size", self.sort.arrow(SortKey::Size)),
+                },
+                match self.sort.key {
+                    SortKey::Modified => format!(
+                        "{:10} ",
+                        self.sort.arrow(SortKey::Modified).to_string() + "Modified"
+                    ),
+                    SortKey::Items => format!(
+                        "{:>9} ",
+                        self.sort.arrow(SortKey::Items).to_string() + "Items"
+                    ),
+                    _ => String::new(),
+                },
+                match self.has_images(&self.items) {
+                    true => format!("{:>8}{:12}", "Virtual", ""),
+                    false => String::new(),
+                },
+                self.sort.arrow(SortKey::Name),
+                graph = self.graph.width() as usize,
+            ))
+            .bold(),
+            header,
+        );
+
+        // only the visible window is handed to ratatui, so scroll it ourselves.
+        let height = self.area.height as usize;
+        let selected = self
+            .state
+            .selected()
+            .map(|i| i.min(self.items.len().saturating_sub(1)));
+        self.state.select(selected);
+        if let Some(selected) = selected {
+            if selected < self.offset {
+                self.offset = selected;
+            } else if selected >= self.offset + height {
+                self.offset = selected + 1 - height;
             }
+        }
+        self.offset = self.offset.min(self.items.len().saturating_sub(height));
+        let end = (self.offset + height).min(self.items.len());
+        // of the same size the row shows.
+        let share = |i: &Info| {
+            let size = match self.sort.key {
+                SortKey::Unique => unique(i, &self.duplicated),
+                _ => i.size,
+            };
+            size as f64 / self.total.max(1) as f64
         };
-        match event::read().unwrap() {
-            Event::Key(key) => match key.code {
-                KeyCode::Char('k') => list.state.select_previous(),
-                KeyCode::Char('j') => list.state.select_next(),
-                KeyCode::Char('G') => list.state.select_last(),
-                KeyCode::Char('g') => list.state.select_first(),
-                KeyCode::Char('-') => {
-                    if depths.len() >= 2 {
-                        cwd.pop();
-                        list.items = tree.get(&cwd);
-                        list.state.select(Some(depths.pop().unwrap()));
-                    }
-                }
-                KeyCode::Char('q') | KeyCode::Esc => break,
-                KeyCode::Char('.') => {
-                    let original = env::current_dir().unwrap();
-                    env::set_current_dir(cwd.clone()).unwrap();
-                    Command::new("alacritty")
-                        .arg("-e")
-                        .arg("bash")
-                        .spawn()
-                        .unwrap();
-                    env::set_current_dir(original).unwrap();
-                }
-                KeyCode::Enter => {
-                    interact();
+
+        let list = List::new(
+            self.rows[self.offset..end]
+                .iter()
+                .zip(&self.items[self.offset..end])
+                .map(|(row, i)| {
+                    let mut spans = vec![
+                        Span::styled(
+                            if notes.marks.contains(&i.path) {
+                                "*"
+                            } else {
+                                " "
+                            },
+                            Style::default().fg(Color::Red),
+                        ),
+                        Span::raw(self.graph.draw(share(i))),
+                        Span::styled(
+                            row.as_str(),
+                            Style::default().fg(if i.is_dir { Color::Blue } else { Color::White }),
+                        ),
+                    ];
+                    if notes.caches.contains(&i.path) {
+                        spans.push(Span::styled(" [cache]", Style::default().fg(Color::Green)));
+                    }
+                    if let Some(original) = notes.aliases.get(&i.path) {
+                        spans.push(Span::styled(
+                            format!(" [same as {}]", original.display()),
+                            Style::default().fg(Color::Yellow),
+                        ));
+                    }
+                    if let Some(&dup) = self.duplicated.get(&i.path) {
+                        spans.push(Span::styled(
+                            format!(" ({} duplicated)", ByteSize(dup)),
+                            Style::default().fg(Color::Magenta),
+                        ));
+                    }
+                    if let Some(saved) = notes.compressible.get(&i.path) {
+                        spans.push(Span::styled(
+                            format!(" (~{:.0}% compressible)", saved * 100.0),
+                            Style::default().fg(Color::Cyan),
+                        ));
+                    }
+                    if let Some((kind, usage)) = notes.backups.get(&i.path) {
+                        spans.push(Span::styled(
+                            match usage {
+                                Ok(u) => format!(
+                                    " [{}: {} backed up, {} stored]",
+                                    kind.name(),
+                                    ByteSize(u.logical),
+                                    ByteSize(u.stored)
+                                ),
+                                Err(e) => format!(" [{}: {e}]", kind.name()),
+                            },
+                            Style::default().fg(Color::Cyan),
+                        ));
+                    }
+                    if let Some(why) = notes.undeletable.get(&i.path) {
+                        spans.push(Span::styled(
+                            format!(" [can't delete: {why}]"),
+                            Style::default().fg(Color::Red),
+                        ));
+                    }
+                    if let Some(&holes) = notes.holes.get(&i.path) {
+                        // so a huge sparse image isn't mistaken for that much data.
+                        spans.push(Span::styled(
+                            format!(
+                                " [sparse: {} allocated]",
+                                ByteSize(i.size.saturating_sub(holes))
+                            ),
+                            Style::default().fg(Color::LightBlue),
+                        ));
+                    }
+                    if let Some(&rate) = notes.active.get(&i.path) {
+                        spans.push(Span::styled(
+                            match rate {
+                                1.. => format!(" [writing +{}/s]", ByteSize(rate as u64)),
+                                _ => " [being written]".to_string(),
+                            },
+                            Style::default().fg(Color::LightRed),
+                        ));
+                    }
+                    if let Some(now) = notes.changed.get(&i.path) {
+                        spans.push(Span::styled(
+                            match now {
+                                Some(size) => format!(" (now {})", ByteSize(*size)),
+                                None => " (gone)".to_string(),
+                            },
+                            Style::default().fg(Color::Yellow),
+                        ));
+                    }
+                    ListItem::new(Line::from(spans))
+                }),
+        )
+        .style(Style::new().white())
+        .highlight_style(
+            Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ")
+        .repeat_highlight_symbol(true)
+        .direction(ListDirection::TopToBottom);
+
+        let mut window = ListState::default().with_selected(selected.map(|i| i - self.offset));
+        frame.render_stateful_widget(list, self.area, &mut window);
+    }
+}
+
+/// Formats for report, meant to be read.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Report {
+    /// An indented listing like `tree`, with sizes and percentages
+    Tree,
+    /// A Markdown report of the largest directories, files and extensions
+    Md,
+    /// A Graphviz graph, e.g. for `dot -Tsvg`
+    Dot,
+    /// Plain lists of the largest directories and files, e.g. to mail from cron
+    Top,
+}
+
+/// Formats for export, meant for other tools.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Export {
+    /// Apache Parquet with a row per entry, for DuckDB, Spark and the like
+    Parquet,
+    /// A SQLite database with an `entries` table, written to --output-file
+    Sqlite,
+    /// A checksum of every file for `sha256sum -c`, with paths relative to the root
+    Sha256,
+    /// The JSON that `ncdu -o` writes, for `ncdu -f` and tools built around it
+    Ncdu,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Browse what's taking up space, the default when no command is given
+    Interactive {
+        #[command(flatten)]
+        scan: ScanArgs,
+        #[command(flatten)]
+        browse: BrowseArgs,
+    },
+    /// Scan without browsing and print the total, along with anything skipped,
+    /// e.g. to time scans
+    Scan {
+        /// Exit with status 2 if PATH is bigger than SIZE, or smaller with <,
+        /// e.g. '/var/log>10GB'
+        #[arg(long, value_name = "PATH>SIZE")]
+        fail_if: Vec<Threshold>,
+        /// Time the scan and compare it with the last one of the same paths,
+        /// keeping this one for the next to be compared with
+        #[arg(long, conflicts_with = "import")]
+        benchmark: bool,
+        #[command(flatten)]
+        scan: ScanArgs,
+    },
+    /// Scan without browsing, reporting progress and taking pause, resume
+    /// and cancel commands on --control-socket, for wrappers and GUIs to
+    /// drive, then print the total
+    Serve {
+        #[command(flatten)]
+        scan: ScanArgs,
+    },
+    /// Scan two directories, e.g. two backups, and list what's only in one
+    /// or the other and what changed size, biggest changes first
+    Diff {
+        #[command(flatten)]
+        scan: ScanArgs,
+    },
+    /// Print the tree as a report
+    Report {
+        /// What the report looks like [default: tree, or top with --top]
+        #[arg(long, value_name = "FORMAT")]
+        format: Option<Report>,
+        /// Write to this file instead of standard output
+        #[arg(long, short = 'o', value_name = "FILE")]
+        output_file: Option<PathBuf>,
+        /// How many levels below the root to show
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+        /// Lump together entries smaller than this (e.g. 100MB)
+        #[arg(long, value_name = "SIZE")]
+        min_size: Option<ByteSize>,
+        /// How many of the largest directories and files to list, for top
+        /// and md [default: 20]
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        top: Option<u64>,
+        /// Exit with status 2 if PATH is bigger than SIZE, or smaller with <,
+        /// e.g. '/var/log>10GB'
+        #[arg(long, value_name = "PATH>SIZE")]
+        fail_if: Vec<Threshold>,
+        #[command(flatten)]
+        scan: ScanArgs,
+    },
+    /// Write out every entry for other tools to load
+    Export {
+        /// What to write
+        #[arg(long, value_name = "FORMAT")]
+        format: Export,
+        /// Write to this file instead of standard output
+        #[arg(long, short = 'o', value_name = "FILE")]
+        output_file: Option<PathBuf>,
+        /// With -o, sign the file with this SSH private key, e.g. the host
+        /// key, writing the signature to FILE.sig
+        #[arg(long, value_name = "KEY", requires = "output_file")]
+        sign: Option<PathBuf>,
+        /// Exit with status 2 if PATH is bigger than SIZE, or smaller with <,
+        /// e.g. '/var/log>10GB'
+        #[arg(long, value_name = "PATH>SIZE")]
+        fail_if: Vec<Threshold>,
+        #[command(flatten)]
+        scan: ScanArgs,
+    },
+    /// Delete the paths in a marks file saved with w while browsing, or
+    /// write a script that deletes them to review and run later
+    Clean {
+        /// e.g. marks.txt
+        marks: PathBuf,
+        /// Write the script here instead of deleting anything, e.g. cleanup.sh
+        #[arg(long, value_name = "FILE")]
+        script: Option<PathBuf>,
+        /// Only say what would be deleted and freed
+        #[arg(long, conflicts_with = "script")]
+        dry_run: bool,
+        /// Delete without asking first, e.g. from cron
+        #[arg(long, short = 'y', conflicts_with = "script")]
+        yes: bool,
+        /// Where to append the log of deletions [default: $XDG_STATE_HOME/adansonia/audit.log]
+        #[arg(long, value_name = "FILE")]
+        audit_log: Option<PathBuf>,
+    },
+    /// Run SQL against a database written by export --format sqlite. This
+    /// runs the sqlite3 command, which has to be installed
+    Query {
+        /// The database, e.g. scan.db
+        database: PathBuf,
+        /// e.g. "SELECT path, size FROM entries WHERE parent = '/home' ORDER BY size DESC"
+        sql: String,
+    },
+    /// Rehash the files under a directory and report those that differ from
+    /// a manifest written by export --format sha256
+    Verify {
+        /// e.g. manifest.txt
+        manifest: PathBuf,
+        /// The directory the manifest was written for
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+    /// Check that a file written with export --sign hasn't changed since, and
+    /// say whose key signed it
+    CheckSignature {
+        /// e.g. scan.db, with the signature next to it in scan.db.sig
+        file: PathBuf,
+        /// The keys to accept, in ssh-keygen's allowed_signers format
+        #[arg(long, value_name = "FILE")]
+        allowed_signers: PathBuf,
+    },
+    /// Print a completion script for bash, zsh or fish
+    Completions { shell: completions::Shell },
+    /// Scan a directory and send a summary of it, with the biggest directories
+    /// and how much each changed since the last push, to a central collector
+    Push {
+        /// Where to POST the summary as JSON, e.g. https://stats.example.com/disk
+        #[arg(long, value_name = "URL")]
+        endpoint: String,
+        /// How many of the biggest directories to include
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        top: usize,
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    args_override_self = true,
+    after_help = "Exit status: 0 if all went well, 1 if some entries couldn't be read, 2 if a \
+                  check failed (--fail-if, or verify finding differences), 3 for anything else."
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Action>,
+    #[command(flatten)]
+    scan: ScanArgs,
+    #[command(flatten)]
+    browse: BrowseArgs,
+}
+
+// what to scan and how, for every command that scans. these are plain
+// comments, as clap would take doc comments for the program's description.
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
+    /// Directories or files to scan
+    #[arg(default_value = ".", num_args = 1..)]
+    paths: Vec<PathBuf>,
+    /// Start from the options in this profile of the config file
+    /// [$XDG_CONFIG_HOME/adansonia/config], overriding them with any given here
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+    /// Round each file up to this allocation unit when summing (e.g. 4KiB).
+    /// Implies --apparent-size
+    #[arg(long, value_name = "N")]
+    block_size: Option<ByteSize>,
+    /// Count files with several hard links once per link rather than once
+    #[arg(long)]
+    count_links: bool,
+    /// Count how big files say they are rather than the disk space they use;
+    /// a toggles between the two while browsing
+    #[arg(long)]
+    apparent_size: bool,
+    /// Filesystem types whose mounts aren't offered or scanned across, e.g. tmpfs,fuse.*
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    exclude_fstype: Vec<String>,
+    /// Scan every real filesystem from /proc/mounts at once, one tab each
+    #[arg(long, conflicts_with = "paths")]
+    all_mounts: bool,
+    /// Take the scan from this JSON export, as written by ncdu -o or export
+    /// --format ncdu, instead of scanning; - reads it from standard input
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["paths", "all_mounts"])]
+    import: Option<PathBuf>,
+    /// Skip directories that take longer than this to list [default: 120 on network filesystems, never on local ones]
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+    /// Scan slowly at idle I/O priority, to stay out of the way of production load
+    #[arg(long)]
+    gentle: bool,
+    /// How many directories to list at once, e.g. 2 to 4 for spinning disks and more for
+    /// NVMe [default: one per CPU, or 4 on network filesystems]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+    /// Walk with one thread in name order, so unchanged trees give identical results
+    #[arg(long)]
+    deterministic: bool,
+    /// Keep the scan to this share of one core, e.g. 50 for half of one or 200 for two
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u32).range(1..))]
+    cpu_budget: Option<u32>,
+    /// Leave out the contents of cache directories marked with CACHEDIR.TAG
+    #[arg(long)]
+    exclude_caches: bool,
+    /// Leave out what this rsync-style pattern matches, e.g. /var/lib/docker or
+    /// *.iso. A leading slash anchors it to the directory being scanned
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Leave out what the patterns in this rsync-style exclude file match
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Vec<PathBuf>,
+    /// Leave out what .gitignore, .ignore and .git/info/exclude files say to,
+    /// such as build output
+    #[arg(long)]
+    respect_gitignore: bool,
+    /// Count what extended attributes and ACLs take up along with file contents
+    #[arg(long)]
+    xattrs: bool,
+    /// Report progress and take pause, resume and cancel commands as JSON lines on this Unix socket
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+}
+
+// how to browse, for interactive.
+#[derive(clap::Args, Debug)]
+struct BrowseArgs {
+    /// Leave the mouse to the terminal so text can be selected normally
+    #[arg(long)]
+    no_mouse: bool,
+    /// Browse in this many lines below the prompt instead of taking over the screen
+    #[arg(long, value_name = "LINES", num_args = 0..=1, default_missing_value = "15",
+          value_parser = clap::value_parser!(u16).range(3..))]
+    inline: Option<u16>,
+    /// Announce the selection as lines of plain text for screen readers instead of drawing
+    #[arg(long, conflicts_with = "inline")]
+    accessible: bool,
+    /// How to draw each entry's share of its directory next to its size; b
+    /// goes through the styles
+    #[arg(long, value_name = "STYLE", default_value = "none")]
+    graph: Graph,
+    /// Maximum delay between two clicks for them to count as a double click
+    #[arg(long, value_name = "MS", default_value_t = 400)]
+    double_click_ms: u64,
+    /// Notify, through the terminal, when a scan that took longer than this
+    /// finishes while the terminal isn't focused
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    notify_after: u64,
+    /// Keep about this many entries in memory, moving directories not being
+    /// looked at into a temporary file
+    #[arg(long, value_name = "N")]
+    max_entries: Option<usize>,
+    /// Have deletions bigger than this confirmed by typing yes or the name of
+    /// what's deleted rather than with a key
+    #[arg(long, value_name = "SIZE", default_value = "10GiB")]
+    confirm_over: ByteSize,
+    /// Likewise for deletions of more files than this
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    confirm_files: usize,
+    /// Only say what deleting, moving to the trash and compressing would do
+    /// and free, leaving everything as it is
+    #[arg(long)]
+    dry_run: bool,
+    /// Start with the paths in this file marked, as saved with w
+    #[arg(long, value_name = "FILE")]
+    marks: Option<PathBuf>,
+    /// Where to append the log of mutating actions [default: $XDG_STATE_HOME/adansonia/audit.log]
+    #[arg(long, value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+}
+
+/// What to do with a scan once it's going.
+enum Then {
+    Browse(BrowseArgs),
+    Total {
+        benchmark: bool,
+    },
+    Report {
+        format: Report,
+        file: Option<PathBuf>,
+        prune: Prune,
+    },
+    Export {
+        format: Export,
+        file: Option<PathBuf>,
+        sign: Option<PathBuf>,
+    },
+    Diff {
+        sides: [PathBuf; 2],
+    },
+}
+
+impl Args {
+    /// The options for scanning, when this is a command that scans.
+    fn scan_args(&self) -> Option<&ScanArgs> {
+        match &self.command {
+            None => Some(&self.scan),
+            Some(
+                Action::Interactive { scan, .. }
+                | Action::Scan { scan, .. }
+                | Action::Serve { scan }
+                | Action::Diff { scan }
+                | Action::Report { scan, .. }
+                | Action::Export { scan, .. },
+            ) => Some(scan),
+            Some(_) => None,
+        }
+    }
+}
+
+fn main() {
+    let argv = from_before_commands(env::args_os().collect());
+    let mut args = Args::try_parse_from(&argv).unwrap_or_else(|e| usage(e));
+    if let Some(name) = args.scan_args().and_then(|s| s.profile.clone()) {
+        args = match with_profile(&name, &argv, args.command.is_some()) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("adansonia: {e}");
+                exit(e.exit_status());
+            }
+        };
+    }
+    if let Err(e) = run(args) {
+        eprintln!("adansonia: {e}");
+        exit(e.exit_status());
+    }
+}
+
+/// Rewrites a command line from before there were commands, with --output
+/// FORMAT or -b/--benchmark, as the command that took over, saying so, so
+/// that scripts keep working until they're changed. The rest of the old
+/// flags, such as -o and --depth, are the new command's.
+fn from_before_commands(mut argv: Vec<OsString>) -> Vec<OsString> {
+    let cli = Args::command();
+    let command = argv.get(1).and_then(|a| a.to_str());
+    if command.is_some_and(|c| cli.find_subcommand(c).is_some()) {
+        return argv;
+    }
+    // options end at --, after which anything is a path.
+    let end = argv.iter().position(|a| a == "--").unwrap_or(argv.len());
+    let old = |a: &OsString| {
+        a == "-b"
+            || a == "--benchmark"
+            || a == "--output"
+            || a.to_str().is_some_and(|a| a.starts_with("--output="))
+    };
+    let Some(i) = argv[..end].iter().position(old) else {
+        return argv;
+    };
+    let flag = argv.remove(i).to_string_lossy().into_owned();
+    let (was, new) = match flag.strip_prefix("--output") {
+        None => (flag, "scan --benchmark".to_string()),
+        Some(format) => {
+            let format = match format.strip_prefix('=') {
+                Some(format) => format.to_string(),
+                None if i < argv.len() => argv.remove(i).to_string_lossy().into_owned(),
+                None => {
+                    argv.insert(i, flag.into()); // for clap to say what's wrong
+                    return argv;
+                }
+            };
+            let command = match Export::from_str(&format, true) {
+                Ok(_) => "export",
+                Err(_) => "report",
+            };
+            (
+                format!("--output {format}"),
+                format!("{command} --format {format}"),
+            )
+        }
+    };
+    eprintln!("adansonia: {was} is now `adansonia {new}`, which this is being run as");
+    argv.splice(1..1, new.split(' ').map(OsString::from));
+    argv
+}
+
+/// Prints what clap has to say about the command line and exits, with
+/// EXIT_FATAL for mistakes rather than clap's usual 2, which means a check
+/// failed here.
+fn usage(e: clap::Error) -> ! {
+    let _ = e.print();
+    exit(if e.use_stderr() { EXIT_FATAL } else { EXIT_OK })
+}
+
+/// Says which of the `limits` a finished scan crossed and exits with the
+/// status that calls for.
+fn finish(tree: &Tree, limits: &[Threshold], errors: u64) -> ! {
+    let crossed: Vec<String> = limits.iter().filter_map(|l| l.crossed(tree)).collect();
+    for what in &crossed {
+        eprintln!("adansonia: {what}");
+    }
+    exit(match (crossed.is_empty(), errors) {
+        (false, _) => EXIT_CHECK_FAILED,
+        (true, 0) => EXIT_OK,
+        (true, _) => EXIT_SCAN_ERRORS,
+    })
+}
+
+/// Parses the command line `argv` again with the options from profile
+/// `name` in front, after the command if one was given, so that any given
+/// explicitly take precedence.
+fn with_profile(name: &str, argv: &[OsString], command: bool) -> Result<Args> {
+    let path = dirs::config_file()
+        .ok_or_else(|| io::Error::other("there's no home directory to find a config in"))?;
+    let text = fs::read_to_string(&path)
+        .map_err(|e| io::Error::new(e.kind(), format!("can't read {}: {e}", path.display())))?;
+    let profiles =
+        config::parse(&text).map_err(|e| io::Error::other(format!("{}: {e}", path.display())))?;
+    let Some(options) = profiles.get(name) else {
+        let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        return Err(Error::Io(io::Error::other(format!(
+            "there's no profile {name} in {}, only: {}",
+            path.display(),
+            names.join(", ")
+        ))));
+    };
+    let (given, rest) = argv.split_at(1 + command as usize);
+    // a profile can hold options for several commands, so those the one
+    // being run doesn't take are passed over. ones no command takes are
+    // left for clap to complain about.
+    let cli = Args::command();
+    let takes = |c: &clap::Command, key: &str| c.get_arguments().any(|a| a.get_long() == Some(key));
+    let this = match given.get(1) {
+        Some(command) => cli.find_subcommand(command).unwrap_or(&cli),
+        None => &cli,
+    };
+    let options = options.iter().filter(|option| {
+        let key = option
+            .trim_start_matches('-')
+            .split('=')
+            .next()
+            .unwrap_or_default();
+        takes(this, key) || !cli.get_subcommands().chain([&cli]).any(|c| takes(c, key))
+    });
+    let command_line = given
+        .iter()
+        .cloned()
+        .chain(options.map(Into::into))
+        .chain(rest.iter().cloned());
+    Args::try_parse_from(command_line).map_err(|e| {
+        eprintln!("adansonia: in profile {name} from {}:", path.display());
+        usage(e)
+    })
+}
+
+/// Puts the terminal in raw mode, on the alternate screen unless inline, and
+/// restores it when dropped, so that errors and panics don't leave the shell
+/// unusable.
+struct TerminalGuard {
+    inline: bool,
+}
+
+impl TerminalGuard {
+    fn enter(mouse: bool, inline: bool) -> Result<TerminalGuard> {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            TerminalGuard::restore(inline);
+            hook(info);
+        }));
+
+        enable_raw_mode().map_err(Error::Terminal)?;
+        let guard = TerminalGuard { inline };
+        if !inline {
+            io::stdout()
+                .execute(EnterAlternateScreen)
+                .map_err(Error::Terminal)?;
+        }
+        if mouse {
+            crossterm::execute!(io::stdout(), crossterm::event::EnableMouseCapture)
+                .map_err(Error::Terminal)?;
+        }
+        crossterm::execute!(io::stdout(), crossterm::event::EnableFocusChange)
+            .map_err(Error::Terminal)?;
+        Ok(guard)
+    }
+
+    fn restore(inline: bool) {
+        let _ = disable_raw_mode();
+        if !inline {
+            let _ = io::stdout().execute(LeaveAlternateScreen);
+        }
+        let _ = crossterm::execute!(io::stdout(), crossterm::event::DisableMouseCapture);
+        let _ = crossterm::execute!(io::stdout(), crossterm::event::DisableFocusChange);
+        if TITLED.load(Ordering::Relaxed) {
+            // hand the title back to the shell, and the directory too, as
+            // the shell doesn't say it again until it next changes.
+            let here = env::current_dir().unwrap_or_default();
+            let _ = crossterm::execute!(io::stdout(), SetTitle(""));
+            let _ = write!(io::stdout(), "{}", osc7(&here));
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+/// Whether the terminal's title and directory have been set, so they need
+/// clearing on the way out.
+static TITLED: AtomicBool = AtomicBool::new(false);
+
+/// Titles the terminal after the directory being looked at and its size,
+/// and tells it the directory with OSC 7 so it can open new tabs there.
+fn set_title(dir: &Path, size: ByteSize) -> io::Result<()> {
+    TITLED.store(true, Ordering::Relaxed);
+    let title = format!("{} ({size}) - adansonia", dir.display());
+    crossterm::execute!(io::stdout(), SetTitle(title))?;
+    write!(io::stdout(), "{}", osc7(dir))?;
+    io::stdout().flush()
+}
+
+/// Gets the user's attention from another window: OSC 9 shows `text` as a
+/// desktop notification in terminals that support it, and the bell makes
+/// the rest flag the window or tab.
+fn notify(text: &str) -> io::Result<()> {
+    write!(io::stdout(), "\x1b]9;{text}\x07\x07")?;
+    io::stdout().flush()
+}
+
+fn rusage() -> libc::rusage {
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    usage
+}
+
+/// The user and system time this process has used so far.
+fn cpu_time() -> Duration {
+    let usage = rusage();
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+/// The most memory this process has had resident at once, in bytes.
+fn peak_memory() -> u64 {
+    rusage().ru_maxrss as u64 * 1024
+}
+
+/// Where memory has gone, for the benchmark and the worker table: the peak
+/// resident and what the tree takes of it.
+fn memory_use(tree: &Tree) -> String {
+    let memory = tree.memory();
+    format!(
+        "peak memory {}, of which the tree holds {}: {} of entries and {} of paths",
+        ByteSize(peak_memory()),
+        ByteSize(memory.index + memory.paths),
+        ByteSize(memory.index),
+        ByteSize(memory.paths)
+    )
+}
+
+/// The OSC 7 sequence for `dir`: a file URL.
+fn osc7(dir: &Path) -> String {
+    format!(
+        "\x1b]7;file://{}{}\x1b\\",
+        push::hostname(),
+        percent_encode(dir)
+    )
+}
+
+/// `path` with anything but unreserved characters and slashes
+/// percent-encoded, as URLs and trash info files want it.
+fn percent_encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        match b {
+            b'/' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            _ if b.is_ascii_alphanumeric() => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        TerminalGuard::restore(self.inline);
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    // the limits to check once the scan is done, for commands that finish.
+    let (args, then, fail_if) = match args.command {
+        None => (args.scan, Then::Browse(args.browse), vec![]),
+        Some(Action::Interactive { scan, browse }) => (scan, Then::Browse(browse), vec![]),
+        Some(Action::Scan {
+            fail_if,
+            benchmark,
+            scan,
+        }) => (scan, Then::Total { benchmark }, fail_if),
+        Some(Action::Serve { scan }) => {
+            if scan.control_socket.is_none() {
+                let e = io::Error::other(
+                    "serve needs a socket to serve on, give one with --control-socket",
+                );
+                return Err(Error::Io(e));
+            }
+            (scan, Then::Total { benchmark: false }, vec![])
+        }
+        Some(Action::Diff { scan }) => {
+            let fail = |why: &str| Err(Error::Io(io::Error::other(why.to_string())));
+            if scan.import.is_some() || scan.all_mounts {
+                return fail("diff scans the two directories it's given");
+            }
+            let [left, right] = &scan.paths[..] else {
+                return fail("diff compares two directories, give it two");
+            };
+            let canonical = |p: &PathBuf| p.canonicalize().map_err(|e| Error::Root(p.clone(), e));
+            let sides = [canonical(left)?, canonical(right)?];
+            if !sides.iter().all(|p| p.is_dir()) {
+                return fail("diff compares two directories, not files");
+            }
+            if sides[0].starts_with(&sides[1]) || sides[1].starts_with(&sides[0]) {
+                return fail("can't compare a directory with one inside it");
+            }
+            (scan, Then::Diff { sides }, vec![])
+        }
+        Some(Action::Report {
+            format,
+            output_file,
+            depth,
+            min_size,
+            top,
+            fail_if,
+            scan,
+        }) => {
+            let format = format.unwrap_or(match top {
+                Some(_) => Report::Top,
+                None => Report::Tree,
+            });
+            let prune = Prune {
+                depth,
+                min_size: min_size.map_or(0, |b| b.as_u64()),
+                top: top.map(|n| n as usize),
+            };
+            let file = output_file;
+            let then = Then::Report {
+                format,
+                file,
+                prune,
+            };
+            (scan, then, fail_if)
+        }
+        Some(Action::Export {
+            format,
+            output_file,
+            sign,
+            fail_if,
+            scan,
+        }) => {
+            if matches!(format, Export::Sqlite) && output_file.is_none() {
+                let e = io::Error::other("sqlite needs a database to write, give one with -o");
+                return Err(Error::Io(e));
+            }
+            if matches!(format, Export::Parquet)
+                && output_file.is_none()
+                && io::stdout().is_terminal()
+            {
+                let e = io::Error::other("parquet is binary, redirect it to a file or use -o");
+                return Err(Error::Io(e));
+            }
+            let file = output_file;
+            (scan, Then::Export { format, file, sign }, fail_if)
+        }
+        Some(Action::Query { database, sql }) => return Ok(sqlite::query(&database, &sql)?),
+        Some(Action::Verify { manifest, path }) => return verify(&manifest, &path),
+        Some(Action::Clean {
+            marks,
+            script,
+            dry_run,
+            yes,
+            audit_log,
+        }) => {
+            return clean(
+                &marks,
+                script.as_deref(),
+                dry_run,
+                yes,
+                AuditLog::new(audit_log),
+            )
+        }
+        Some(Action::Completions { shell }) => {
+            print!("{}", completions::script(shell, &Args::command()));
+            return Ok(());
+        }
+        Some(Action::Push {
+            endpoint,
+            top,
+            path,
+        }) => return push(&endpoint, top, &path),
+        Some(Action::CheckSignature {
+            file,
+            allowed_signers,
+        }) => {
+            let signer = signing::verify(&file, &allowed_signers).map_err(|e| {
+                io::Error::new(e.kind(), format!("can't trust {}: {e}", file.display()))
+            })?;
+            println!("{} was signed by {signer}", file.display());
+            return Ok(());
+        }
+    };
+    // every filesystem is scanned separately and stops at the others' mount
+    // points, so nested ones aren't counted twice.
+    let all_mounts = args.all_mounts;
+    // an imported scan was taken elsewhere, so nothing here applies to it.
+    let imported = args.import.as_deref().map(import).transpose()?;
+    // bind mounts are left out like mounts of excluded types. not being able
+    // to tell them apart isn't worth failing over.
+    let aliases = match imported {
+        Some(_) => HashMap::new(),
+        None => mounts::aliases().unwrap_or_default(),
+    };
+    let mut excluded = mounts::of_types(&args.exclude_fstype)?;
+    excluded.extend(aliases.keys().cloned());
+    let is_dir = |p: &Path| match &imported {
+        Some(imported) => imported.entries.iter().any(|x| x.path == p && x.is_dir),
+        None => p.is_dir(),
+    };
+    let mut paths = if let Some(imported) = &imported {
+        vec![imported.entries[0].path.clone()]
+    } else if all_mounts {
+        let mut mounts = mounts::real_mounts()?;
+        mounts.retain(|m| !excluded.contains(m));
+        if mounts.is_empty() {
+            return Err(Error::Io(io::Error::other("every filesystem is excluded")));
+        }
+        mounts
+    } else {
+        args.paths
+            .iter()
+            .map(|p| p.canonicalize().map_err(|e| Error::Root(p.clone(), e)))
+            .collect::<Result<Vec<_>>>()?
+    };
+    if !all_mounts {
+        // paths sort component-wise, so anything inside another argument (and
+        // so already covered by it) comes right after it.
+        paths.sort();
+        paths.dedup_by(|p, kept| p.starts_with(kept));
+    }
+    let here = env::current_dir()?;
+    let fail_if = fail_if
+        .into_iter()
+        .map(|mut limit| {
+            let path = here.join(&limit.path);
+            limit.path = path.canonicalize().unwrap_or(path);
+            match paths.iter().any(|p| limit.path.starts_with(p)) {
+                true => Ok(limit),
+                false => Err(Error::Io(io::Error::other(format!(
+                    "--fail-if {} isn't in what's being scanned",
+                    limit.path.display()
+                )))),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // a single directory is browsed as is. otherwise, the listing starts from
+    // the directory that holds everything given, showing only those entries.
+    let root = match paths.as_slice() {
+        [dir] if is_dir(dir) => dir.clone(),
+        _ => common_parent(&paths),
+    };
+    let focus = match paths.as_slice() {
+        [file] if !is_dir(file) => Some(file.clone()),
+        _ => None,
+    };
+    let mut cwd = root.clone();
+
+    let block_size = args.block_size.map(|b| b.as_u64()).filter(|&b| b > 0);
+    // scans count apparent sizes, with disk usage alongside. unless asked
+    // for apparent sizes, the two are swapped as entries come in.
+    let mut apparent = args.apparent_size || block_size.is_some();
+    // patterns given one by one come first, so they win over the files'.
+    let mut rules = String::new();
+    for pattern in &args.exclude {
+        rules += &format!("- {pattern}\n");
+    }
+    for file in &args.exclude_from {
+        rules += &fs::read_to_string(file).map_err(|e| Error::Root(file.clone(), e))?;
+        rules += "\n";
+    }
+    // scans that aren't browsed, like scheduled ones, are held to the budget
+    // as a whole by a cgroup where one can be made, kernel time included.
+    // otherwise the workers rest between batches, which keeps the browser
+    // itself responsive.
+    let confined = match (args.cpu_budget, &then) {
+        (Some(_), Then::Browse(_)) | (None, _) => None,
+        (Some(percent), _) => cgroup::confine(percent).ok(),
+    };
+    let options = Options {
+        block_size,
+        exclude_caches: args.exclude_caches,
+        timeout: args.timeout.map(Duration::from_secs),
+        gentle: args.gentle,
+        cpu_budget: args.cpu_budget.filter(|_| confined.is_none()),
+        threads: args.threads.map(|n| n as usize),
+        deterministic: args.deterministic,
+        aliases: aliases.clone(),
+        excludes: Excludes::parse(&rules),
+        respect_gitignore: args.respect_gitignore,
+        xattrs: args.xattrs,
+        count_links: args.count_links,
+    };
+    if args.gentle {
+        priority::lower()?;
+    }
+    let mut sources = match imported {
+        Some(_) => vec![],
+        None => paths
+            .iter()
+            .map(|p| {
+                LocalSource::new(p, options.clone())
+                    .map(Arc::new)
+                    .map_err(|e| Error::Root(p.clone(), e))
+            })
+            .collect::<Result<Vec<_>>>()?,
+    };
+    let imported_from = args.import.clone();
+    let origin = imported.as_ref().map(|imported| {
+        let file = imported_from.as_deref().unwrap_or(Path::new("-"));
+        match imported.scanned {
+            Some(t) => format!(
+                "a scan imported from {}, taken {}",
+                file.display(),
+                audit::timestamp(UNIX_EPOCH + Duration::from_secs(t.max(0) as u64))
+            ),
+            None => format!("a scan imported from {}", file.display()),
+        }
+    });
+    let (started, cpu) = (Instant::now(), cpu_time());
+    let scan = match imported {
+        Some(imported) => scan::Scan::of(imported.entries),
+        None => {
+            let mut scans = sources
+                .iter()
+                .zip(&paths)
+                .map(|(source, p)| scan::scan(source.clone(), p))
+                .collect::<Result<Vec<_>>>()?;
+            match scans.len() {
+                1 => scans.pop().unwrap(),
+                _ => scan::Scan::merge(scans),
+            }
+        }
+    };
+    let errors = scan.errors.clone();
+    let timed_out = scan.timed_out.clone();
+    let control = scan.control.clone();
+    let listening = match &args.control_socket {
+        Some(path) => Some(control::serve(path, control.clone())?),
+        None => None,
+    };
+    let network = paths
+        .iter()
+        .zip(&sources)
+        .filter(|(_, s)| s.is_network())
+        .map(|(p, _)| p.to_string_lossy())
+        .collect::<Vec<_>>();
+    let network = (!network.is_empty()).then(|| {
+        format!(
+            "{} on a network filesystem, scanning gently",
+            network.join(", ")
+        )
+    });
+    let args = match then {
+        Then::Browse(browse) => browse,
+        Then::Total { benchmark } => {
+            if let Some(network) = &network {
+                println!("{network}");
+            }
+            let mut tree = scan.wait();
+            let (elapsed, cpu) = (started.elapsed(), cpu_time().saturating_sub(cpu));
+            tree.data.extend(between(&root, &paths));
+            tree.preprocess();
+            if !apparent {
+                tree.swap_sizes();
+            }
+            let entries = tree.subtree(&root);
+            let dirs = entries
+                .iter()
+                .filter(|i| i.is_dir && i.path != root)
+                .count();
+            println!(
+                "{} in {} files and {} directories",
+                ByteSize(tree.size(&root)),
+                commaify(entries.iter().filter(|i| !i.is_dir).count()),
+                commaify(dirs)
+            );
+            let mounts = skipped(&sources, &paths.iter().cloned().collect(), &excluded);
+            if !mounts.is_empty() {
+                let mounts: Vec<_> = mounts.iter().map(|m| m.to_string_lossy()).collect();
+                println!(
+                    "skipped {} mounts totaling unknown size: {}",
+                    mounts.len(),
+                    mounts.join(", ")
+                );
+            }
+            let caches: usize = sources.iter().map(|s| s.caches().len()).sum();
+            if caches > 0 {
+                println!("{} directories marked with CACHEDIR.TAG", commaify(caches));
+            }
+            if let Some(block_size) = block_size {
+                println!(
+                    "{} of slack space at block size {}",
+                    ByteSize(sources.iter().map(|s| s.slack()).sum::<u64>()),
+                    ByteSize(block_size)
+                );
+            }
+            if benchmark {
+                let run = benchmark::Run {
+                    when: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs()),
+                    elapsed,
+                    cpu,
+                    calls: sources.iter().map(|s| s.calls()).sum(),
+                    entries: entries.len() as u64,
+                    threads: sources.iter().map(|s| s.threads()).sum(),
+                    peak: peak_memory(),
+                    paths: paths.clone(),
+                };
+                println!(
+                    "scanned in {elapsed:.2?}, with {cpu:.2?} of CPU time and {} filesystem calls",
+                    commaify(run.calls)
+                );
+                println!("{}", memory_use(&tree));
+                if let Some(file) = dirs::state_dir().map(|dir| dir.join("benchmarks")) {
+                    match benchmark::previous(&file, &paths)? {
+                        Some(before) => println!("{}", benchmark::compare(&before, &run)),
+                        None => println!("no earlier run of the same paths to compare with"),
+                    }
+                    benchmark::record(&file, &run)?;
+                }
+            }
+            drop(listening); // exit skips destructors
+            finish(&tree, &fail_if, errors.load(Ordering::Relaxed));
+        }
+        then => {
+            let mut tree = scan.collect();
+            tree.data.extend(between(&root, &paths));
+            tree.preprocess();
+            if !apparent {
+                tree.swap_sizes();
+            }
+            let (file, sign) = match &then {
+                Then::Export { file, sign, .. } => (file.as_deref(), sign.as_deref()),
+                Then::Report { file, .. } => (file.as_deref(), None),
+                Then::Diff { .. } => (None, None),
+                Then::Browse(_) | Then::Total { .. } => unreachable!(),
+            };
+            let names = user_names();
+            let owner = |uid| names.get(&uid).cloned().unwrap_or_else(|| uid.to_string());
+            let out: Box<dyn Write> = match file {
+                Some(file)
+                    if !matches!(
+                        then,
+                        Then::Export {
+                            format: Export::Sqlite,
+                            ..
+                        }
+                    ) =>
+                {
+                    Box::new(fs::File::create(file)?)
+                }
+                _ => Box::new(io::stdout().lock()),
+            };
+            let mut out = io::BufWriter::new(out);
+            let written = match &then {
+                Then::Report { format, prune, .. } => {
+                    let report = match format {
+                        Report::Tree => output::tree(&tree, &root, prune),
+                        Report::Md => output::markdown(&tree, &root, prune),
+                        Report::Dot => output::dot(&tree, &root, prune),
+                        Report::Top => output::top(&tree, &root, prune),
+                    };
+                    out.write_all(report.as_bytes())
+                }
+                Then::Diff {
+                    sides: [left, right],
+                } => out.write_all(output::diff(&tree, left, right).as_bytes()),
+                Then::Export { format, .. } => match format {
+                    Export::Parquet => parquet::write(&mut out, tree.subtree(&root), owner),
+                    Export::Sqlite => {
+                        let scanned = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |d| d.as_secs());
+                        let mut db = io::BufWriter::new(fs::File::create(file.unwrap())?);
+                        sqlite::write(&mut db, &root, tree.subtree(&root), scanned as i64, owner)
+                            .and_then(|()| db.flush())
+                    }
+                    Export::Ncdu => {
+                        // ncdu has both sizes, each in its place.
+                        if !apparent {
+                            tree.swap_sizes();
+                        }
+                        let scanned = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |d| d.as_secs());
+                        let written = output::ncdu(&mut out, tree.subtree(&root), scanned as i64);
+                        if !apparent {
+                            tree.swap_sizes(); // back, for --fail-if
+                        }
+                        written
+                    }
+                    Export::Sha256 => {
+                        let base = if root.is_dir() {
+                            &root
+                        } else {
+                            root.parent().unwrap_or(&root)
+                        };
+                        manifest::write(&mut out, base, tree.subtree(&root)).map(|failed| {
+                            for (path, e) in failed {
+                                eprintln!("couldn't hash {}: {e}", path.display());
+                            }
+                        })
+                    }
+                },
+                Then::Browse(_) | Then::Total { .. } => unreachable!(),
+            }
+            .and_then(|()| out.flush());
+            match written {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {} // piped into head
+                result => result?,
+            }
+            if let (Some(key), Some(file)) = (sign, file) {
+                signing::sign(file, key).map_err(|e| {
+                    io::Error::new(e.kind(), format!("can't sign {}: {e}", file.display()))
+                })?;
+            }
+            let errors = errors.load(Ordering::Relaxed);
+            if errors > 0 {
+                eprintln!("{} entries couldn't be read", commaify(errors));
+            }
+            drop(listening);
+            finish(&tree, &fail_if, errors);
+        }
+    };
+
+    let marks = match &args.marks {
+        Some(file) => cleanup::load_marks(file).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("can't read marks from {}: {e}", file.display()),
+            )
+        })?,
+        None => vec![],
+    };
+    let mut mouse = !args.no_mouse && !args.accessible;
+    let _guard = TerminalGuard::enter(mouse, args.inline.is_some() || args.accessible)?;
+    let viewport = match args.inline {
+        Some(lines) => Viewport::Inline(lines),
+        None => Viewport::Fullscreen,
+    };
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal =
+        Terminal::with_options(backend, TerminalOptions { viewport }).map_err(Error::Terminal)?;
+
+    let mut message: Option<String> = network.or(origin); // shown at the bottom until the next key press
+
+    // the free space here says nothing about where an import was taken.
+    let mut free = match imported_from {
+        Some(_) => None,
+        None => fsstat::free_space(&cwd).ok(),
+    };
+    let mut list: StatefulList = StatefulList::new(Listing::default());
+    list.graph = args.graph;
+    list.dir = cwd.clone();
+    let mut collapse = false; // show chains of lone directories as one row
+
+    // the directory the view is confined to with F, and those it was before.
+    let mut top = root.clone();
+    let mut focused: Vec<PathBuf> = vec![];
+    let mut hidden: Vec<Tree> = vec![]; // entries hidden with x, to put back with X
+    let mut deleting: Option<(Info, Confirm)> = None; // the entry d is waiting to delete
+
+    // with W, the marked entries are taken off the totals to preview deleting
+    // them; this is how much comes off each path.
+    let mut whatif = false;
+    let mut pretend: HashMap<PathBuf, u64> = HashMap::new();
+
+    // the listing is browsable while the scan is still running: entries are
+    // collected as they arrive and a sorted, accumulated snapshot is rebuilt
+    // every so often. rebuilding is O(n log n), so back off as the tree grows.
+    let mut scan = Some(scan);
+    let began = Instant::now(); // to tell how fast files have grown since
+    let mut unfocused = false; // whether to notify when the scan finishes
+    let mut pending: Vec<Info> = between(&root, &paths);
+    let mut tree = Tree { data: vec![] };
+    let mut last_rebuild: Option<Instant> = None;
+    let mut rebuild_cost = Duration::ZERO;
+    let mut spinner = SPINNER.iter().cycle();
+
+    // other filesystems mounted below the root are skipped, but can be scanned
+    // separately on request and grafted into the tree once they're done.
+    let mut included: HashSet<PathBuf> = paths.iter().cloned().collect();
+    let mut includes: Vec<(scan::Scan, Vec<Info>)> = vec![];
+    let mut panel: Option<ListState> = None; // the list of skipped mounts, when open
+    let mut open: Option<(ListState, Vec<OpenFile>)> = None; // files processes have open
+    let mut repos: Option<(ListState, Vec<git::Repo>)> = None; // git repositories and their upkeep
+    let mut mailboxes: Option<(ListState, Vec<Mailbox>)> = None; // mail, totalled per mailbox
+
+    // crash artifacts, and the confirmation of deleting them once asked.
+    let mut leftovers: Option<(ListState, Vec<Artifact>, Option<Confirm>)> = None;
+    let mut advice: Option<(ListState, Vec<Log>)> = None; // logs to rotate or compress
+    let mut inspected: Option<(PathBuf, Vec<String>)> = None; // the details popup
+    let mut watcher: Option<activity::Watcher> = None; // looking for files being written
+
+    let double_click = Duration::from_millis(args.double_click_ms);
+    let mut last_click: Option<(Instant, usize)> = None;
+
+    let mut prompt: Option<(Input, Prompt)> = None;
+    let audit = AuditLog::new(args.audit_log.clone());
+    let mut notes = Annotations {
+        marks: marks.into_iter().collect(),
+        changed: HashMap::new(),
+        compressible: HashMap::new(),
+        caches: HashSet::new(),
+        aliases,
+        active: HashMap::new(),
+        backups: HashMap::new(),
+        holes: HashMap::new(),
+        undeletable: HashMap::new(),
+    };
+    let names = user_names();
+    check_deletable(&mut notes.undeletable, notes.marks.iter(), &names);
+    // estimates of compressibility arrive here as they're worked out.
+    let (estimates_tx, estimates) = mpsc::channel::<(PathBuf, Option<f64>)>();
+    // messages in mboxes are counted in the background.
+    let (counts_tx, counts) = mpsc::channel::<(PathBuf, usize)>();
+    // logs compressed from the report, with their new size on success.
+    let (squeezed_tx, squeezed) = mpsc::channel::<(Info, io::Result<PathBuf>)>();
+    // maintenance run in git repositories reports back here.
+    let (upkeep_tx, upkeep) = mpsc::channel::<(PathBuf, &str, io::Result<()>)>();
+    // and stats from backup tools, for the repositories found.
+    let (backups_tx, backups) =
+        mpsc::channel::<(PathBuf, backup::Kind, io::Result<backup::Usage>)>();
+    let mut dupes: Option<mpsc::Receiver<Vec<Vec<PathBuf>>>> = None; // detection in progress
+    let mut finder: Option<Finder> = None;
+    let mut searching: Option<mpsc::Receiver<Results>> = None; // a regex search going on
+    let mut results: Option<Results> = None; // and what it found
+    let mut show: Option<PathBuf> = None; // picked in another view, to select in its directory
+    let mut comparing: Option<PathBuf> = None; // the left side of a comparison, picked with c
+    let mut comparison: Option<Comparison> = None;
+
+    let mut stats: Option<(PathBuf, usize, Stats)> = None; // shown instead of the listing
+    let mut chart = Chart::Shape;
+    let mut debug = false; // the hidden worker table, toggled with F12
+
+    let mut spill = args.max_entries.map(Spill::new).transpose()?;
+
+    let mut size = ByteSize(0);
+    let mut dirty = true;
+    let mut last_frame = Instant::now() - FRAME;
+    let mut macros = Macros::default();
+    let mut replay: VecDeque<KeyEvent> = VecDeque::new(); // keys of a macro still to go
+
+    // what's been announced with --accessible: the directory, the selection,
+    // the last message, whether a scan was running and the prompt's input.
+    let mut heard = (PathBuf::new(), None, None, true, None);
+    let mut titled = (PathBuf::new(), ByteSize(0)); // what the terminal's title says
+    'main: loop {
+        if let Some(s) = &scan {
+            let mut finished = false;
+            loop {
+                match s.rx.try_recv() {
+                    Ok(mut batch) => {
+                        if !apparent {
+                            swap_sizes(&mut batch);
+                        }
+                        pending.append(&mut batch)
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        finished = true;
+                        break;
+                    }
+                }
+            }
+
+            let due = last_rebuild
+                .is_none_or(|t| t.elapsed() > (rebuild_cost * 4).max(Duration::from_millis(250)));
+            if finished {
+                tree = Tree {
+                    data: mem::take(&mut pending),
+                };
+                tree.preprocess();
+                scan = None;
+                if let Some(spill) = &mut spill {
+                    spill.shrink(&mut tree, &cwd)?;
+                }
+                let mounts = skipped(&sources, &included, &excluded);
+                if !mounts.is_empty() {
+                    message = Some(format!(
+                        "skipped {} mounts on other devices, M to review",
+                        mounts.len()
+                    ));
+                }
+                let stuck = timed_out.lock().unwrap().clone();
+                if !stuck.is_empty() {
+                    let stuck: Vec<_> = stuck.iter().map(|p| p.to_string_lossy()).collect();
+                    message = Some(format!(
+                        "gave up on {} directories that stopped responding: {}",
+                        stuck.len(),
+                        stuck.join(", ")
+                    ));
+                }
+                notes.caches = sources.iter().flat_map(|s| s.caches()).collect();
+                notes.holes = holes(&sources);
+                if unfocused && began.elapsed() >= Duration::from_secs(args.notify_after) {
+                    notify(&format!(
+                        "adansonia finished scanning {}: {}",
+                        root.display(),
+                        ByteSize(tree.size(&root))
+                    ))?;
+                }
+                if message.is_none() && !notes.caches.is_empty() {
+                    let total: u64 = notes.caches.iter().map(|c| tree.size(c)).sum();
+                    let count = commaify(notes.caches.len());
+                    message = Some(match options.exclude_caches {
+                        true => format!("left out the contents of {count} cache directories"),
+                        false => format!("{count} cache directories hold {}", ByteSize(total)),
+                    });
+                }
+                if let Some(file) = &focus {
+                    list.select_path(file);
+                    message = Some(details(file, tree.size(file)));
+                }
+            } else if due {
+                let now = Instant::now();
+                tree = Tree {
+                    data: pending.clone(),
+                };
+                tree.preprocess();
+                rebuild_cost = now.elapsed();
+                last_rebuild = Some(Instant::now());
+            }
+            if finished || due {
+                list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                size = ByteSize(tree.size(&top));
+            }
+            dirty = true; // keep the spinner and item count moving
+        }
+
+        let mut k = 0;
+        while k < includes.len() {
+            let (s, data) = &mut includes[k];
+            let finished = loop {
+                match s.rx.try_recv() {
+                    Ok(mut batch) => {
+                        if !apparent {
+                            swap_sizes(&mut batch);
+                        }
+                        data.append(&mut batch)
+                    }
+                    Err(TryRecvError::Empty) => break false,
+                    Err(TryRecvError::Disconnected) => break true,
+                }
+            };
+            if !finished {
+                k += 1;
+                continue;
+            }
+            let (s, data) = includes.swap_remove(k);
+            notes.caches = sources.iter().flat_map(|s| s.caches()).collect();
+            notes.holes = holes(&sources);
+            errors.fetch_add(s.errors.load(Ordering::Relaxed), Ordering::Relaxed);
+            if scan.is_some() {
+                // the main scan hasn't finished yet, so it'll sort these in.
+                pending.extend(data);
+            } else {
+                let mut sub = Tree { data };
+                sub.preprocess();
+                tree.graft(sub);
+                list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                size = ByteSize(tree.size(&top));
+            }
+            dirty = true;
+        }
+
+        for (path, saved) in estimates.try_iter() {
+            match saved {
+                Some(saved) => notes.compressible.insert(path, saved),
+                None => notes.compressible.remove(&path),
+            };
+            dirty = true;
+        }
+        for (path, kind, usage) in backups.try_iter() {
+            message = Some(match &usage {
+                Ok(u) => format!(
+                    "{} repository {}: {} backed up, {} stored",
+                    kind.name(),
+                    path.display(),
+                    ByteSize(u.logical),
+                    ByteSize(u.stored)
+                ),
+                Err(e) => format!("{} repository {}: {e}", kind.name(), path.display()),
+            });
+            notes.backups.insert(path, (kind, usage));
+            dirty = true;
+        }
+        for (path, messages) in counts.try_iter() {
+            let found = mailboxes.iter_mut().flat_map(|(_, found)| found.iter_mut());
+            for mailbox in found.filter(|m| m.path == path) {
+                mailbox.messages = Some(messages);
+            }
+            dirty = true;
+        }
+        for (log, result) in squeezed.try_iter() {
+            match result {
+                Ok(path) => {
+                    tree.remove(&log.path);
+                    let source = source_for(&sources, &path);
+                    let mut compressed = Info {
+                        size: source.size_on_disk(&path, true).unwrap_or(0),
+                        disk: source.size_on_disk(&path, false).unwrap_or(0),
+                        path,
+                        ..log.clone()
+                    };
+                    if !apparent {
+                        mem::swap(&mut compressed.size, &mut compressed.disk);
+                    }
+                    let mut sub = Tree {
+                        data: vec![compressed.clone()],
+                    };
+                    sub.preprocess();
+                    tree.graft(sub);
+                    list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                    size = ByteSize(tree.size(&top));
+                    message = Some(match audit.record("compress", &log.path, Some(log.size)) {
+                        Ok(()) => format!(
+                            "compressed {} from {} to {}",
+                            log.path.display(),
+                            ByteSize(log.size),
+                            ByteSize(compressed.size)
+                        ),
+                        Err(e) => format!("couldn't write audit log: {e}"),
+                    });
+                }
+                Err(e) => {
+                    message = Some(format!("couldn't compress {}: {e}", log.path.display()));
+                }
+            }
+            dirty = true;
+        }
+        for (repo, command, result) in upkeep.try_iter() {
+            let git = repo.join(".git");
+            message = Some(match result {
+                Ok(()) => {
+                    let now = source_for(&sources, &git).size_on_disk(&git, apparent);
+                    notes.changed.insert(git.clone(), now);
+                    format!(
+                        "{command} in {} finished, .git went from {} to {}",
+                        repo.display(),
+                        ByteSize(tree.size(&git)),
+                        ByteSize(now.unwrap_or(0))
+                    )
+                }
+                Err(e) => format!("{command} in {} failed: {e}", repo.display()),
+            });
+            dirty = true;
+        }
+
+        if let Some(files) = watcher.as_ref().and_then(|w| w.rx.try_iter().last()) {
+            // directories are as busy as everything being written inside them.
+            notes.active.clear();
+            for (path, rate) in files {
+                for p in path.ancestors().take_while(|p| p.starts_with(&root)) {
+                    *notes.active.entry(p.to_path_buf()).or_default() += rate;
+                }
+            }
+            dirty = true;
+        }
+
+        if let Some(groups) = dupes.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            // charge each copy to everything above it.
+            let mut duplicated: HashMap<PathBuf, u64> = HashMap::new();
+            let mut wasted = 0;
+            for group in &groups {
+                let size = tree.size(&group[0]);
+                wasted += size * (group.len() as u64 - 1);
+                for path in group {
+                    for p in path.ancestors().take_while(|p| p.starts_with(&root)) {
+                        *duplicated.entry(p.to_path_buf()).or_default() += size;
+                    }
+                }
+            }
+            message = Some(format!(
+                "{} sets of duplicates, {} could be saved; U sorts by unique size",
+                commaify(groups.len()),
+                ByteSize(wasted)
+            ));
+            list.duplicated = duplicated;
+            list.set_items(listing(&tree, &cwd, collapse, &pretend));
+            dupes = None;
+            dirty = true;
+        }
+        if let Some(f) = &mut finder {
+            if let Some(matches) = f.search.as_ref().and_then(|s| s.done()) {
+                f.matches = Some(matches);
+                f.search = None;
+                f.state.select(Some(0));
+                dirty = true;
+            }
+        }
+        if let Some(done) = searching.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            message = match done.total {
+                0 => Some(format!(
+                    "nothing under {} matches {}",
+                    top.display(),
+                    done.pattern
+                )),
+                _ => None,
+            };
+            results = Some(done).filter(|r| r.total > 0);
+            searching = None;
+            dirty = true;
+        }
+        if let Some(path) = show.take() {
+            if let Some(spill) = &mut spill {
+                spill.page_in(&mut tree, &path)?;
+            }
+            if tree.find(&path).is_some() {
+                // it may be outside the directory focused on.
+                if !path.starts_with(&top) {
+                    top = root.clone();
+                    focused.clear();
+                    size = ByteSize(tree.size(&top));
+                }
+                let parent = path.parent().filter(|p| p.starts_with(&top));
+                cwd = parent.unwrap_or(&top).to_path_buf();
+                list.enter(&cwd, listing(&tree, &cwd, collapse, &pretend));
+                list.select_path(&path);
+            } else {
+                message = Some(format!("{} isn't in the scan", path.display()));
+            }
+            dirty = true;
+        }
+        // keep spinners turning for background work.
+        dirty |= dupes.is_some() || !includes.is_empty() || debug;
+        dirty |= finder.as_ref().is_some_and(|f| f.search.is_some());
+
+        if dirty && args.accessible {
+            // say what changed as a line of text, which screen readers read
+            // out, instead of drawing. sizes changing as the scan goes on
+            // aren't worth interrupting for.
+            let selected = list.selected().map(|i| &list.items[i]);
+            let mut lines = vec![];
+            if heard.0 != cwd {
+                lines.push(match list.items.len() {
+                    0 if scan.is_some() => format!("scanning {}", cwd.display()),
+                    0 => format!("{} is empty", cwd.display()),
+                    n => format!("in {}, {n} entries", cwd.display()),
+                });
+            }
+            if heard.0 != cwd || heard.1.as_ref() != selected.map(|i| &i.path) {
+                if let Some(i) = selected {
+                    lines.push(format!(
+                        "{}, {}, {}, {} of {}",
+                        i.path
+                            .file_name()
+                            .unwrap_or(i.path.as_os_str())
+                            .to_string_lossy(),
+                        ByteSize(i.size),
+                        if i.is_dir { "directory" } else { "file" },
+                        list.selected().unwrap() + 1,
+                        list.items.len()
+                    ));
+                }
+            }
+            if message.is_some() && heard.2 != message {
+                lines.extend(message.clone());
+            }
+            if heard.3 && scan.is_none() {
+                lines.push(format!("scan finished, {} in total", size));
+            }
+            let typed = prompt
+                .as_ref()
+                .map(|(input, p)| format!("{}: {}", input.label(), p.text()));
+            if typed.is_some() && heard.4 != typed {
+                lines.extend(typed.clone());
+            }
+            heard = (
+                cwd.clone(),
+                selected.map(|i| i.path.clone()),
+                message.clone(),
+                scan.is_some(),
+                typed,
+            );
+            let mut out = io::stdout().lock();
+            for line in lines {
+                // raw mode doesn't return the cursor to the start on its own.
+                write!(out, "{line}\r\n")?;
+            }
+            out.flush()?;
+            dirty = false;
+        }
+
+        if dirty && last_frame.elapsed() >= FRAME {
+            last_frame = Instant::now();
+            let progress = match &scan {
+                Some(s) if s.control.is_paused() => {
+                    format!(" paused at {} items, p resumes", commaify(pending.len()))
                 }
+                Some(_) => format!(
+                    " {} scanning… {} items",
+                    spinner.next().unwrap(),
+                    commaify(pending.len())
+                ),
+                None if dupes.is_some() => {
+                    format!(" {} looking for duplicates…", spinner.next().unwrap())
+                }
+                None if !includes.is_empty() => format!(
+                    " {} including {} mounts…",
+                    spinner.next().unwrap(),
+                    includes.len()
+                ),
+                None => String::new(),
+            };
+            let unreadable = match errors.load(Ordering::Relaxed) {
+                0 => String::new(),
+                n => format!(" [{} unreadable]", commaify(n)),
+            };
+            let marked = match notes.marks.len() {
+                0 => String::new(),
+                n => format!(" [{n} marked]"),
+            } + &match hidden.len() {
+                0 => String::new(),
+                n => format!(" [{n} hidden]"),
+            };
+            let filter = match &list.filter {
+                Some(filter) => format!(" [filter: {filter}]"),
+                None => String::new(),
+            };
+            let filter = match apparent {
+                true => filter + " [apparent sizes]",
+                false => filter,
+            };
+            let focused_on = match focused.is_empty() {
+                true => String::new(),
+                false => format!(
+                    " [focused on {:?}, Backspace returns]",
+                    top.file_name().unwrap()
+                ),
+            };
+            let marked = match whatif {
+                true => format!(
+                    "{marked} [what if: {} freed]",
+                    ByteSize(pretend.get(&root).copied().unwrap_or(0))
+                ),
+                false => marked,
+            };
+            let recording = match macros.recording() {
+                Some(r) => format!(" [recording @{r}]"),
+                None => String::new(),
+            };
+            let mounts = skipped(&sources, &included, &excluded);
+            let here = (cwd.clone(), ByteSize(tree.size(&cwd)));
+            if titled != here {
+                set_title(&here.0, here.1)?;
+                titled = here;
+            }
+            if let Some((dir, len, shape)) = &mut stats {
+                // the subtree changes as the scan goes on or we move around.
+                if *dir != cwd || *len != tree.data.len() {
+                    (*dir, *len) = (cwd.clone(), tree.data.len());
+                    *shape = Stats::new(tree.subtree(&cwd));
+                }
+            }
+            list.total = tree
+                .size(&cwd)
+                .saturating_sub(pretend.get(&cwd).copied().unwrap_or(0));
+            let typing = prompt.is_some()
+                || deleting.as_ref().is_some_and(|(_, c)| c.prompt().is_some())
+                || leftovers
+                    .as_ref()
+                    .is_some_and(|(_, _, c)| c.as_ref().is_some_and(|c| c.prompt().is_some()));
+            terminal
+                .draw(|frame| {
+                    let [tabs, area, footer] = Layout::vertical([
+                        Constraint::Length(all_mounts.into()),
+                        Constraint::Min(0),
+                        Constraint::Length(typing.into()),
+                    ])
+                    .areas(frame.area());
+                    if all_mounts {
+                        let titles = paths
+                            .iter()
+                            .map(|m| format!("{} {}", m.display(), ByteSize(tree.size(m))));
+                        let tabs_widget = Tabs::new(titles)
+                            .select(paths.iter().rposition(|m| cwd.starts_with(m)))
+                            .highlight_style(Style::default().fg(Color::Black).bg(Color::Yellow));
+                        frame.render_widget(tabs_widget, tabs);
+                    }
+                    let name = cwd.file_name().unwrap_or(OsStr::new("/"));
+                    match &stats {
+                        _ if debug => render_workers(
+                            frame,
+                            area,
+                            format!("Workers{progress}"),
+                            &control.workers(),
+                            memory_use(&tree),
+                        ),
+                        Some((_, _, shape)) => render_stats(
+                            frame,
+                            area,
+                            format!("Statistics - {name:?}{progress}"),
+                            shape,
+                            chart,
+                        ),
+                        None => list.render(
+                            frame,
+                            area,
+                            format!(
+                                "Files - {:?} {} ({}){}{}{}{}{}{}{}",
+                                name,
+                                list.items.len(),
+                                ByteSize(
+                                    size.as_u64()
+                                        .saturating_sub(pretend.get(&top).copied().unwrap_or(0))
+                                ),
+                                focused_on,
+                                list.sort.describe(),
+                                unreadable,
+                                marked,
+                                filter,
+                                recording,
+                                progress,
+                            ),
+                            message.as_deref(),
+                            free.map(|f| f + pretend.get(&root).copied().unwrap_or(0)),
+                            &notes,
+                        ),
+                    }
+                    if let Some((input, prompt)) = &prompt {
+                        prompt.render(frame, footer, input.label());
+                    }
+                    if let Some((confirm, prompt)) =
+                        deleting.as_ref().and_then(|(_, c)| Some((c, c.prompt()?)))
+                    {
+                        prompt.render(frame, footer, &confirm.question);
+                    }
+                    if let Some(state) = &mut panel {
+                        let block = Block::bordered()
+                            .title(format!("Skipped mounts ({})", mounts.len()))
+                            .title_bottom("enter: include, a: include all, s: show in listing, esc: close");
+                        let rows = mounts.iter().map(|m| m.display().to_string()).collect();
+                        render_popup(frame, block, rows, None, state);
+                    }
+                    if let Some((state, files)) = &mut open {
+                        let rows: Vec<_> = files
+                            .iter()
+                            .map(|f| {
+                                format!(
+                                    "{:>10} {:<16} {}{}",
+                                    ByteSize(f.size).to_string(),
+                                    format!("{} ({})", f.holder.name, f.holder.pid),
+                                    if f.writing { "writing " } else { "" },
+                                    f.path.display()
+                                )
+                            })
+                            .collect();
+                        let block = Block::bordered()
+                            .title(format!("Open files under {}", cwd.display()))
+                            .title_bottom("enter: show in listing, esc: close");
+                        render_popup(frame, block, rows, None, state);
+                    }
+                    if let Some((path, lines)) = &inspected {
+                        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+                        let area = centered(
+                            frame.area(),
+                            (width as u16 + 4).max(60),
+                            lines.len() as u16 + 2,
+                        );
+                        let popup = List::new(lines.iter().map(String::as_str)).block(
+                            Block::bordered()
+                                .title(path.display().to_string())
+                                .title_bottom("any key closes"),
+                        );
+                        frame.render_widget(Clear, area);
+                        frame.render_widget(popup, area);
+                    }
+                    if let Some((state, found)) = &mut advice {
+                        let rows: Vec<_> = found
+                            .iter()
+                            .map(|log| {
+                                format!(
+                                    "{:>10} {:>12} {:<8}  {}",
+                                    ByteSize(log.info.size).to_string(),
+                                    match log.growth {
+                                        g if g >= 1.0 => format!("+{}/s", ByteSize(g as u64)),
+                                        _ => String::new(),
+                                    },
+                                    match log.advice {
+                                        logs::Advice::Rotate => "rotate",
+                                        logs::Advice::Compress => "compress",
+                                    },
+                                    log.info.path.display()
+                                )
+                            })
+                            .collect();
+                        let block = Block::bordered()
+                            .title(format!(
+                                "Logs to rotate or compress under {}",
+                                cwd.display()
+                            ))
+                            .title_bottom(
+                                "z: compress, a: compress all rotated, s: show in listing, esc: close",
+                            );
+                        render_popup(frame, block, rows, None, state);
+                    }
+                    if let Some((state, found, confirm)) = &mut leftovers {
+                        let rows: Vec<_> = found
+                            .iter()
+                            .map(|a| {
+                                format!(
+                                    "{:>10} {:<14} {}{}",
+                                    ByteSize(a.info.size).to_string(),
+                                    a.kind,
+                                    a.info.path.display(),
+                                    match notes.undeletable.get(&a.info.path) {
+                                        Some(why) => format!(" (can't delete: {why})"),
+                                        None => String::new(),
+                                    }
+                                )
+                            })
+                            .collect();
+                        let total: u64 = found.iter().map(|a| a.info.size).sum();
+                        let bottom = match confirm {
+                            Some(c) if c.prompt().is_some() => "esc: don't delete them".to_string(),
+                            Some(c) => format!("{}, any other key doesn't", c.question),
+                            None => "enter: show in listing, d: delete all, esc: close".to_string(),
+                        };
+                        // wide enough for the question to be read whole.
+                        let width = rows
+                            .iter()
+                            .map(|r| r.chars().count())
+                            .chain([bottom.chars().count()])
+                            .max()
+                            .unwrap_or(0) as u16
+                            + 6;
+                        let block = Block::bordered()
+                            .title(format!(
+                                "Crash and debug leftovers under {}: {} files, {}",
+                                cwd.display(),
+                                found.len(),
+                                ByteSize(total)
+                            ))
+                            .title_bottom(bottom);
+                        render_popup(frame, block, rows, Some(width), state);
+                        if let Some(c) = confirm {
+                            if let Some(prompt) = c.prompt() {
+                                prompt.render(frame, footer, &c.question);
+                            }
+                        }
+                    }
+                    if let Some((state, found)) = &mut mailboxes {
+                        let rows: Vec<_> = found
+                            .iter()
+                            .map(|m| {
+                                format!(
+                                    "{:>10} {:>9} messages  {:<7}  {}",
+                                    ByteSize(m.size).to_string(),
+                                    match m.messages {
+                                        Some(n) => commaify(n),
+                                        None => "…".to_string(),
+                                    },
+                                    match m.format {
+                                        mail::Format::Maildir => "maildir",
+                                        mail::Format::Mbox => "mbox",
+                                    },
+                                    m.path.display()
+                                )
+                            })
+                            .collect();
+                        let block = Block::bordered()
+                            .title(format!("Mailboxes under {}", cwd.display()))
+                            .title_bottom("enter: show in listing, esc: close");
+                        render_popup(frame, block, rows, None, state);
+                    }
+                    if let Some(f) = &mut finder {
+                        let best = f.matches.as_ref().map_or(&[][..], |m| &m.best[..]);
+                        let rows: Vec<_> = best
+                            .iter()
+                            .map(|&i| {
+                                let path = &f.paths[i];
+                                let (size, slash) = match tree.find(&top.join(path)) {
+                                    Some(i) => (ByteSize(i.size).to_string(), i.is_dir),
+                                    None => ("gone".to_string(), false),
+                                };
+                                let slash = if slash { "/" } else { "" };
+                                format!("{size:>10}  {}{slash}", path.display())
+                            })
+                            .collect();
+                        let area = centered(
+                            frame.area(),
+                            (frame.area().width * 4 / 5).max(60),
+                            FINDER_ROWS as u16 + 3,
+                        );
+                        let title = match (&f.search, &f.matches) {
+                            (Some(_), _) => format!(
+                                "Find {} searching {} entries…",
+                                spinner.next().unwrap(),
+                                commaify(f.paths.len())
+                            ),
+                            (None, Some(m)) => format!(
+                                "Find - {} of {} entries match",
+                                commaify(m.all.len()),
+                                commaify(f.paths.len())
+                            ),
+                            (None, None) => format!("Find - {} entries", commaify(f.paths.len())),
+                        };
+                        let block = Block::bordered()
+                            .title(title)
+                            .title_bottom("enter: go to, esc: close");
+                        let inner = block.inner(area);
+                        let [input, results] =
+                            Layout::vertical([Constraint::Length(1), Constraint::Min(0)])
+                                .areas(inner);
+                        frame.render_widget(Clear, area);
+                        frame.render_widget(block, area);
+                        frame.render_stateful_widget(selectable(rows), results, &mut f.state);
+                        f.prompt.render(frame, input, "find");
+                    }
+                    if let Some(r) = &mut results {
+                        let names: Vec<String> = r
+                            .found
+                            .iter()
+                            .map(|i| {
+                                let name = i.path.file_name().unwrap_or(i.path.as_os_str());
+                                let slash = if i.is_dir { "/" } else { "" };
+                                format!("{}{slash}", name.to_string_lossy())
+                            })
+                            .collect();
+                        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+                        let width = width.min(40);
+                        let rows: Vec<_> = r
+                            .found
+                            .iter()
+                            .zip(&names)
+                            .map(|(i, name)| {
+                                format!(
+                                    "{:>10}  {name:<width$}  in {}",
+                                    ByteSize(i.size).to_string(),
+                                    i.path.parent().unwrap().display()
+                                )
+                            })
+                            .collect();
+                        let title = match r.total > r.found.len() {
+                            true => format!(
+                                "Search {} - {} matches, the largest {} shown",
+                                r.pattern,
+                                commaify(r.total),
+                                commaify(r.found.len())
+                            ),
+                            false => format!("Search {} - {} matches", r.pattern, commaify(r.total)),
+                        };
+                        let block = Block::bordered()
+                            .title(title)
+                            .title_bottom("enter: go to, esc: close");
+                        let width = frame.area().width * 4 / 5;
+                        render_popup(frame, block, rows, Some(width), &mut r.state);
+                    }
+                    if let Some(c) = &mut comparison {
+                        let row = |d: &Difference, size: Option<u64>| match size {
+                            Some(size) => format!(
+                                "{:>10}  {}{}",
+                                ByteSize(size).to_string(),
+                                d.path.display(),
+                                if d.is_dir { "/" } else { "" }
+                            ),
+                            None => String::new(),
+                        };
+                        let left: Vec<_> = c.differences.iter().map(|d| row(d, d.left)).collect();
+                        let right: Vec<_> = c
+                            .differences
+                            .iter()
+                            .map(|d| match (d.left, d.change()) {
+                                (Some(_), n) if d.right.is_some() => format!(
+                                    "{}  {}{}",
+                                    row(d, d.right),
+                                    if n < 0 { "-" } else { "+" },
+                                    ByteSize(n.unsigned_abs())
+                                ),
+                                _ => row(d, d.right),
+                            })
+                            .collect();
+                        let area = centered(
+                            frame.area(),
+                            (frame.area().width * 9 / 10).max(60),
+                            left.len() as u16 + 2,
+                        );
+                        let [l, r] = Layout::horizontal([Constraint::Fill(1); 2]).areas(area);
+                        let shown = match c.differences.len() < c.only[0] + c.only[1] + c.changed {
+                            true => format!(", the biggest {} shown", commaify(c.differences.len())),
+                            false => String::new(),
+                        };
+                        let panels = [
+                            selectable(left).block(
+                                Block::bordered()
+                                    .title(format!(
+                                        "{} - {} only here",
+                                        c.sides[0].display(),
+                                        commaify(c.only[0])
+                                    ))
+                                    .title_bottom(format!(
+                                        "{} changed{shown}",
+                                        commaify(c.changed)
+                                    )),
+                            ),
+                            selectable(right).block(
+                                Block::bordered()
+                                    .title(format!(
+                                        "{} - {} only here",
+                                        c.sides[1].display(),
+                                        commaify(c.only[1])
+                                    ))
+                                    .title_bottom("enter: show in listing, esc: close"),
+                            ),
+                        ];
+                        frame.render_widget(Clear, area);
+                        for (panel, side) in panels.into_iter().zip([l, r]) {
+                            // the right one scrolls to wherever the left one did.
+                            frame.render_stateful_widget(panel, side, &mut c.state);
+                        }
+                    }
+                    if let Some((state, found)) = &mut repos {
+                        let rows: Vec<_> = found
+                            .iter()
+                            .map(|r| {
+                                format!(
+                                    "{:>10} .git {:>10} work {:>3} packs {:>6} loose  {}{}",
+                                    ByteSize(r.git).to_string(),
+                                    ByteSize(r.work).to_string(),
+                                    r.packs,
+                                    r.loose,
+                                    r.path.display(),
+                                    match r.advice() {
+                                        Some(command) => format!(" (run {command})"),
+                                        None => String::new(),
+                                    }
+                                )
+                            })
+                            .collect();
+                        let block = Block::bordered()
+                            .title(format!("Git repositories under {}", cwd.display()))
+                            .title_bottom("enter: run suggestion, s: show in listing, esc: close");
+                        render_popup(frame, block, rows, None, state);
+                    }
+                })
+                .map_err(Error::Terminal)?;
+            dirty = false;
+        }
+
+        // a frame held back by the cap is drawn as soon as it's allowed.
+        let wait = match dirty {
+            true => FRAME.saturating_sub(last_frame.elapsed()),
+            false => TICK,
+        };
+        if replay.is_empty() && !event::poll(wait).map_err(Error::Terminal)? {
+            continue;
+        }
+        // drain everything that's queued before drawing again, so a burst of
+        // scroll events costs one frame rather than one each.
+        loop {
+            let mut activate = false;
+            let event = match replay.pop_front() {
+                Some(key) => Event::Key(key),
+                None => event::read().map_err(Error::Terminal)?,
+            };
+            if let Event::Key(_) = event {
+                message = None;
+            }
+            // terminals that don't report focus are taken to be focused.
+            match event {
+                Event::FocusLost => unfocused = true,
+                Event::FocusGained => unfocused = false,
                 _ => {}
-            },
-            Event::Mouse(MouseEvent { kind, row, .. }) => match kind {
-                MouseEventKind::Down(_) => {
-                    if row >= list.area.y && row < list.area.y + list.area.height {
-                        let index = (row - list.area.y - 1) as usize;
-                        if let Some(selected) = list.state.selected() {
-                            if selected == index {
-                                interact();
+            }
+            // the mouse moving over the window changes nothing on screen.
+            if let Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Moved,
+                ..
+            }) = event
+            {
+                if !more_keys(&replay)? {
+                    break;
+                }
+                continue;
+            }
+            'handled: {
+                // Q and @ are typed into prompts and the finder like any
+                // other key, and answer confirmations, rather than start or
+                // play macros.
+                let listening = prompt.is_none()
+                    && finder.is_none()
+                    && deleting.is_none()
+                    && results.is_none()
+                    && comparison.is_none()
+                    && panel.is_none()
+                    && open.is_none()
+                    && inspected.is_none()
+                    && advice.is_none()
+                    && leftovers.is_none()
+                    && mailboxes.is_none()
+                    && repos.is_none();
+                if let Event::Key(key) = &event {
+                    match macros.handle(*key, listening) {
+                        macros::Outcome::Pass => {}
+                        macros::Outcome::Taken(said) => {
+                            message = said;
+                            break 'handled;
+                        }
+                        macros::Outcome::Replay(keys) => {
+                            replay.extend(keys);
+                            break 'handled;
+                        }
+                    }
+                }
+                if let (Some(state), Event::Key(key)) = (&mut panel, &event) {
+                    let mounts = skipped(&sources, &included, &excluded);
+                    let chosen = match key.code {
+                        KeyCode::Char('k') => {
+                            state.select_previous();
+                            vec![]
+                        }
+                        KeyCode::Char('j') => {
+                            state.select_next();
+                            vec![]
+                        }
+                        KeyCode::Enter => {
+                            let selected = state.selected().map(|i| i.min(mounts.len() - 1));
+                            selected.map(|i| mounts[i].clone()).into_iter().collect()
+                        }
+                        KeyCode::Char('a') => mounts.clone(),
+                        KeyCode::Char('s') => {
+                            let selected = state.selected().map(|i| i.min(mounts.len() - 1));
+                            show = selected.map(|i| mounts[i].clone());
+                            panel = None;
+                            vec![]
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('M') | KeyCode::Esc => {
+                            panel = None;
+                            vec![]
+                        }
+                        _ => vec![],
+                    };
+                    for mount in chosen {
+                        let started = LocalSource::new(&mount, options.clone())
+                            .map_err(|e| Error::Root(mount.clone(), e))
+                            .and_then(|s| {
+                                let s = Arc::new(s);
+                                Ok((s.clone(), scan::scan(s, &mount)?))
+                            });
+                        match started {
+                            Ok((s, scan)) => {
+                                sources.push(s);
+                                includes.push((scan, vec![]));
+                                included.insert(mount);
+                            }
+                            Err(e) => message = Some(e.to_string()),
+                        }
+                    }
+                    if skipped(&sources, &included, &excluded).is_empty() {
+                        panel = None;
+                    }
+                    break 'handled;
+                }
+                if let (Some((state, files)), Event::Key(key)) = (&mut open, &event) {
+                    match key.code {
+                        KeyCode::Char('k') => state.select_previous(),
+                        KeyCode::Char('j') => state.select_next(),
+                        KeyCode::Enter | KeyCode::Char('s') => {
+                            let i = state.selected().unwrap_or(0).min(files.len() - 1);
+                            let file = &files[i];
+                            if let Some(spill) = &mut spill {
+                                spill.page_in(&mut tree, &file.path)?;
+                            }
+                            if tree.find(&file.path).is_some() {
+                                show = Some(file.path.clone());
                             } else {
+                                // deleted, or created since the scan.
+                                message = Some(format!(
+                                    "{} isn't in the scan; its space is held until {} closes it",
+                                    file.path.display(),
+                                    file.holder.name
+                                ));
+                            }
+                            open = None;
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('P') | KeyCode::Esc => open = None,
+                        _ => {}
+                    }
+                    break 'handled;
+                }
+                if inspected.is_some() && matches!(event, Event::Key(_)) {
+                    inspected = None;
+                    break 'handled;
+                }
+                if let (Some((state, found)), Event::Key(key)) = (&mut advice, &event) {
+                    let selected = state.selected().unwrap_or(0).min(found.len() - 1);
+                    match key.code {
+                        KeyCode::Char('z' | 'a') if scan.is_some() => {
+                            message = Some("wait for the scan to finish first".to_string());
+                        }
+                        KeyCode::Char('z') if found[selected].advice == logs::Advice::Rotate => {
+                            // compressing it would pull it out from under whatever writes to it.
+                            message = Some("that log is still in use; rotate it first".to_string());
+                        }
+                        KeyCode::Char(c @ ('z' | 'a')) => {
+                            let picked: Vec<Info> = found
+                                .iter()
+                                .enumerate()
+                                .filter(|(i, log)| {
+                                    log.advice == logs::Advice::Compress
+                                        && (c == 'a' || *i == selected)
+                                })
+                                .map(|(_, log)| log.info.clone())
+                                .collect();
+                            if args.dry_run {
+                                let total: u64 = picked.iter().map(|log| log.size).sum();
+                                message = Some(format!(
+                                    "would compress {} logs totaling {}",
+                                    picked.len(),
+                                    ByteSize(total)
+                                ));
+                                break 'handled;
+                            }
+                            found.retain(|log| !picked.iter().any(|p| p.path == log.info.path));
+                            message = Some(format!("compressing {} logs…", picked.len()));
+                            let tx = squeezed_tx.clone();
+                            thread::spawn(move || {
+                                picked.into_par_iter().for_each_with(tx, |tx, log| {
+                                    let result = compress::gzip(&log.path);
+                                    let _ = tx.send((log, result));
+                                });
+                            });
+                            if found.is_empty() {
+                                advice = None;
+                            }
+                        }
+                        KeyCode::Char('k') => state.select_previous(),
+                        KeyCode::Char('j') => state.select_next(),
+                        KeyCode::Enter | KeyCode::Char('s') => {
+                            show = Some(found[selected].info.path.clone());
+                            advice = None;
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('O') | KeyCode::Esc => advice = None,
+                        _ => {}
+                    }
+                    break 'handled;
+                }
+                if let (Some((state, found, confirm)), Event::Key(key)) = (&mut leftovers, &event) {
+                    if let Some(c) = confirm {
+                        match c.answer(*key) {
+                            None => {}
+                            Some(false) => *confirm = None,
+                            Some(true) if args.dry_run => {
+                                found.retain(|a| !notes.undeletable.contains_key(&a.info.path));
+                                let total: u64 = found.iter().map(|a| a.info.size).sum();
+                                message = Some(format!(
+                                    "would delete {} files, freeing {}",
+                                    found.len(),
+                                    ByteSize(total)
+                                ));
+                                leftovers = None;
+                            }
+                            Some(true) => {
+                                let (mut freed, mut failed) = (0, 0);
+                                found.retain(|a| !notes.undeletable.contains_key(&a.info.path));
+                                let before = fsstat::free_space(&cwd).ok();
+                                for Artifact { info: i, .. } in found.iter() {
+                                    if let Err(e) = fs::remove_file(&i.path) {
+                                        failed += 1;
+                                        message = Some(format!(
+                                            "couldn't delete {}: {e}",
+                                            i.path.display()
+                                        ));
+                                        continue;
+                                    }
+                                    if let Err(e) = audit.record("delete", &i.path, Some(i.size)) {
+                                        message = Some(format!("couldn't write audit log: {e}"));
+                                    }
+                                    freed += tree.remove(&i.path).unwrap_or(0);
+                                    notes.marks.remove(&i.path);
+                                }
+                                if whatif {
+                                    pretend = self::pretend(&tree, &root, &notes.marks);
+                                }
+                                list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                                size = ByteSize(tree.size(&top));
+                                free = fsstat::free_space(&cwd).ok();
+                                if failed == 0 {
+                                    message = Some(format!(
+                                        "deleted {} files, freeing {}{}",
+                                        found.len(),
+                                        ByteSize(freed),
+                                        free_went(before, free)
+                                    ));
+                                }
+                                leftovers = None;
+                            }
+                        }
+                        break 'handled;
+                    }
+                    match key.code {
+                        KeyCode::Char('d') if scan.is_some() => {
+                            message = Some("wait for the scan to finish first".to_string());
+                        }
+                        KeyCode::Char('d')
+                            if found
+                                .iter()
+                                .all(|a| notes.undeletable.contains_key(&a.info.path)) =>
+                        {
+                            message = Some("none of these can be deleted".to_string());
+                        }
+                        KeyCode::Char('d') => {
+                            let doomed: Vec<&Info> = found
+                                .iter()
+                                .map(|a| &a.info)
+                                .filter(|i| !notes.undeletable.contains_key(&i.path))
+                                .collect();
+                            let total: u64 = doomed.iter().map(|i| i.size).sum();
+                            let what = match found.len() - doomed.len() {
+                                0 => format!("all {} files", doomed.len()),
+                                n => format!("all but the {n} that can't be"),
+                            };
+                            // what's held open isn't freed until it's closed.
+                            let paths: HashSet<&Path> = doomed.iter().map(|i| &*i.path).collect();
+                            let mut holders = vec![];
+                            for f in procfs::open_files(&cwd) {
+                                if paths.contains(&*f.path)
+                                    && !holders
+                                        .iter()
+                                        .any(|h: &procfs::Holder| h.pid == f.holder.pid)
+                                {
+                                    holders.push(f.holder);
+                                }
+                            }
+                            let open = match holders.is_empty() {
+                                true => String::new(),
+                                false => {
+                                    format!(" some are open by {};", procfs::describe(&holders))
+                                }
+                            };
+                            let big = total > args.confirm_over.as_u64()
+                                || doomed.len() > args.confirm_files;
+                            let question = format!("delete {what} ({})?{open}", ByteSize(total));
+                            *confirm = Some(Confirm::new(&question, 'd', None, big));
+                        }
+                        KeyCode::Char('k') => state.select_previous(),
+                        KeyCode::Char('j') => state.select_next(),
+                        KeyCode::Enter | KeyCode::Char('s') => {
+                            let chosen = state.selected().unwrap_or(0).min(found.len() - 1);
+                            show = Some(found[chosen].info.path.clone());
+                            leftovers = None;
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('T') | KeyCode::Esc => leftovers = None,
+                        _ => {}
+                    }
+                    break 'handled;
+                }
+                if let (Some((state, found)), Event::Key(key)) = (&mut mailboxes, &event) {
+                    match key.code {
+                        KeyCode::Char('k') => state.select_previous(),
+                        KeyCode::Char('j') => state.select_next(),
+                        KeyCode::Enter | KeyCode::Char('s') => {
+                            let chosen = state.selected().unwrap_or(0).min(found.len() - 1);
+                            show = Some(found[chosen].path.clone());
+                            mailboxes = None;
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('I') | KeyCode::Esc => mailboxes = None,
+                        _ => {}
+                    }
+                    break 'handled;
+                }
+                if let (Some((state, found)), Event::Key(key)) = (&mut repos, &event) {
+                    match key.code {
+                        KeyCode::Char('k') => state.select_previous(),
+                        KeyCode::Char('j') => state.select_next(),
+                        KeyCode::Enter => {
+                            let repo = &found[state.selected().unwrap_or(0).min(found.len() - 1)];
+                            match repo.advice() {
+                                Some(command) => {
+                                    message = Some(format!(
+                                        "running {command} in {}…",
+                                        repo.path.display()
+                                    ));
+                                    let (tx, path) = (upkeep_tx.clone(), repo.path.clone());
+                                    thread::spawn(move || {
+                                        let result = git::run(&path, command);
+                                        let _ = tx.send((path, command, result));
+                                    });
+                                    repos = None;
+                                }
+                                None => {
+                                    message =
+                                        Some(format!("{} needs no upkeep", repo.path.display()));
+                                }
+                            }
+                        }
+                        KeyCode::Char('s') => {
+                            let chosen = state.selected().unwrap_or(0).min(found.len() - 1);
+                            show = Some(found[chosen].path.clone());
+                            repos = None;
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('V') | KeyCode::Esc => repos = None,
+                        _ => {}
+                    }
+                    break 'handled;
+                }
+                if let (Some(r), Event::Key(key)) = (&mut results, &event) {
+                    match key.code {
+                        KeyCode::Char('k') | KeyCode::Up => r.state.select_previous(),
+                        KeyCode::Char('j') | KeyCode::Down => r.state.select_next(),
+                        KeyCode::Enter | KeyCode::Char('s') => {
+                            let chosen = r.state.selected().unwrap_or(0).min(r.found.len() - 1);
+                            show = Some(r.found[chosen].path.clone());
+                            results = None;
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('?') | KeyCode::Esc => results = None,
+                        _ => {}
+                    }
+                    break 'handled;
+                }
+                if let (Some(c), Event::Key(key)) = (&mut comparison, &event) {
+                    match key.code {
+                        KeyCode::Char('k') | KeyCode::Up => c.state.select_previous(),
+                        KeyCode::Char('j') | KeyCode::Down => c.state.select_next(),
+                        KeyCode::Enter | KeyCode::Char('s') => {
+                            // the right side, unless it's only on the left.
+                            let chosen =
+                                c.state.selected().unwrap_or(0).min(c.differences.len() - 1);
+                            let d = &c.differences[chosen];
+                            let side = &c.sides[d.right.is_some() as usize];
+                            show = Some(side.join(&d.path));
+                            comparison = None;
+                        }
+                        KeyCode::Char('q') | KeyCode::Char('c') | KeyCode::Esc => comparison = None,
+                        _ => {}
+                    }
+                    break 'handled;
+                }
+                if let (Some(f), Event::Key(key)) = (&mut finder, &event) {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Up => f.state.select_previous(),
+                        KeyCode::Char('p') if ctrl => f.state.select_previous(),
+                        KeyCode::Down => f.state.select_next(),
+                        KeyCode::Char('n') if ctrl => f.state.select_next(),
+                        KeyCode::Enter => {
+                            let best = f.matches.as_ref().map_or(&[][..], |m| &m.best[..]);
+                            let chosen = f
+                                .state
+                                .selected()
+                                .unwrap_or(0)
+                                .min(best.len().saturating_sub(1));
+                            if let Some(&i) = best.get(chosen) {
+                                show = Some(top.join(&f.paths[i]));
+                                finder = None;
+                            }
+                        }
+                        _ => {
+                            let typed = f.prompt.text();
+                            match f.prompt.handle(*key) {
+                                Outcome::Cancel => finder = None,
+                                _ if f.prompt.text() != typed => f.update(),
+                                _ => {}
+                            }
+                        }
+                    }
+                    break 'handled;
+                }
+                if let (Some((i, confirm)), Event::Key(key)) = (&mut deleting, &event) {
+                    let Some(yes) = confirm.answer(*key) else {
+                        break 'handled;
+                    };
+                    let i = i.clone();
+                    deleting = None;
+                    let name = i.path.file_name().unwrap();
+                    if !yes {
+                        message = Some("nothing was deleted".to_string());
+                    } else if args.dry_run {
+                        message = Some(format!(
+                            "would delete {name:?}, freeing {}",
+                            ByteSize(i.size)
+                        ));
+                    } else {
+                        if let Some(spill) = &mut spill {
+                            spill.page_in(&mut tree, &i.path)?;
+                        }
+                        let before = fsstat::free_space(&cwd).ok();
+                        let deleted = removable::delete(&i.path);
+                        // on failure some of it may be gone, which the tree should
+                        // show. children sort after their parents.
+                        let gone: Vec<PathBuf> = match deleted {
+                            Ok(()) => vec![i.path.clone()],
+                            Err(_) => {
+                                let entries = tree.subtree(&i.path).iter().rev();
+                                let paths = entries.map(|i| i.path.clone());
+                                paths.filter(|p| fs::symlink_metadata(p).is_err()).collect()
+                            }
+                        };
+                        let freed: u64 = gone.iter().filter_map(|p| tree.remove(p)).sum();
+                        free = fsstat::free_space(&cwd).ok();
+                        let went = free_went(before, free);
+                        message = Some(match deleted {
+                            Ok(()) => {
+                                format!("deleted {name:?}, freeing {}{went}", ByteSize(freed))
+                            }
+                            Err(e) if gone.is_empty() => format!("couldn't delete {name:?}: {e}"),
+                            Err(e) => format!("couldn't delete all of {name:?}: {e}{went}"),
+                        });
+                        if !gone.is_empty() {
+                            if let Err(e) = audit.record("delete", &i.path, Some(freed)) {
+                                message = Some(format!("couldn't write audit log: {e}"));
+                            }
+                        }
+                        notes
+                            .marks
+                            .retain(|m| !gone.iter().any(|g| m.starts_with(g)));
+                        if whatif {
+                            pretend = self::pretend(&tree, &root, &notes.marks);
+                        }
+                        list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                        size = ByteSize(tree.size(&top));
+                    }
+                    break 'handled;
+                }
+                if let (Some((input, p)), Event::Key(key)) = (&mut prompt, &event) {
+                    match p.handle(*key) {
+                        Outcome::Pending => {}
+                        Outcome::Cancel => prompt = None,
+                        Outcome::Submit(text) => {
+                            match input {
+                                Input::Filter => {
+                                    list.filter = Some(text).filter(|s| !s.is_empty());
+                                    list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                                }
+                                Input::Script => {
+                                    if let Some(spill) = &mut spill {
+                                        for p in &notes.marks {
+                                            spill.page_in(&mut tree, p)?;
+                                        }
+                                    }
+                                    let script = if text.is_empty() { "cleanup.sh" } else { &text };
+                                    let entries: Vec<Info> = notes
+                                        .marks
+                                        .iter()
+                                        .filter_map(|p| tree.find(p))
+                                        .cloned()
+                                        .collect();
+                                    message = Some(
+                                        match cleanup::write_script(Path::new(script), &entries) {
+                                            Ok(total) => format!(
+                                                "wrote {script}, which would free {}",
+                                                ByteSize(total)
+                                            ),
+                                            Err(e) => format!("couldn't write {script}: {e}"),
+                                        },
+                                    );
+                                }
+                                Input::SaveMarks => {
+                                    let file = if text.is_empty() { "marks.txt" } else { &text };
+                                    message = Some(
+                                        match cleanup::save_marks(Path::new(file), &notes.marks) {
+                                            Ok(()) => {
+                                                format!(
+                                                    "saved {} marks to {file}",
+                                                    notes.marks.len()
+                                                )
+                                            }
+                                            Err(e) => format!("couldn't write {file}: {e}"),
+                                        },
+                                    );
+                                }
+                                Input::LoadMarks => {
+                                    let file = if text.is_empty() { "marks.txt" } else { &text };
+                                    message = Some(match cleanup::load_marks(Path::new(file)) {
+                                        Ok(marks) => {
+                                            let n = marks.len();
+                                            check_deletable(
+                                                &mut notes.undeletable,
+                                                marks.iter(),
+                                                &names,
+                                            );
+                                            notes.marks.extend(marks);
+                                            format!("marked {n} paths from {file}")
+                                        }
+                                        Err(e) => format!("couldn't read {file}: {e}"),
+                                    });
+                                    if whatif {
+                                        pretend = self::pretend(&tree, &root, &notes.marks);
+                                        list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                                    }
+                                }
+                                Input::Search if text.is_empty() => {}
+                                Input::Search => {
+                                    let entries =
+                                        tree.subtree(&top).iter().skip(1).cloned().collect();
+                                    let (tx, rx) = mpsc::channel();
+                                    message =
+                                        Some(match Results::search(text.clone(), entries, tx) {
+                                            Ok(()) => {
+                                                searching = Some(rx);
+                                                format!("searching for {text}…")
+                                            }
+                                            Err(e) => format!("bad regex {text:?}: {e}"),
+                                        });
+                                }
+                                Input::Jump => {
+                                    let path = resolve(&cwd, &text);
+                                    if let (Some(spill), Some(path)) = (&mut spill, &path) {
+                                        spill.page_in(&mut tree, path)?;
+                                    }
+                                    match path {
+                                        Some(path)
+                                            if path.starts_with(&top)
+                                                && tree.find(&path).is_some_and(|i| i.is_dir) =>
+                                        {
+                                            cwd = path;
+                                            list.enter(
+                                                &cwd,
+                                                listing(&tree, &cwd, collapse, &pretend),
+                                            );
+                                        }
+                                        _ => {
+                                            message =
+                                                Some(format!("no directory {text:?} in the scan"))
+                                        }
+                                    }
+                                }
+                            }
+                            prompt = None;
+                        }
+                    }
+                    break 'handled;
+                }
+                match event {
+                    Event::Key(key) => match key.code {
+                        KeyCode::Char('k') => list.state.select_previous(),
+                        KeyCode::Char('j') => list.state.select_next(),
+                        KeyCode::Char('G') => list.state.select_last(),
+                        KeyCode::Char('g') => list.state.select_first(),
+                        KeyCode::Char('-') if cwd != top => {
+                            // go up, selecting the directory we just left.
+                            let child = cwd.clone();
+                            cwd = above(&tree, &top, &cwd, collapse);
+                            list.enter(&cwd, listing(&tree, &cwd, collapse, &pretend));
+                            list.select_path(&child);
+                        }
+                        KeyCode::Char(c @ ('J' | 'K')) if cwd != top => {
+                            // step over to the next or previous directory beside
+                            // this one, in the order the parent lists them.
+                            let parent = above(&tree, &top, &cwd, collapse);
+                            let Listing {
+                                items: mut siblings,
+                                counts,
+                            } = listing(&tree, &parent, collapse, &pretend);
+                            siblings.retain(|i| i.is_dir);
+                            list.sort.apply(&mut siblings, &list.duplicated, &counts);
+                            let at = siblings.iter().position(|i| i.path == cwd).unwrap_or(0);
+                            let next = match c {
+                                'J' => siblings.get(at + 1),
+                                _ => at.checked_sub(1).and_then(|i| siblings.get(i)),
+                            };
+                            match next {
+                                Some(next) => {
+                                    cwd = next.path.clone();
+                                    list.enter(&cwd, listing(&tree, &cwd, collapse, &pretend));
+                                }
+                                None => {
+                                    let which = if c == 'J' { "last" } else { "first" };
+                                    message = Some(format!("this is the {which} directory here"));
+                                }
+                            }
+                        }
+                        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            finder = Some(Finder::new(&tree, &top));
+                        }
+                        KeyCode::Char('F') => {
+                            // look at the selected directory as if it were all
+                            // there is, until Backspace.
+                            if let Some(i) = list.selected().map(|s| &list.items[s]) {
+                                if i.is_dir {
+                                    focused.push(mem::replace(&mut top, i.path.clone()));
+                                    cwd = top.clone();
+                                    list.enter(&cwd, listing(&tree, &cwd, collapse, &pretend));
+                                    size = ByteSize(tree.size(&top));
+                                }
+                            }
+                        }
+                        KeyCode::Backspace if !focused.is_empty() => {
+                            top = focused.pop().unwrap();
+                            size = ByteSize(tree.size(&top));
+                        }
+                        KeyCode::Char('b') => {
+                            list.graph = list.graph.next();
+                            let shown = match list.graph {
+                                Graph::None => "no longer showing shares of the directory",
+                                Graph::Bar => "showing each entry's share as a bar",
+                                Graph::Percent => "showing each entry's share as a percentage",
+                                Graph::Both => {
+                                    "showing each entry's share as a percentage and a bar"
+                                }
+                            };
+                            message = Some(shown.to_string());
+                        }
+                        KeyCode::Char('C') => {
+                            collapse = !collapse;
+                            list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                            message = Some(match collapse {
+                                true => "showing chains of lone directories as one row".to_string(),
+                                false => "showing every directory".to_string(),
+                            });
+                        }
+                        KeyCode::Char('c') => {
+                            // the first c picks the left side, the second compares.
+                            if let Some(i) = list.selected().map(|s| &list.items[s]) {
+                                let name = i.path.file_name().unwrap_or(i.path.as_os_str());
+                                match comparing.take() {
+                                    _ if !i.is_dir => {
+                                        message = Some(format!("{name:?} isn't a directory"));
+                                    }
+                                    None => {
+                                        message = Some(format!(
+                                            "c on another directory to compare it with {name:?}"
+                                        ));
+                                        comparing = Some(i.path.clone());
+                                    }
+                                    Some(left) if left == i.path => {
+                                        message = Some("not comparing".to_string());
+                                    }
+                                    Some(left)
+                                        if left.starts_with(&i.path)
+                                            || i.path.starts_with(&left) =>
+                                    {
+                                        message = Some(format!(
+                                            "can't compare {} with {}, one holds the other",
+                                            left.display(),
+                                            i.path.display()
+                                        ));
+                                    }
+                                    Some(left) => {
+                                        let right = i.path.clone();
+                                        if let Some(spill) = &mut spill {
+                                            spill.page_in(&mut tree, &left)?;
+                                            spill.page_in(&mut tree, &right)?;
+                                        }
+                                        let c = Comparison::new(&tree, left, right);
+                                        if c.differences.is_empty() {
+                                            message = Some(format!(
+                                                "{} and {} hold the same",
+                                                c.sides[0].display(),
+                                                c.sides[1].display()
+                                            ));
+                                        } else {
+                                            comparison = Some(c);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        KeyCode::Char('/') => prompt = Some((Input::Filter, Prompt::new("filter"))),
+                        KeyCode::Char('?') => {
+                            prompt = Some((Input::Search, Prompt::new("search by regex")))
+                        }
+                        KeyCode::Char('w') => {
+                            prompt = Some((Input::SaveMarks, Prompt::new("marks")));
+                        }
+                        KeyCode::Char('L') => {
+                            prompt = Some((Input::LoadMarks, Prompt::new("marks")));
+                        }
+                        KeyCode::Char(' ') => {
+                            if let Some(selected) = list.selected() {
+                                let path = &list.items[selected].path;
+                                if !notes.marks.remove(path) {
+                                    notes.marks.insert(path.clone());
+                                    // say straight away if it couldn't be deleted.
+                                    check_deletable(
+                                        &mut notes.undeletable,
+                                        [path].into_iter(),
+                                        &names,
+                                    );
+                                }
+                                list.state.select_next();
+                                if whatif {
+                                    pretend = self::pretend(&tree, &root, &notes.marks);
+                                    list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                                }
+                            }
+                        }
+                        KeyCode::Char('a') => {
+                            // everything, spilled, hidden or still coming in,
+                            // trades its size for the other kind.
+                            apparent = !apparent;
+                            tree.swap_sizes();
+                            swap_sizes(&mut pending);
+                            for (_, data) in &mut includes {
+                                swap_sizes(data);
+                            }
+                            for sub in &mut hidden {
+                                sub.swap_sizes();
+                            }
+                            if let Some(spill) = &mut spill {
+                                spill.swap_sizes();
+                            }
+                            // re-stats were of the other kind.
+                            notes.changed.clear();
+                            if whatif {
+                                pretend = self::pretend(&tree, &root, &notes.marks);
+                            }
+                            list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                            size = ByteSize(tree.size(&top));
+                            message = Some(match apparent {
+                                true => "showing apparent sizes, a shows disk usage".to_string(),
+                                false => "showing disk usage".to_string(),
+                            });
+                        }
+                        KeyCode::Char('W') => {
+                            whatif = !whatif;
+                            pretend = match whatif {
+                                true => self::pretend(&tree, &root, &notes.marks),
+                                false => HashMap::new(),
+                            };
+                            list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                            message = Some(match whatif {
+                                true => {
+                                    "what if: marked entries count as deleted, W stops".to_string()
+                                }
+                                false => "showing actual sizes".to_string(),
+                            });
+                        }
+                        // these go to the files themselves, which for an import
+                        // aren't here, whatever's at the same paths.
+                        KeyCode::Char(
+                            c @ ('d' | 't' | 'E' | 'R' | 'A' | 'P' | 'i' | 'O' | 'T' | 'I' | 'V'
+                            | 'D' | 'Z' | 'B' | 'o' | '.'),
+                        ) if imported_from.is_some() => {
+                            message =
+                                Some(format!("{c} needs the files, and this scan was imported"));
+                        }
+                        KeyCode::Char('d') if scan.is_some() => {
+                            message = Some("wait for the scan to finish first".to_string());
+                        }
+                        KeyCode::Char('d') => {
+                            if let Some(i) = list.selected().map(|s| list.items[s].clone()) {
+                                if let Some(spill) = &mut spill {
+                                    spill.page_in(&mut tree, &i.path)?;
+                                }
+                                let name = i.path.file_name().unwrap();
+                                message = match removable::obstacle(&i.path, &names) {
+                                    Some(why) => Some(format!("can't delete {name:?}: {why}")),
+                                    None => {
+                                        let what = if i.is_dir {
+                                            " and everything in it"
+                                        } else {
+                                            ""
+                                        };
+                                        let files = tree
+                                            .subtree(&i.path)
+                                            .iter()
+                                            .filter(|x| !x.is_dir)
+                                            .count();
+                                        let size = match i.is_dir {
+                                            true => format!(
+                                                "{} in {} files",
+                                                ByteSize(i.size),
+                                                commaify(files)
+                                            ),
+                                            false => ByteSize(i.size).to_string(),
+                                        };
+                                        // what's held open isn't freed until it's closed.
+                                        let open = match procfs::open_handles(&i.path) {
+                                            h if h.is_empty() => String::new(),
+                                            h => format!(" open by {};", procfs::describe(&h)),
+                                        };
+                                        let big = i.size > args.confirm_over.as_u64()
+                                            || files > args.confirm_files;
+                                        let confirm = Confirm::new(
+                                            &format!("delete {name:?}{what} ({size})?{open}"),
+                                            'y',
+                                            Some(name.to_string_lossy().into_owned()),
+                                            big,
+                                        );
+                                        // a safeword's prompt asks it itself.
+                                        let question = match confirm.prompt() {
+                                            Some(_) => None,
+                                            None => Some(confirm.question.clone()),
+                                        };
+                                        deleting = Some((i.clone(), confirm));
+                                        question
+                                    }
+                                };
+                            }
+                        }
+                        KeyCode::Char('t') if scan.is_some() => {
+                            message = Some("wait for the scan to finish first".to_string());
+                        }
+                        KeyCode::Char('t') => {
+                            // no confirmation: unlike d, it can be undone.
+                            if let Some(i) = list.selected().map(|s| list.items[s].clone()) {
+                                let name = i.path.file_name().unwrap();
+                                if let Some(why) = removable::obstacle(&i.path, &names) {
+                                    message =
+                                        Some(format!("can't move {name:?} to the trash: {why}"));
+                                } else if args.dry_run {
+                                    message = Some(format!(
+                                        "would move {name:?} ({}) to the trash",
+                                        ByteSize(i.size)
+                                    ));
+                                } else {
+                                    if let Some(spill) = &mut spill {
+                                        spill.page_in(&mut tree, &i.path)?;
+                                    }
+                                    match trash::trash(&i.path) {
+                                        Ok(to) => {
+                                            // it's still taking up space if the
+                                            // trash can is in the tree.
+                                            let moved = tree.rename(&i.path, &to).unwrap_or(i.size);
+                                            message = Some(format!(
+                                                "moved {name:?} ({}) to the trash",
+                                                ByteSize(moved)
+                                            ));
+                                            if let Err(e) =
+                                                audit.record("trash", &i.path, Some(moved))
+                                            {
+                                                message =
+                                                    Some(format!("couldn't write audit log: {e}"));
+                                            }
+                                            notes.marks.retain(|m| !m.starts_with(&i.path));
+                                            if whatif {
+                                                pretend = self::pretend(&tree, &root, &notes.marks);
+                                            }
+                                            list.set_items(listing(
+                                                &tree, &cwd, collapse, &pretend,
+                                            ));
+                                            size = ByteSize(tree.size(&top));
+                                            free = fsstat::free_space(&cwd).ok();
+                                        }
+                                        Err(e) => {
+                                            message = Some(format!(
+                                                "can't move {name:?} to the trash: {e}"
+                                            ));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        KeyCode::Char('x') if scan.is_some() => {
+                            message =
+                                Some("entries can be hidden once the scan is done".to_string());
+                        }
+                        KeyCode::Char('x') => {
+                            // hide the selection for this session, as if it were
+                            // gone, to see what's left without it.
+                            if let Some(i) = list.selected().map(|s| list.items[s].clone()) {
+                                hidden.extend(tree.cut(&i.path));
+                                if whatif {
+                                    pretend = self::pretend(&tree, &root, &notes.marks);
+                                }
+                                list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                                size = ByteSize(tree.size(&top));
+                                message = Some(format!(
+                                    "hid {:?} ({}), X shows everything again",
+                                    i.path.file_name().unwrap(),
+                                    ByteSize(i.size)
+                                ));
+                            }
+                        }
+                        KeyCode::Char('X') if !hidden.is_empty() => {
+                            // newest first, so a hidden directory is back before
+                            // anything hidden inside it earlier.
+                            for sub in hidden.drain(..).rev() {
+                                tree.graft(sub);
+                            }
+                            if whatif {
+                                pretend = self::pretend(&tree, &root, &notes.marks);
+                            }
+                            list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                            size = ByteSize(tree.size(&top));
+                        }
+                        KeyCode::Char('E') if notes.marks.is_empty() => {
+                            message = Some("mark entries with space first".to_string());
+                        }
+                        KeyCode::Char('E') => {
+                            prompt = Some((Input::Script, Prompt::new("script")));
+                        }
+                        KeyCode::Char('R') => {
+                            // re-stat the listing in parallel and flag what changed.
+                            let sizes: Vec<Option<u64>> = list
+                                .items
+                                .par_iter()
+                                .map(|i| {
+                                    source_for(&sources, &i.path).size_on_disk(&i.path, apparent)
+                                })
+                                .collect();
+                            let mut delta: i64 = 0;
+                            let mut count = 0;
+                            for (i, now) in list.items.iter().zip(sizes) {
+                                notes.changed.remove(&i.path);
+                                if now != Some(i.size) {
+                                    delta += now.unwrap_or(0) as i64 - i.size as i64;
+                                    count += 1;
+                                    notes.changed.insert(i.path.clone(), now);
+                                }
+                            }
+                            message = Some(match count {
+                                0 => "everything here matches the scan".to_string(),
+                                _ => format!(
+                                    "{count} entries changed since the scan ({}{})",
+                                    if delta < 0 { "-" } else { "+" },
+                                    ByteSize(delta.unsigned_abs())
+                                ),
+                            });
+                        }
+                        KeyCode::Char('M') => {
+                            if skipped(&sources, &included, &excluded).is_empty() {
+                                message = Some("no mounts were skipped".to_string());
+                            } else {
+                                panel = Some(ListState::default().with_selected(Some(0)));
+                            }
+                        }
+                        KeyCode::Tab | KeyCode::BackTab if all_mounts => {
+                            // move to the next or previous filesystem's tab.
+                            let at = paths.iter().rposition(|m| cwd.starts_with(m));
+                            let n = paths.len();
+                            let i = match (at, key.code) {
+                                (Some(i), KeyCode::Tab) => (i + 1) % n,
+                                (Some(i), _) => (i + n - 1) % n,
+                                (None, _) => 0,
+                            };
+                            cwd = paths[i].clone();
+                            list.enter(&cwd, listing(&tree, &cwd, collapse, &pretend));
+                        }
+                        KeyCode::F(12) => debug = !debug,
+                        KeyCode::Char('A') if watcher.is_some() => {
+                            watcher = None;
+                            notes.active.clear();
+                            message = Some("stopped watching for writes".to_string());
+                        }
+                        KeyCode::Char('A') => {
+                            // the biggest files are the ones worth knowing about.
+                            let mut files: Vec<&Info> =
+                                tree.data.iter().filter(|x| !x.is_dir).collect();
+                            files.sort_by_key(|x| std::cmp::Reverse(x.size));
+                            let files = files.iter().take(1000).map(|x| x.path.clone()).collect();
+                            watcher = Some(activity::watch(files, root.clone()));
+                            message =
+                                Some("watching the largest files for writes; A stops".to_string());
+                        }
+                        KeyCode::Char('P') => {
+                            let files = procfs::open_files(&cwd);
+                            if files.is_empty() {
+                                message = Some("no files here are open".to_string());
+                            } else {
+                                open = Some((ListState::default().with_selected(Some(0)), files));
+                            }
+                        }
+                        KeyCode::Char('i') => {
+                            if let Some(i) = list.selected().map(|s| &list.items[s]) {
+                                inspected = Some((i.path.clone(), inspect(i)));
+                            }
+                        }
+                        KeyCode::Char('O') => {
+                            // logs that grew since the scan are measured again,
+                            // so rotation can be suggested before they get big.
+                            let elapsed = began.elapsed().as_secs_f64();
+                            let mut found: Vec<Log> = logs::candidates(&tree, &cwd)
+                                .into_par_iter()
+                                .filter_map(|info| {
+                                    let now = source_for(&sources, &info.path)
+                                        .size_on_disk(&info.path, apparent)?;
+                                    let growth = (now as f64 - info.size as f64) / elapsed;
+                                    let advice = logs::advise(&info, growth)?;
+                                    Some(Log {
+                                        info,
+                                        advice,
+                                        growth,
+                                    })
+                                })
+                                .collect();
+                            found.sort_by_key(|log| std::cmp::Reverse(log.info.size));
+                            if found.is_empty() {
+                                message =
+                                    Some("no logs here need rotating or compressing".to_string());
+                            } else {
+                                advice = Some((ListState::default().with_selected(Some(0)), found));
+                            }
+                        }
+                        KeyCode::Char('T') => {
+                            let found = artifacts::find(&tree, &cwd);
+                            let paths = found.iter().map(|a| &a.info.path);
+                            check_deletable(&mut notes.undeletable, paths, &names);
+                            if found.is_empty() {
+                                message =
+                                    Some("no core dumps or other crash leftovers here".to_string());
+                            } else {
+                                leftovers = Some((
+                                    ListState::default().with_selected(Some(0)),
+                                    found,
+                                    None,
+                                ));
+                            }
+                        }
+                        KeyCode::Char('I') => {
+                            let found = mail::find(&tree, &cwd);
+                            if found.is_empty() {
+                                message = Some("no maildirs or mboxes here".to_string());
+                            } else {
+                                let mboxes: Vec<_> = found
+                                    .iter()
+                                    .filter(|m| m.messages.is_none())
+                                    .map(|m| m.path.clone())
+                                    .collect();
+                                let tx = counts_tx.clone();
+                                thread::spawn(move || {
+                                    mboxes.into_par_iter().for_each_with(tx, |tx, path| {
+                                        if let Ok(messages) = mail::count(&path) {
+                                            let _ = tx.send((path, messages));
+                                        }
+                                    });
+                                });
+                                mailboxes =
+                                    Some((ListState::default().with_selected(Some(0)), found));
+                            }
+                        }
+                        KeyCode::Char('V') => {
+                            let found = git::find(&tree, &cwd);
+                            if found.is_empty() {
+                                message = Some("no git repositories here".to_string());
+                            } else {
+                                repos = Some((ListState::default().with_selected(Some(0)), found));
+                            }
+                        }
+                        KeyCode::Char('S') => {
+                            stats = match stats {
+                                Some(_) => None,
+                                None => Some((PathBuf::new(), 0, Stats::default())),
+                            };
+                        }
+                        KeyCode::Char(c @ ('h' | 'y')) if stats.is_some() => {
+                            let pick = if c == 'h' { Chart::Sizes } else { Chart::Years };
+                            chart = if chart == pick { Chart::Shape } else { pick };
+                        }
+                        KeyCode::Char('p') => match &scan {
+                            Some(s) if s.control.is_paused() => s.control.resume(),
+                            Some(s) => s.control.pause(),
+                            None => message = Some("the scan has already finished".to_string()),
+                        },
+                        KeyCode::Char('D') if scan.is_some() => {
+                            message = Some("wait for the scan to finish first".to_string());
+                        }
+                        KeyCode::Char('D') if dupes.is_some() => {}
+                        KeyCode::Char('D') => {
+                            let files: Vec<_> = tree
+                                .data
+                                .iter()
+                                .filter(|x| !x.is_dir)
+                                .map(|x| (x.path.clone(), x.size))
+                                .collect();
+                            let (tx, rx) = mpsc::channel();
+                            thread::spawn(move || tx.send(dupes::find(files)));
+                            dupes = Some(rx);
+                        }
+                        KeyCode::Char('U') if list.duplicated.is_empty() => {
+                            message = Some("find duplicates with D first".to_string());
+                        }
+                        KeyCode::Char('U') => list.set_sort(SortKey::Unique),
+                        KeyCode::Char('r') => {
+                            list.set_sort(list.sort.key.next());
+                            message = Some(format!("sorted by {}", list.sort.key.name()));
+                        }
+                        KeyCode::Char('Z') => {
+                            // sample the biggest files under each entry in the
+                            // background and annotate the rows as results come in.
+                            let jobs: Vec<_> = list
+                                .items
+                                .iter()
+                                .map(|i| {
+                                    (i.path.clone(), compress::candidates(tree.subtree(&i.path)))
+                                })
+                                .filter(|(_, files)| !files.is_empty())
+                                .collect();
+                            message = Some(match jobs.len() {
+                                0 => "nothing here is big enough to sample".to_string(),
+                                n => format!("estimating compressibility of {n} entries…"),
+                            });
+                            let tx = estimates_tx.clone();
+                            thread::spawn(move || {
+                                jobs.into_par_iter().for_each_with(tx, |tx, (path, files)| {
+                                    let _ = tx.send((path, compress::estimate(&files)));
+                                });
+                            });
+                        }
+                        KeyCode::Char('B') => {
+                            // ask restic and borg what the repositories under
+                            // here hold once deduplicated, in the background.
+                            let repos = backup::find(&tree, &cwd);
+                            message = Some(match repos.len() {
+                                0 => "no restic or borg repositories here".to_string(),
+                                n => format!("asking about {n} backup repositories…"),
+                            });
+                            for (path, kind) in repos {
+                                let tx = backups_tx.clone();
+                                thread::spawn(move || {
+                                    let usage = backup::usage(&path, kind);
+                                    let _ = tx.send((path, kind, usage));
+                                });
+                            }
+                        }
+                        KeyCode::Char(':') => prompt = Some((Input::Jump, Prompt::new("jump"))),
+                        KeyCode::Char('q') | KeyCode::Esc => break 'main,
+                        KeyCode::Char('o') => {
+                            // which processes have files open under the selection?
+                            if let Some(i) = list.selected().map(|s| &list.items[s]) {
+                                let path = &i.path;
+                                let holders = procfs::open_handles(path);
+                                message = Some(if holders.is_empty() {
+                                    format!("nothing under {:?} is open", path.file_name().unwrap())
+                                } else {
+                                    format!("open by {}", procfs::describe(&holders))
+                                });
+                            }
+                        }
+                        KeyCode::Char('m') => {
+                            // give the mouse back to the terminal for selecting text.
+                            mouse = !mouse;
+                            if mouse {
+                                crossterm::execute!(
+                                    terminal.backend_mut(),
+                                    crossterm::event::EnableMouseCapture
+                                )
+                                .map_err(Error::Terminal)?;
+                            } else {
+                                crossterm::execute!(
+                                    terminal.backend_mut(),
+                                    crossterm::event::DisableMouseCapture
+                                )
+                                .map_err(Error::Terminal)?;
+                            }
+                            message = Some(format!(
+                                "mouse capture {}",
+                                if mouse { "on" } else { "off" }
+                            ));
+                        }
+                        KeyCode::Char('.') => {
+                            if let Err(e) = audit.record("shell", &cwd, None) {
+                                message = Some(format!("couldn't write audit log: {e}"));
+                            }
+                            if let Err(e) = spawn_detached(
+                                Command::new("alacritty")
+                                    .arg("-e")
+                                    .arg("bash")
+                                    .current_dir(&cwd),
+                            ) {
+                                message = Some(format!("couldn't open a terminal: {e}"));
+                            }
+                        }
+                        KeyCode::Enter => activate = true,
+                        _ => {}
+                    },
+                    Event::Mouse(MouseEvent {
+                        kind, column, row, ..
+                    }) => match kind {
+                        MouseEventKind::Down(_) => {
+                            if let Some(key) = list.column_at(column, row) {
+                                list.set_sort(key);
+                            }
+                            if let Some(index) = list.index_at(row) {
+                                // a single click only selects; two in quick succession
+                                // on the same row activate it.
+                                let now = Instant::now();
+                                activate = last_click
+                                    .is_some_and(|(at, i)| i == index && now - at <= double_click);
+                                last_click = (!activate).then_some((now, index));
                                 list.state.select(Some(index));
                             }
+                        }
+                        MouseEventKind::ScrollDown => {
+                            list.state.select_next();
+                        }
+                        MouseEventKind::ScrollUp => {
+                            list.state.select_previous();
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+
+                if activate {
+                    if let Some(selected) = list.selected() {
+                        let i = &list.items[selected];
+                        if i.is_dir {
+                            cwd = i.path.clone();
+                            list.enter(&cwd, listing(&tree, &cwd, collapse, &pretend));
+                        } else if imported_from.is_some() {
+                            message =
+                                Some("the files of an imported scan can't be opened".to_string());
                         } else {
-                            list.state.select(Some(index));
+                            if let Err(e) = spawn_detached(Command::new("xdg-open").arg(&i.path)) {
+                                message = Some(format!("couldn't open {:?}: {e}", i.path));
+                            }
                         }
                     }
                 }
-                MouseEventKind::ScrollDown => {
-                    list.state.select_next();
-                }
-                MouseEventKind::ScrollUp => {
-                    list.state.select_previous();
+                // someone else may be cleaning up at the same time.
+                if scan.is_none() {
+                    // an import's paths are somewhere else, so they'd all seem gone.
+                    let vanished = match imported_from {
+                        Some(_) => None,
+                        None => leave_vanished(&mut tree, &root, &mut cwd),
+                    };
+                    if let Some(warning) = vanished {
+                        if !cwd.starts_with(&top) {
+                            top = root.clone();
+                            focused.clear();
+                        }
+                        list.enter(&cwd, listing(&tree, &cwd, collapse, &pretend));
+                        size = ByteSize(tree.size(&top));
+                        message = Some(warning);
+                    }
+                    if let Some(spill) = &mut spill {
+                        if spill.page_in(&mut tree, &cwd)? {
+                            list.set_items(listing(&tree, &cwd, collapse, &pretend));
+                        }
+                        spill.shrink(&mut tree, &cwd)?;
+                    }
                 }
-                _ => {}
-            },
-            _ => continue,
+            }
+            dirty = true;
+            if !more_keys(&replay)? {
+                break;
+            }
         }
     }
 
-    disable_raw_mode().unwrap();
-    crossterm::execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        crossterm::event::DisableMouseCapture
-    )
-    .unwrap();
+    if args.inline.is_some() {
+        // hand the lines back to the shell, like fzf does.
+        terminal.clear().map_err(Error::Terminal)?;
+    }
+    Ok(())
 }
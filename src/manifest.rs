@@ -0,0 +1,348 @@
+//! Checksum manifests in the format of `sha256sum`, so archived copies can
+//! be checked with `sha256sum -c` without adansonia.
+
+use crate::tree::Info;
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Hashes every file in `entries` in parallel and writes a manifest line for
+/// each, with paths relative to `base`. Returns the files that couldn't be
+/// read, which are left out.
+pub fn write(
+    out: &mut impl Write,
+    base: &Path,
+    entries: &[Info],
+) -> io::Result<Vec<(PathBuf, io::Error)>> {
+    let hashed: Vec<_> = entries
+        .par_iter()
+        .filter(|i| !i.is_dir)
+        .map(|i| (&i.path, hash(&i.path)))
+        .collect();
+    let mut failed = vec![];
+    for (path, digest) in hashed {
+        match digest {
+            Ok(digest) => {
+                out.write_all(line(&digest, path.strip_prefix(base).unwrap_or(path)).as_bytes())?
+            }
+            Err(e) => failed.push((path.clone(), e)),
+        }
+    }
+    Ok(failed)
+}
+
+/// What's wrong with a file, according to a manifest.
+#[derive(Debug)]
+pub enum Problem {
+    /// Its contents hash differently.
+    Changed,
+    /// It's in the manifest but wasn't found.
+    Missing,
+    /// It was found but not listed in the manifest.
+    New,
+    Unreadable(io::Error),
+}
+
+/// Checks `entries`, scanned from `base`, against a manifest as written by
+/// `write` or sha256sum, rehashing in parallel. Returns every file that
+/// doesn't match, in manifest order followed by the new ones.
+pub fn verify(
+    manifest: &str,
+    base: &Path,
+    entries: &[Info],
+) -> io::Result<Vec<(PathBuf, Problem)>> {
+    let listed = parse(manifest)?;
+    let found: HashSet<&Path> = entries
+        .iter()
+        .filter(|i| !i.is_dir)
+        .map(|i| i.path.as_path())
+        .collect();
+    let mut problems: Vec<_> = listed
+        .par_iter()
+        .filter_map(|(name, digest)| {
+            let path = base.join(name);
+            if !found.contains(path.as_path()) {
+                return Some((path, Problem::Missing));
+            }
+            match hash(&path) {
+                Ok(now) if now == *digest => None,
+                Ok(_) => Some((path, Problem::Changed)),
+                Err(e) => Some((path, Problem::Unreadable(e))),
+            }
+        })
+        .collect();
+    let listed: HashSet<PathBuf> = listed
+        .into_iter()
+        .map(|(name, _)| base.join(name))
+        .collect();
+    problems.extend(
+        entries
+            .iter()
+            .filter(|i| !i.is_dir && !listed.contains(&i.path))
+            .map(|i| (i.path.clone(), Problem::New)),
+    );
+    Ok(problems)
+}
+
+/// The names and digests in a manifest.
+fn parse(manifest: &str) -> io::Result<Vec<(PathBuf, [u8; 32])>> {
+    let invalid = |n: usize| {
+        let e = format!("line {} isn't a sha256sum manifest line", n + 1);
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    };
+    let mut listed = vec![];
+    for (n, line) in manifest.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let (escaped, line) = match line.strip_prefix('\\') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        // sha256sum separates the name with a '*' instead in binary mode.
+        let (hex, name) = match line.split_at_checked(64) {
+            Some((hex, rest)) => (hex, rest.strip_prefix("  ").or(rest.strip_prefix(" *"))),
+            None => return Err(invalid(n)),
+        };
+        let name = name
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| invalid(n))?;
+        if !hex.is_ascii() {
+            return Err(invalid(n));
+        }
+        let mut digest = [0; 32];
+        for (i, byte) in digest.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid(n))?;
+        }
+        let name = match escaped {
+            true => unescape(name),
+            false => name.to_string(),
+        };
+        listed.push((PathBuf::from(name), digest));
+    }
+    Ok(listed)
+}
+
+/// Undoes the escaping `line` does.
+fn unescape(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => out.push('\n'),
+            ('\\', Some('\\')) => out.push('\\'),
+            _ => {
+                out.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    out
+}
+
+/// The SHA-256 of a file's contents.
+pub fn hash(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut sha = Sha256::default();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(sha.finish()),
+            n => sha.update(&buf[..n]),
+        }
+    }
+}
+
+/// A manifest line. Like sha256sum, names with a backslash or newline in
+/// them are escaped and the line marked with a leading backslash.
+fn line(digest: &[u8; 32], path: &Path) -> String {
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    let name = path.to_string_lossy();
+    if name.contains(['\\', '\n']) {
+        let name = name.replace('\\', "\\\\").replace('\n', "\\n");
+        format!("\\{hex}  {name}\n")
+    } else {
+        format!("{hex}  {name}\n")
+    }
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 as in FIPS 180-4, which is short enough not to need a crate.
+struct Sha256 {
+    state: [u32; 8],
+    block: Vec<u8>,
+    len: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256 {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: Vec::with_capacity(64),
+            len: 0,
+        }
+    }
+}
+
+impl Sha256 {
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        while !data.is_empty() {
+            let n = (64 - self.block.len()).min(data.len());
+            self.block.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.block.len() == 64 {
+                self.compress();
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bits = self.len * 8;
+        self.block.push(0x80);
+        if self.block.len() > 56 {
+            self.block.resize(64, 0);
+            self.compress();
+        }
+        self.block.resize(56, 0);
+        self.block.extend(bits.to_be_bytes());
+        self.compress();
+        let mut digest = [0; 32];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in self.block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+        }
+        for (s, x) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(x);
+        }
+        self.block.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(data: &[u8]) -> String {
+        let mut sha = Sha256::default();
+        // in pieces, to cross block boundaries mid-update.
+        for piece in data.chunks(37) {
+            sha.update(piece);
+        }
+        sha.finish().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn sha256_matches_known_digests() {
+        assert_eq!(
+            hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&[b'a'; 1000]),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn verify_reports_changed_missing_and_new_files() {
+        let dir = std::env::temp_dir().join(format!("adansonia-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let info = |name: &str| Info {
+            path: dir.join(name),
+            depth: 0,
+            size: 0,
+            disk: 0,
+            is_dir: false,
+            mtime: 0,
+            uid: 0,
+        };
+        std::fs::write(dir.join("same"), "a").unwrap();
+        std::fs::write(dir.join("edited"), "b").unwrap();
+        std::fs::write(dir.join("new\\n"), "c").unwrap();
+        let mut manifest = vec![];
+        write(
+            &mut manifest,
+            &dir,
+            &[info("same"), info("edited"), info("new\\n")],
+        )
+        .unwrap();
+        let manifest = String::from_utf8(manifest).unwrap();
+        let manifest = manifest.replace("new", "gone");
+        std::fs::write(dir.join("edited"), "B").unwrap();
+
+        let scanned = [info("same"), info("edited"), info("new\\n")];
+        let problems = verify(&manifest, &dir, &scanned).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let problems: Vec<String> = problems
+            .iter()
+            .map(|(p, problem)| format!("{} {problem:?}", p.strip_prefix(&dir).unwrap().display()))
+            .collect();
+        assert_eq!(
+            problems,
+            ["edited Changed", "gone\\n Missing", "new\\n New"]
+        );
+        assert!(verify("abc  x", &dir, &[]).is_err());
+    }
+
+    #[test]
+    fn escapes_names_like_sha256sum() {
+        let digest = [0xab; 32];
+        let hex = "ab".repeat(32);
+        assert_eq!(line(&digest, Path::new("a b")), format!("{hex}  a b\n"));
+        assert_eq!(
+            line(&digest, Path::new("a\\b\nc")),
+            format!("\\{hex}  a\\\\b\\nc\n")
+        );
+        assert_eq!(unescape("a\\\\nb\\nc"), "a\\nb\nc");
+    }
+}
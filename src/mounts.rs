@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+
+// kernel and memory-backed filesystems, which don't hold anything worth
+// looking at on disk.
+const VIRTUAL: [&str; 24] = [
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+/// Mount points of real filesystems, from /proc/mounts, in path order. A
+/// filesystem mounted in several places is only listed once.
+pub fn real_mounts() -> io::Result<Vec<PathBuf>> {
+    let mut mounts = vec![];
+    let mut sources = vec![];
+    for (source, target, fstype) in table()? {
+        if VIRTUAL.contains(&fstype.as_str()) || fstype.starts_with("fuse.") {
+            continue;
+        }
+        // bind mounts show the same device again.
+        if source.starts_with(b"/") && sources.contains(&source) {
+            continue;
+        }
+        sources.push(source);
+        mounts.push(target);
+    }
+    mounts.sort();
+    mounts.dedup();
+    Ok(mounts)
+}
+
+/// Mount points whose filesystem type matches one of `patterns`, which are
+/// either exact names or end in `*` to match a prefix, like `fuse.*`.
+pub fn of_types(patterns: &[String]) -> io::Result<HashSet<PathBuf>> {
+    if patterns.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let matches = |fstype: &str| {
+        patterns.iter().any(|p| match p.strip_suffix('*') {
+            Some(prefix) => fstype.starts_with(prefix),
+            None => fstype == p,
+        })
+    };
+    let table = table()?.into_iter();
+    Ok(table
+        .filter(|(_, _, t)| matches(t))
+        .map(|(_, m, _)| m)
+        .collect())
+}
+
+/// Mount points that show a filesystem, or part of one, that's mounted
+/// somewhere else too (bind mounts, container volumes), each with the
+/// directory its contents are already found under. Of the mounts of one
+/// filesystem, the one showing the most of it is kept.
+pub fn aliases() -> io::Result<HashMap<PathBuf, PathBuf>> {
+    // from /proc/self/mountinfo: the device, the directory within the
+    // filesystem that's mounted, and where.
+    let mut by_device: HashMap<Vec<u8>, Vec<(PathBuf, PathBuf)>> = HashMap::new();
+    for line in fs::read("/proc/self/mountinfo")?.split(|&b| b == b'\n') {
+        let fields: Vec<&[u8]> = line.split(|&b| b == b' ').collect();
+        let [_, _, device, root, target, ..] = fields[..] else {
+            continue;
+        };
+        let root = PathBuf::from(unescape(root));
+        let target = PathBuf::from(unescape(target));
+        by_device
+            .entry(device.to_vec())
+            .or_default()
+            .push((root, target));
+    }
+
+    let mut aliases = HashMap::new();
+    for mut mounts in by_device.into_values() {
+        // shortest root first, then in mount order.
+        mounts.sort_by_key(|(root, _)| root.components().count());
+        let (main_root, main_target) = mounts[0].clone();
+        for (root, target) in mounts.into_iter().skip(1) {
+            // subtrees outside the main mount's aren't counted there.
+            let Ok(inside) = root.strip_prefix(&main_root) else {
+                continue;
+            };
+            let original = match inside.as_os_str().is_empty() {
+                true => main_target.clone(),
+                false => main_target.join(inside),
+            };
+            if target != original {
+                aliases.insert(target, original);
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+/// The source, mount point and filesystem type of every line of /proc/mounts.
+fn table() -> io::Result<Vec<(Vec<u8>, PathBuf, String)>> {
+    let mut table = vec![];
+    for line in fs::read("/proc/mounts")?.split(|&b| b == b'\n') {
+        let fields: Vec<&[u8]> = line.split(|&b| b == b' ').collect();
+        let [source, target, fstype, ..] = fields[..] else {
+            continue;
+        };
+        let fstype = String::from_utf8_lossy(fstype).into_owned();
+        table.push((source.to_vec(), PathBuf::from(unescape(target)), fstype));
+    }
+    Ok(table)
+}
+
+/// Undoes the octal escapes (`\040` for a space and so on) in /proc/mounts.
+fn unescape(field: &[u8]) -> OsString {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < field.len() {
+        let octal = field
+            .get(i + 1..i + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u8::from_str_radix(d, 8).ok());
+        match octal {
+            Some(b) if field[i] == b'\\' => {
+                out.push(b);
+                i += 4;
+            }
+            _ => {
+                out.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+    OsString::from_vec(out)
+}
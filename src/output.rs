@@ -0,0 +1,562 @@
+use crate::compare::{self, Difference};
+use crate::tree::{Info, Tree};
+use bytesize::ByteSize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+/// How much of a tree to show when writing it out.
+#[derive(Clone, Copy, Default)]
+pub struct Prune {
+    /// Levels below the root to show, or all of them.
+    pub depth: Option<usize>,
+    /// Entries smaller than this are lumped together.
+    pub min_size: u64,
+    /// Rows in lists of the largest entries, or ROWS.
+    pub top: Option<usize>,
+}
+
+/// The children of `dir` worth showing, largest first, and the number and
+/// total size of those that were left out. Nothing is shown past the
+/// maximum depth.
+fn kept(tree: &Tree, dir: &Path, level: usize, prune: &Prune) -> (Vec<Info>, usize, u64) {
+    if prune.depth.is_some_and(|d| level >= d) {
+        return (vec![], 0, 0);
+    }
+    let (kept, rest): (Vec<Info>, Vec<Info>) = tree
+        .get(dir)
+        .into_iter()
+        .partition(|x| x.size >= prune.min_size);
+    (kept, rest.len(), rest.iter().map(|x| x.size).sum())
+}
+
+pub(crate) fn name(info: &Info) -> String {
+    match info.path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => info.path.to_string_lossy().into_owned(),
+    }
+}
+
+fn percent(size: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => size as f64 * 100.0 / total as f64,
+    }
+}
+
+/// Writes `root` and what's below it as a Graphviz graph, with bigger
+/// entries drawn in bigger type.
+pub fn dot(tree: &Tree, root: &Path, prune: &Prune) -> String {
+    let total = tree.size(root);
+    let mut out = String::new();
+    out.push_str("digraph adansonia {\n");
+    out.push_str("    rankdir=LR;\n");
+    out.push_str("    node [shape=box, style=rounded, fontname=\"sans-serif\"];\n");
+
+    let label = |out: &mut String, id: usize, name: &str, size: u64| {
+        let share = percent(size, total);
+        let _ = writeln!(
+            out,
+            "    n{id} [label=\"{}\\n{} ({share:.1}%)\", fontsize={:.0}];",
+            escape(name),
+            ByteSize(size),
+            10.0 + share / 5.0,
+        );
+    };
+    let Some(top) = tree.find(root) else {
+        out.push_str("}\n");
+        return out;
+    };
+    label(&mut out, 0, &top.path.to_string_lossy(), top.size);
+
+    let mut next = 1;
+    let mut stack = vec![(root.to_path_buf(), 0, 0)]; // directory, its node, its level
+    while let Some((dir, parent, level)) = stack.pop() {
+        let (children, hidden, hidden_size) = kept(tree, &dir, level, prune);
+        for child in children {
+            label(&mut out, next, &name(&child), child.size);
+            let _ = writeln!(out, "    n{parent} -> n{next};");
+            if child.is_dir {
+                stack.push((child.path, next, level + 1));
+            }
+            next += 1;
+        }
+        if hidden > 0 {
+            label(&mut out, next, &format!("{hidden} more"), hidden_size);
+            let _ = writeln!(out, "    n{next} [style=\"rounded,dashed\"];");
+            let _ = writeln!(out, "    n{parent} -> n{next};");
+            next += 1;
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Writes `root` and what's below it indented like `tree --charset ascii`,
+/// each entry with its size and share of the whole.
+pub fn tree(tree: &Tree, root: &Path, prune: &Prune) -> String {
+    let total = tree.size(root);
+    let mut out = String::new();
+    let Some(top) = tree.find(root) else {
+        return out;
+    };
+    let _ = writeln!(out, "{} {}", top.path.to_string_lossy(), ByteSize(top.size));
+    branches(tree, root, 0, prune, total, "", &mut out);
+    out
+}
+
+fn branches(
+    tree: &Tree,
+    dir: &Path,
+    level: usize,
+    prune: &Prune,
+    total: u64,
+    indent: &str,
+    out: &mut String,
+) {
+    let (children, hidden, hidden_size) = kept(tree, dir, level, prune);
+    let n = children.len() + (hidden > 0) as usize;
+    let line = |out: &mut String, i: usize, name: &str, size: u64| {
+        let branch = if i + 1 == n { "`-- " } else { "|-- " };
+        let share = percent(size, total);
+        let _ = writeln!(
+            out,
+            "{indent}{branch}{name} {} ({share:.1}%)",
+            ByteSize(size)
+        );
+    };
+    for (i, child) in children.iter().enumerate() {
+        let slash = if child.is_dir { "/" } else { "" };
+        line(out, i, &format!("{}{slash}", name(child)), child.size);
+        if child.is_dir {
+            let indent = format!("{indent}{}", if i + 1 == n { "    " } else { "|   " });
+            branches(tree, &child.path, level + 1, prune, total, &indent, out);
+        }
+    }
+    if hidden > 0 {
+        line(out, n - 1, &format!("[{hidden} more]"), hidden_size);
+    }
+}
+
+// rows in each table of the Markdown report, and each list of the top one.
+const ROWS: usize = 20;
+
+/// The largest directories under `root`, down to the given depth (one level
+/// by default), and its largest files, biggest first. Those smaller than
+/// the minimum size are left out.
+fn largest<'a>(tree: &'a Tree, root: &Path, prune: &Prune) -> (Vec<&'a Info>, Vec<&'a Info>) {
+    let subtree = tree.subtree(root);
+    let depth = root.components().count() + prune.depth.unwrap_or(1);
+    let rows = prune.top.unwrap_or(ROWS);
+    let mut dirs: Vec<&Info> = subtree
+        .iter()
+        .skip(1)
+        .filter(|x| x.is_dir && x.depth <= depth && x.size >= prune.min_size)
+        .collect();
+    dirs.sort_by_key(|x| std::cmp::Reverse(x.size));
+    dirs.truncate(rows);
+    let mut files: Vec<&Info> = subtree
+        .iter()
+        .filter(|x| !x.is_dir && x.size >= prune.min_size)
+        .collect();
+    files.sort_by_key(|x| std::cmp::Reverse(x.size));
+    files.truncate(rows);
+    (dirs, files)
+}
+
+/// Writes the largest directories and files under `root` as plain text,
+/// one per line with its size and share of the total, for mailing from
+/// cron and the like.
+pub fn top(tree: &Tree, root: &Path, prune: &Prune) -> String {
+    let total = tree.size(root);
+    let subtree = tree.subtree(root);
+    let files = subtree.iter().filter(|x| !x.is_dir).count();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}: {} in {} files and {} directories",
+        root.to_string_lossy(),
+        ByteSize(total),
+        crate::commaify(files),
+        crate::commaify(subtree.len().saturating_sub(files + 1)),
+    );
+    let (dirs, files) = largest(tree, root, prune);
+    for (heading, entries) in [("Largest directories", dirs), ("Largest files", files)] {
+        if entries.is_empty() {
+            continue;
+        }
+        let _ = writeln!(out, "\n{heading}");
+        for x in entries {
+            let slash = if x.is_dir { "/" } else { "" };
+            let _ = writeln!(
+                out,
+                "{:>10} {:>6.1}%  {}{slash}",
+                ByteSize(x.size).to_string(),
+                percent(x.size, total),
+                x.path.to_string_lossy()
+            );
+        }
+    }
+    out
+}
+
+/// Writes what differs between the directories `left` and `right`, biggest
+/// changes first: + for what's only in `right`, - for what's only in `left`
+/// and ~ for what's in both at different sizes.
+pub fn diff(tree: &Tree, left: &Path, right: &Path) -> String {
+    let differences = compare::compare(tree.subtree(left), tree.subtree(right));
+    let count = |f: fn(&Difference) -> bool| differences.iter().filter(|d| f(d)).count();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} only in {}, {} only in {}, {} changed",
+        crate::commaify(count(|d| d.right.is_none())),
+        left.to_string_lossy(),
+        crate::commaify(count(|d| d.left.is_none())),
+        right.to_string_lossy(),
+        crate::commaify(count(|d| d.left.is_some() && d.right.is_some())),
+    );
+    for d in &differences {
+        let slash = if d.is_dir { "/" } else { "" };
+        let (mark, sizes) = match (d.left, d.right) {
+            (Some(l), Some(r)) => ('~', format!(" ({} to {})", ByteSize(l), ByteSize(r))),
+            (None, _) => ('+', String::new()),
+            (_, None) => ('-', String::new()),
+        };
+        let sign = if d.change() < 0 { '-' } else { '+' };
+        let _ = writeln!(
+            out,
+            "{mark} {:>11}  {}{slash}{sizes}",
+            format!("{sign}{}", ByteSize(d.change().unsigned_abs())),
+            d.path.to_string_lossy()
+        );
+    }
+    out
+}
+
+/// Writes a Markdown report on `root`: its largest directories down to the
+/// given depth (one level by default), its largest files and how much each
+/// file extension takes up. Rows smaller than the minimum size are left out.
+pub fn markdown(tree: &Tree, root: &Path, prune: &Prune) -> String {
+    let total = tree.size(root);
+    let subtree = tree.subtree(root);
+    let relative = |x: &Info| {
+        let path = x.path.strip_prefix(root).unwrap_or(&x.path);
+        format!("`{}`", path.to_string_lossy().replace('|', "\\|"))
+    };
+    let mut out = String::new();
+    let _ = writeln!(out, "# Disk usage of `{}`\n", root.to_string_lossy());
+    let files = subtree.iter().filter(|x| !x.is_dir).count();
+    let _ = writeln!(
+        out,
+        "{} in {} files and {} directories.\n",
+        ByteSize(total),
+        crate::commaify(files),
+        crate::commaify(subtree.len().saturating_sub(files + 1)),
+    );
+
+    let (dirs, files) = largest(tree, root, prune);
+    out.push_str("## Largest directories\n\n| Directory | Size | Share |\n|---|--:|--:|\n");
+    for x in dirs {
+        let share = percent(x.size, total);
+        let _ = writeln!(
+            out,
+            "| {} | {} | {share:.1}% |",
+            relative(x),
+            ByteSize(x.size)
+        );
+    }
+    out.push_str("\n## Largest files\n\n| File | Size | Share |\n|---|--:|--:|\n");
+    for x in files {
+        let share = percent(x.size, total);
+        let _ = writeln!(
+            out,
+            "| {} | {} | {share:.1}% |",
+            relative(x),
+            ByteSize(x.size)
+        );
+    }
+
+    let mut extensions: HashMap<String, (u64, u64)> = HashMap::new();
+    for x in subtree.iter().filter(|x| !x.is_dir) {
+        let extension = match x.path.extension() {
+            Some(e) => format!(
+                "`.{}`",
+                e.to_string_lossy().to_lowercase().replace('|', "\\|")
+            ),
+            None => "(none)".to_string(),
+        };
+        let (count, size) = extensions.entry(extension).or_default();
+        *count += 1;
+        *size += x.size;
+    }
+    let mut extensions: Vec<_> = extensions
+        .into_iter()
+        .filter(|&(_, (_, size))| size >= prune.min_size)
+        .collect();
+    extensions.sort_by_key(|&(ref e, (_, size))| (std::cmp::Reverse(size), e.clone()));
+    out.push_str("\n## By extension\n\n| Extension | Files | Size | Share |\n|---|--:|--:|--:|\n");
+    for (extension, (count, size)) in extensions.iter().take(prune.top.unwrap_or(ROWS)) {
+        let share = percent(*size, total);
+        let count = crate::commaify(count);
+        let _ = writeln!(
+            out,
+            "| {extension} | {count} | {} | {share:.1}% |",
+            ByteSize(*size)
+        );
+    }
+    out
+}
+
+/// Writes `entries`, scanned at `scanned`, in the JSON format of `ncdu -o`,
+/// so they can be browsed with `ncdu -f` or fed to tools built around it.
+/// Sizes are apparent ones, with disk usage alongside, and directories
+/// only count their own, as ncdu adds the rest up itself.
+pub fn ncdu(out: &mut dyn io::Write, entries: &[Info], scanned: i64) -> io::Result<()> {
+    // entries are in path order, so each directory's contents follow it.
+    let mut own: Vec<(u64, u64)> = entries.iter().map(|x| (x.size, x.disk)).collect();
+    let mut open: Vec<usize> = vec![];
+    for (i, x) in entries.iter().enumerate() {
+        while open.last().is_some_and(|&d| entries[d].depth >= x.depth) {
+            open.pop();
+        }
+        if let Some(&d) = open.last() {
+            own[d].0 = own[d].0.saturating_sub(x.size);
+            own[d].1 = own[d].1.saturating_sub(x.disk);
+        }
+        if x.is_dir {
+            open.push(i);
+        }
+    }
+
+    write!(
+        out,
+        "[1,2,{{\"progname\":\"adansonia\",\"progver\":\"{}\",\"timestamp\":{scanned}}}",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    open.clear();
+    for (i, x) in entries.iter().enumerate() {
+        while open.last().is_some_and(|&d| entries[d].depth >= x.depth) {
+            open.pop();
+            out.write_all(b"]")?;
+        }
+        // a directory is an array of itself and then what's in it.
+        out.write_all(if x.is_dir { b",\n[" } else { b",\n" })?;
+        if x.is_dir {
+            open.push(i);
+        }
+        // the root goes by its whole path.
+        let name = match i {
+            0 => x.path.to_string_lossy().into_owned(),
+            _ => name(x),
+        };
+        write!(
+            out,
+            "{{\"name\":{},\"asize\":{},\"dsize\":{},\"uid\":{},\"mtime\":{}}}",
+            crate::summary::string(&name),
+            own[i].0,
+            own[i].1,
+            x.uid,
+            x.mtime
+        )?;
+    }
+    for _ in open {
+        out.write_all(b"]")?;
+    }
+    out.write_all(b"]\n")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn tree(entries: &[(&str, u64)]) -> Tree {
+        let mut tree = Tree {
+            data: entries
+                .iter()
+                .map(|&(p, size)| Info {
+                    path: PathBuf::from(p),
+                    depth: Path::new(p).components().count(),
+                    size,
+                    disk: 0,
+                    is_dir: !p.contains('.'),
+                    mtime: 0,
+                    uid: 0,
+                })
+                .collect(),
+        };
+        tree.preprocess();
+        tree
+    }
+
+    #[test]
+    fn dot_prunes_by_depth_and_size() {
+        let tree = tree(&[
+            ("/r", 0),
+            ("/r/a", 0),
+            ("/r/a/big.bin", 89),
+            ("/r/a/deep", 0),
+            ("/r/a/deep/x.bin", 5),
+            ("/r/s.txt", 2),
+            ("/r/t\"q.txt", 4),
+        ]);
+        let prune = Prune {
+            depth: Some(2),
+            min_size: 4,
+            top: None,
+        };
+        let dot = dot(&tree, Path::new("/r"), &prune);
+
+        assert!(dot.starts_with("digraph adansonia {\n"));
+        assert!(dot.contains(r#"n0 [label="/r\n100 B (100.0%)", fontsize=30];"#));
+        assert!(dot.contains(r#"label="big.bin\n89 B (89.0%)""#));
+        assert!(dot.contains(r#"label="t\"q.txt\n4 B (4.0%)""#));
+        assert!(dot.contains(r#"label="deep\n5 B (5.0%)""#));
+        assert!(!dot.contains("x.bin"), "deeper than the limit");
+        assert!(dot.contains(r#"label="1 more\n2 B (2.0%)""#));
+        assert!(!dot.contains("s.txt"));
+        assert_eq!(dot.matches("->").count(), 5);
+    }
+
+    #[test]
+    fn diff_marks_what_was_added_removed_and_resized() {
+        let tree = tree(&[
+            ("/b", 0),
+            ("/b/old", 0),
+            ("/b/old/gone.bin", 100),
+            ("/b/old/grew.log", 20),
+            ("/b/new", 0),
+            ("/b/new/grew.log", 25),
+            ("/b/new/extra", 0),
+            ("/b/new/extra/z.bin", 200),
+        ]);
+        let diff = diff(&tree, Path::new("/b/old"), Path::new("/b/new"));
+
+        assert_eq!(
+            diff,
+            "1 only in /b/old, 1 only in /b/new, 1 changed\n\
+             +      +200 B  extra/\n\
+             -      -100 B  gone.bin\n\
+             ~        +5 B  grew.log (20 B to 25 B)\n"
+        );
+    }
+
+    #[test]
+    fn markdown_has_directories_files_and_extensions() {
+        let tree = tree(&[
+            ("/r", 0),
+            ("/r/a", 0),
+            ("/r/a/x.log", 50),
+            ("/r/a/y.LOG", 20),
+            ("/r/a/deep", 0),
+            ("/r/a/deep/z|1.gz", 25),
+            ("/r/.hidden", 5),
+        ]);
+        let md = markdown(&tree, Path::new("/r"), &Prune::default());
+
+        assert!(md.starts_with("# Disk usage of `/r`\n\n100 B in 4 files and 2 directories.\n"));
+        assert!(md.contains("| `a` | 95 B | 95.0% |\n"));
+        assert!(!md.contains("| `a/deep` |"), "deeper than one level");
+        assert!(md.contains("| `a/deep/z\\|1.gz` | 25 B | 25.0% |\n"));
+        assert!(md.contains("| `.log` | 2 | 70 B | 70.0% |\n"));
+        assert!(md.contains("| (none) | 1 | 5 B | 5.0% |\n"));
+    }
+
+    #[test]
+    fn top_lists_the_largest_first() {
+        let tree = tree(&[
+            ("/r", 0),
+            ("/r/a", 0),
+            ("/r/a/x.log", 50),
+            ("/r/a/deep", 0),
+            ("/r/a/deep/z.gz", 25),
+            ("/r/b", 0),
+            ("/r/b/y.txt", 20),
+            ("/r/.hidden", 5),
+        ]);
+        let prune = Prune {
+            top: Some(2),
+            ..Prune::default()
+        };
+        assert_eq!(
+            top(&tree, Path::new("/r"), &prune),
+            "/r: 100 B in 4 files and 3 directories\n\
+             \nLargest directories\n\
+             \x20     75 B   75.0%  /r/a/\n\
+             \x20     20 B   20.0%  /r/b/\n\
+             \nLargest files\n\
+             \x20     50 B   50.0%  /r/a/x.log\n\
+             \x20     25 B   25.0%  /r/a/deep/z.gz\n"
+        );
+        let tree = self::tree(&[("/e", 0)]);
+        assert_eq!(
+            top(&tree, Path::new("/e"), &prune),
+            "/e: 0 B in 0 files and 0 directories\n"
+        );
+    }
+
+    #[test]
+    fn ncdu_nests_directories_with_their_own_sizes() {
+        let tree = tree(&[
+            ("/r", 4),
+            ("/r/a", 4),
+            ("/r/a/x.bin", 5),
+            ("/r/b", 4),
+            ("/r/c\"q\".txt", 3),
+        ]);
+        let mut out = vec![];
+        ncdu(&mut out, &tree.data, 100).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        let version = env!("CARGO_PKG_VERSION");
+        let entry = |name: &str, size: u64| {
+            format!("{{\"name\":{name},\"asize\":{size},\"dsize\":0,\"uid\":0,\"mtime\":0}}")
+        };
+        assert_eq!(
+            json,
+            format!(
+                "[1,2,{{\"progname\":\"adansonia\",\"progver\":\"{version}\",\"timestamp\":100}},\n\
+                 [{},\n[{},\n{}],\n[{}],\n{}]]\n",
+                entry("\"/r\"", 4),
+                entry("\"a\"", 4),
+                entry("\"x.bin\"", 5),
+                entry("\"b\"", 4),
+                entry(r#""c\"q\".txt""#, 3),
+            )
+        );
+    }
+
+    #[test]
+    fn tree_draws_branches_with_shares() {
+        let tree = tree(&[
+            ("/r", 0),
+            ("/r/a", 0),
+            ("/r/a/big.bin", 60),
+            ("/r/a/small.bin", 20),
+            ("/r/b", 0),
+            ("/r/b/c.txt", 15),
+            ("/r/d.txt", 3),
+            ("/r/e.txt", 2),
+        ]);
+        let prune = Prune {
+            depth: None,
+            min_size: 10,
+            top: None,
+        };
+        assert_eq!(
+            super::tree(&tree, Path::new("/r"), &prune),
+            "/r 100 B\n\
+             |-- a/ 80 B (80.0%)\n\
+             |   |-- big.bin 60 B (60.0%)\n\
+             |   `-- small.bin 20 B (20.0%)\n\
+             |-- b/ 15 B (15.0%)\n\
+             |   `-- c.txt 15 B (15.0%)\n\
+             `-- [2 more] 5 B (5.0%)\n"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! A minimal Apache Parquet writer for exporting scans: one flat schema of
+//! required columns, PLAIN encoded and uncompressed, which every reader
+//! understands. See https://github.com/apache/parquet-format for the layout.
+
+use crate::tree::Info;
+use std::io::{self, Write};
+
+const MAGIC: &[u8] = b"PAR1";
+// rows per row group, so readers can work through big scans in pieces.
+const GROUP: usize = 1 << 20;
+
+// thrift compact protocol types.
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+// from parquet.thrift.
+const BYTE_ARRAY: i32 = 6;
+const INT32: i32 = 1;
+const INT64: i32 = 2;
+const REQUIRED: i32 = 0;
+const UTF8: i32 = 0;
+const TIMESTAMP_MILLIS: i32 = 9;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const UNCOMPRESSED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+
+/// The columns written: name, physical type, converted type.
+const COLUMNS: [(&str, i32, Option<i32>); 5] = [
+    ("path", BYTE_ARRAY, Some(UTF8)),
+    ("size", INT64, None),
+    ("mtime", INT64, Some(TIMESTAMP_MILLIS)),
+    ("owner", BYTE_ARRAY, Some(UTF8)),
+    ("depth", INT32, None),
+];
+
+/// Writes `entries` as a Parquet file with path, size, mtime, owner and
+/// depth columns. `owner` names the owner of each entry's uid.
+pub fn write(
+    out: &mut impl Write,
+    entries: &[Info],
+    owner: impl Fn(u32) -> String,
+) -> io::Result<()> {
+    let mut offset = MAGIC.len() as i64;
+    out.write_all(MAGIC)?;
+    let mut groups = vec![];
+    for rows in entries.chunks(GROUP) {
+        let mut chunks = vec![];
+        for (column, (_, kind, _)) in COLUMNS.iter().enumerate() {
+            let mut values = vec![];
+            for x in rows {
+                match column {
+                    0 => byte_array(&mut values, x.path.to_string_lossy().as_bytes()),
+                    1 => values.extend((x.size as i64).to_le_bytes()),
+                    2 => values.extend(x.mtime.saturating_mul(1000).to_le_bytes()),
+                    3 => byte_array(&mut values, owner(x.uid).as_bytes()),
+                    _ => values.extend((x.depth as i32).to_le_bytes()),
+                }
+            }
+            let mut header = Thrift::default();
+            header.i32(1, DATA_PAGE);
+            header.i32(2, values.len() as i32);
+            header.i32(3, values.len() as i32);
+            header.begin(5);
+            header.i32(1, rows.len() as i32);
+            header.i32(2, PLAIN);
+            header.i32(3, RLE); // though required columns have no levels
+            header.i32(4, RLE);
+            header.end();
+            header.stop();
+            out.write_all(&header.bytes)?;
+            out.write_all(&values)?;
+            let size = (header.bytes.len() + values.len()) as i64;
+            chunks.push((*kind, offset, size));
+            offset += size;
+        }
+        groups.push((rows.len() as i64, chunks));
+    }
+
+    let mut meta = Thrift::default();
+    meta.i32(1, 1);
+    meta.list(2, COLUMNS.len() + 1, T_STRUCT);
+    meta.item();
+    meta.binary(4, b"schema");
+    meta.i32(5, COLUMNS.len() as i32);
+    meta.end();
+    for (name, kind, converted) in COLUMNS {
+        meta.item();
+        meta.i32(1, kind);
+        meta.i32(3, REQUIRED);
+        meta.binary(4, name.as_bytes());
+        if let Some(converted) = converted {
+            meta.i32(6, converted);
+        }
+        meta.end();
+    }
+    meta.i64(3, entries.len() as i64);
+    meta.list(4, groups.len(), T_STRUCT);
+    for (rows, chunks) in &groups {
+        meta.item();
+        meta.list(1, chunks.len(), T_STRUCT);
+        for (&(kind, offset, size), (name, _, _)) in chunks.iter().zip(COLUMNS) {
+            meta.item();
+            meta.i64(2, offset);
+            meta.begin(3);
+            meta.i32(1, kind);
+            meta.list(2, 1, T_I32);
+            meta.zigzag(PLAIN as i64);
+            meta.list(3, 1, T_BINARY);
+            meta.raw_binary(name.as_bytes());
+            meta.i32(4, UNCOMPRESSED);
+            meta.i64(5, *rows);
+            meta.i64(6, size);
+            meta.i64(7, size);
+            meta.i64(9, offset);
+            meta.end();
+            meta.end();
+        }
+        let total: i64 = chunks.iter().map(|&(_, _, size)| size).sum();
+        meta.i64(2, total);
+        meta.i64(3, *rows);
+        meta.end();
+    }
+    meta.binary(
+        6,
+        concat!("adansonia version ", env!("CARGO_PKG_VERSION")).as_bytes(),
+    );
+    meta.stop();
+    out.write_all(&meta.bytes)?;
+    out.write_all(&(meta.bytes.len() as u32).to_le_bytes())?;
+    out.write_all(MAGIC)
+}
+
+fn byte_array(values: &mut Vec<u8>, bytes: &[u8]) {
+    values.extend((bytes.len() as u32).to_le_bytes());
+    values.extend(bytes);
+}
+
+/// Just enough of Thrift's compact protocol to write Parquet metadata.
+/// Structs are opened with `begin` (or `item` inside a list) and closed with
+/// `end`; the outermost one is closed with `stop`.
+#[derive(Default)]
+struct Thrift {
+    bytes: Vec<u8>,
+    last: Vec<i16>, // the last field id written, for each open struct
+    field: i16,
+}
+
+impl Thrift {
+    fn varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.bytes.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.bytes.push(n as u8);
+    }
+
+    fn zigzag(&mut self, n: i64) {
+        self.varint(((n << 1) ^ (n >> 63)) as u64);
+    }
+
+    fn header(&mut self, id: i16, kind: u8) {
+        let delta = id - self.field;
+        if (1..=15).contains(&delta) {
+            self.bytes.push((delta as u8) << 4 | kind);
+        } else {
+            self.bytes.push(kind);
+            self.zigzag(id as i64);
+        }
+        self.field = id;
+    }
+
+    fn i32(&mut self, id: i16, n: i32) {
+        self.header(id, T_I32);
+        self.zigzag(n as i64);
+    }
+
+    fn i64(&mut self, id: i16, n: i64) {
+        self.header(id, T_I64);
+        self.zigzag(n);
+    }
+
+    fn binary(&mut self, id: i16, bytes: &[u8]) {
+        self.header(id, T_BINARY);
+        self.raw_binary(bytes);
+    }
+
+    /// A binary value on its own, as a list element. Other list elements
+    /// are written with zigzag, or begun with item if they're structs.
+    fn raw_binary(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.bytes.extend(bytes);
+    }
+
+    fn list(&mut self, id: i16, len: usize, kind: u8) {
+        self.header(id, T_LIST);
+        if len < 15 {
+            self.bytes.push((len as u8) << 4 | kind);
+        } else {
+            self.bytes.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    fn begin(&mut self, id: i16) {
+        self.header(id, T_STRUCT);
+        self.item();
+    }
+
+    fn item(&mut self) {
+        self.last.push(self.field);
+        self.field = 0;
+    }
+
+    fn end(&mut self) {
+        self.bytes.push(0);
+        self.field = self.last.pop().unwrap();
+    }
+
+    fn stop(&mut self) {
+        self.bytes.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn frames_pages_and_footer() {
+        let entries: Vec<Info> = ["/r", "/r/a"]
+            .iter()
+            .map(|p| Info {
+                path: PathBuf::from(p),
+                depth: 2,
+                size: 7,
+                disk: 0,
+                is_dir: false,
+                mtime: 1,
+                uid: 1000,
+            })
+            .collect();
+        let mut out = vec![];
+        write(&mut out, &entries, |uid| format!("u{uid}")).unwrap();
+
+        assert!(out.starts_with(MAGIC) && out.ends_with(MAGIC));
+        let footer = u32::from_le_bytes(out[out.len() - 8..out.len() - 4].try_into().unwrap());
+        let meta = &out[out.len() - 8 - footer as usize..out.len() - 8];
+        let created_by = concat!("adansonia version ", env!("CARGO_PKG_VERSION"), "\0");
+        assert!(meta.ends_with(created_by.as_bytes()));
+        // the path page follows the header straight after the magic, its
+        // values each prefixed with their length.
+        let values = [&2u32.to_le_bytes()[..], b"/r", &4u32.to_le_bytes(), b"/r/a"].concat();
+        let page = out.windows(values.len()).position(|w| w == values).unwrap();
+        assert!(page > MAGIC.len() && page < 32);
+        let owner = [&5u32.to_le_bytes()[..], b"u1000"].concat();
+        assert!(out.windows(owner.len()).any(|w| w == owner));
+        let mtime = 1000i64.to_le_bytes();
+        assert!(out.windows(16).any(|w| w == [mtime, mtime].concat()));
+    }
+}
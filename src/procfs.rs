@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A process holding at least one descriptor open somewhere under a path.
+pub struct Holder {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// Walks `/proc/*/fd` looking for descriptors that point at `path` or anything
+/// beneath it. Processes we aren't allowed to inspect are silently skipped, so
+/// without root this only sees our own user's processes.
+pub fn open_handles(path: &Path) -> Vec<Holder> {
+    let mut holders = vec![];
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return holders;
+    };
+    for proc in procs.flatten() {
+        let Some(pid) = proc.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(proc.path().join("fd")) else {
+            continue;
+        };
+        let holds = fds
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .any(|target| target.starts_with(path));
+        if holds {
+            let name = fs::read_to_string(proc.path().join("comm")).unwrap_or_default();
+            holders.push(Holder {
+                pid,
+                name: name.trim_end().to_string(),
+            });
+        }
+    }
+    holders
+}
+
+/// Formats holders as `name (pid), ...` for the status bar.
+pub fn describe(holders: &[Holder]) -> String {
+    holders
+        .iter()
+        .map(|h| format!("{} ({})", h.name, h.pid))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A regular file a process has open.
+pub struct OpenFile {
+    pub path: PathBuf, // ends in " (deleted)" once the file has been unlinked
+    pub size: u64,     // as it is now, not as scanned
+    pub holder: Holder,
+    pub writing: bool, // opened for writing
+}
+
+/// Every regular file under `path` that some process has open, largest
+/// first, including deleted files whose space won't be freed until they're
+/// closed. Like open_handles, only sees processes we may inspect.
+pub fn open_files(path: &Path) -> Vec<OpenFile> {
+    let mut files: Vec<OpenFile> = vec![];
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return files;
+    };
+    for proc in procs.flatten() {
+        let Some(pid) = proc.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(proc.path().join("fd")) else {
+            continue;
+        };
+        let name = fs::read_to_string(proc.path().join("comm")).unwrap_or_default();
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            // stat through the descriptor, which works for deleted files too.
+            let metadata = fd.path().metadata();
+            if !target.starts_with(path) || !metadata.as_ref().is_ok_and(|m| m.is_file()) {
+                continue;
+            }
+            let info = proc.path().join("fdinfo").join(fd.file_name());
+            let writing = is_writing(&fs::read_to_string(info).unwrap_or_default());
+            // a process often has the same file open more than once.
+            let seen = files
+                .iter_mut()
+                .find(|f| f.holder.pid == pid && f.path == target);
+            match seen {
+                Some(file) => file.writing |= writing,
+                None => files.push(OpenFile {
+                    path: target,
+                    size: metadata.map_or(0, |m| m.len()),
+                    holder: Holder {
+                        pid,
+                        name: name.trim_end().to_string(),
+                    },
+                    writing,
+                }),
+            }
+        }
+    }
+    files.sort_by_key(|f| std::cmp::Reverse(f.size));
+    files
+}
+
+/// Whether an fdinfo file's `flags:` line, in octal, has write access.
+fn is_writing(fdinfo: &str) -> bool {
+    let flags = fdinfo
+        .lines()
+        .find_map(|l| l.strip_prefix("flags:"))
+        .and_then(|f| i32::from_str_radix(f.trim(), 8).ok());
+    flags.is_some_and(|f| matches!(f & libc::O_ACCMODE, libc::O_WRONLY | libc::O_RDWR))
+}
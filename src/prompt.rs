@@ -0,0 +1,181 @@
+use crate::dirs;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Position, Rect};
+use ratatui::text::{Line, Span};
+use ratatui::Frame;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const HISTORY_LEN: usize = 100;
+
+pub enum Outcome {
+    Pending,
+    Submit(String),
+    Cancel,
+}
+
+/// A single-line input shown in the footer, with readline-ish editing and a
+/// history that is kept per prompt and persisted across sessions.
+pub struct Prompt {
+    name: &'static str,
+    input: Vec<char>,
+    cursor: usize, // in chars, 0..=input.len()
+    history: Vec<String>,
+    browsing: Option<usize>, // position in history while pressing up/down
+}
+
+impl Prompt {
+    pub fn new(name: &'static str) -> Prompt {
+        let history = history_file(name)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|s| s.lines().map(String::from).collect())
+            .unwrap_or_default();
+        Prompt {
+            name,
+            input: vec![],
+            cursor: 0,
+            history,
+            browsing: None,
+        }
+    }
+
+    /// A prompt without history, for answers that shouldn't be offered
+    /// again, such as a confirmation.
+    pub fn once() -> Prompt {
+        Prompt {
+            name: "",
+            input: vec![],
+            cursor: 0,
+            history: vec![],
+            browsing: None,
+        }
+    }
+
+    pub fn handle(&mut self, key: KeyEvent) -> Outcome {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => {
+                let input: String = self.input.iter().collect();
+                self.remember(&input);
+                return Outcome::Submit(input);
+            }
+            KeyCode::Esc => return Outcome::Cancel,
+            KeyCode::Char('c') if ctrl => return Outcome::Cancel,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.input.len(),
+            KeyCode::Char('u') if ctrl => {
+                self.input.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char('w') if ctrl => {
+                // delete the word before the cursor, along with trailing spaces.
+                let mut start = self.cursor;
+                while start > 0 && self.input[start - 1] == ' ' {
+                    start -= 1;
+                }
+                while start > 0 && self.input[start - 1] != ' ' {
+                    start -= 1;
+                }
+                self.input.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            KeyCode::Char(c) => {
+                self.input.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.input.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.len(),
+            KeyCode::Up => self.browse(true),
+            KeyCode::Down => self.browse(false),
+            _ => {}
+        }
+        Outcome::Pending
+    }
+
+    /// Steps through history, newest first. Stepping past the newest entry
+    /// clears the input.
+    fn browse(&mut self, older: bool) {
+        let pos = match (self.browsing, older) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => Some(i + 1).filter(|&i| i < self.history.len()),
+        };
+        self.browsing = pos;
+        self.input = pos
+            .map(|i| self.history[i].chars().collect())
+            .unwrap_or_default();
+        self.cursor = self.input.len();
+    }
+
+    fn remember(&mut self, input: &str) {
+        if input.is_empty()
+            || self.name.is_empty()
+            || self.history.last().is_some_and(|last| last == input)
+        {
+            return;
+        }
+        self.history.push(input.to_string());
+        if self.history.len() > HISTORY_LEN {
+            self.history.drain(..self.history.len() - HISTORY_LEN);
+        }
+        if let Some(path) = history_file(self.name) {
+            let _ = fs::create_dir_all(path.parent().unwrap());
+            let _ = save(&path, input);
+        }
+    }
+
+    /// What's been typed so far.
+    pub fn text(&self) -> String {
+        self.input.iter().collect()
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, label: &str) {
+        let before: String = self.input[..self.cursor].iter().collect();
+        let after: String = self.input[self.cursor..].iter().collect();
+        let label = Span::raw(format!("{label}: "));
+        let x = area.x + (label.width() + Span::raw(&before).width()) as u16;
+        frame.render_widget(
+            Line::from(vec![label, Span::raw(before), Span::raw(after)]),
+            area,
+        );
+        frame.set_cursor_position(Position::new(x.min(area.right().saturating_sub(1)), area.y));
+    }
+}
+
+/// Adds `input` to the history in `path`. Other sessions may have added to
+/// it since we read it, so it's read again under a lock and added to rather
+/// than overwritten with ours.
+fn save(path: &Path, input: &str) -> io::Result<()> {
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    dirs::lock(&file)?;
+    let mut history = String::new();
+    file.read_to_string(&mut history)?;
+    let mut history: Vec<&str> = history.lines().collect();
+    if history.last() != Some(&input) {
+        history.push(input);
+    }
+    let keep = &history[history.len().saturating_sub(HISTORY_LEN)..];
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all((keep.join("\n") + "\n").as_bytes())
+}
+
+fn history_file(name: &str) -> Option<PathBuf> {
+    dirs::state_dir().map(|dir| dir.join("history").join(name))
+}
@@ -0,0 +1,101 @@
+//! Sending scan summaries to a central collector, see `adansonia::summary`
+//! for what's sent. Meant to be run from cron on every machine in a fleet.
+
+use crate::dirs;
+use adansonia::summary::Previous;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// This machine's name, as it calls itself.
+pub fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|_| "localhost".to_string())
+}
+
+/// Where what was last pushed for `root` is remembered: one file per root,
+/// named after its path with the slashes swapped out.
+fn record_path(root: &Path) -> Option<PathBuf> {
+    let name = root.to_string_lossy().replace('/', "%");
+    Some(dirs::state_dir()?.join("pushed").join(name))
+}
+
+/// The record of the last push for `root`, locked so that overlapping cron
+/// runs take turns, along with what it says.
+pub struct Record {
+    file: Option<File>,
+    pub previous: Option<Previous>,
+}
+
+impl Record {
+    pub fn open(root: &Path) -> io::Result<Record> {
+        let Some(path) = record_path(root) else {
+            return Ok(Record {
+                file: None,
+                previous: None,
+            });
+        };
+        fs::create_dir_all(path.parent().unwrap())?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        dirs::lock(&file)?;
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        Ok(Record {
+            previous: Previous::parse(&text),
+            file: Some(file),
+        })
+    }
+
+    /// Replaces the record, once the collector has taken the new summary.
+    pub fn save(&mut self, record: &str) -> io::Result<()> {
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+        file.set_len(0)?;
+        file.rewind()?;
+        file.write_all(record.as_bytes())
+    }
+}
+
+/// POSTs `json` to `endpoint` with curl, which copes with HTTPS, proxies
+/// and the rest. Anything but a 2xx response is an error.
+pub fn send(endpoint: &str, json: &str) -> io::Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--max-time", "60"])
+        .args([
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+        ])
+        .arg(endpoint)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::other("this needs curl installed"),
+            _ => e,
+        })?;
+    let mut stdin = child.stdin.take().unwrap();
+    let written = stdin.write_all(json.as_bytes());
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // curl prefixes its messages with "curl: (22) ".
+        let why = stderr.lines().next().unwrap_or("curl failed");
+        let why = why.split_once(") ").map_or(why, |(_, why)| why);
+        return Err(io::Error::other(format!(
+            "couldn't push to {endpoint}: {why}"
+        )));
+    }
+    written
+}
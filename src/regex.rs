@@ -0,0 +1,483 @@
+//! Enough of regular expressions to search paths with, like `\.iso$` or
+//! `^/home/[^/]+/\.cache/`: literals, `.`, classes, `^` and `$`, groups,
+//! alternation and the usual repetitions, plus `(?i)` to ignore case.
+//! Matching runs the pattern's states side by side over the text, so it
+//! takes time in proportion to the text however the pattern is written.
+
+/// A set of characters one position can match.
+#[derive(Clone, Debug)]
+enum Class {
+    Any,
+    Range(char, char),
+    Digit,
+    Word,
+    Space,
+    Not(Box<Class>),
+    Union(Vec<Class>),
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Class::Any => true,
+            Class::Range(a, b) => (*a..=*b).contains(&c),
+            Class::Digit => c.is_ascii_digit(),
+            Class::Word => c.is_alphanumeric() || c == '_',
+            Class::Space => c.is_whitespace(),
+            Class::Not(class) => !class.matches(c),
+            Class::Union(classes) => classes.iter().any(|class| class.matches(c)),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Char(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat(Box<Node>, u32, Option<u32>), // at least, at most
+}
+
+#[derive(Debug)]
+enum Inst {
+    Char(Class),
+    Split(usize, usize), // carry on at both
+    Jump(usize),
+    Start,
+    End,
+    Match,
+}
+
+// repetition counts beyond this are refused, as each repeat is a copy.
+const MAX_REPEAT: u32 = 1000;
+// and so are patterns that come to more states than this.
+const MAX_STATES: usize = 100_000;
+
+/// A compiled pattern.
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    fold: bool, // ignoring case
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let (fold, pattern) = match pattern.strip_prefix("(?i)") {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            at: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.at < parser.chars.len() {
+            return Err("unmatched )".to_string());
+        }
+        let mut program = vec![];
+        compile(&node, &mut program)?;
+        program.push(Inst::Match);
+        Ok(Regex { program, fold })
+    }
+
+    /// Whether the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let mut current = States::new(self.program.len());
+        let mut next = States::new(self.program.len());
+        let mut chars = text.chars().peekable();
+        let mut start = true;
+        loop {
+            // a match may begin at any position.
+            let end = chars.peek().is_none();
+            if self.add(&mut current, 0, start, end) {
+                return true;
+            }
+            let Some(c) = chars.next() else {
+                return false;
+            };
+            let end = chars.peek().is_none();
+            next.clear();
+            for &pc in &current.list {
+                if let Inst::Char(class) = &self.program[pc] {
+                    if self.matches(class, c) && self.add(&mut next, pc + 1, false, end) {
+                        return true;
+                    }
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            start = false;
+        }
+    }
+
+    fn matches(&self, class: &Class, c: char) -> bool {
+        class.matches(c)
+            || self.fold
+                && c.to_lowercase()
+                    .chain(c.to_uppercase())
+                    .any(|c| class.matches(c))
+    }
+
+    /// Adds the state at `pc` and every state reachable from it without
+    /// taking a character. Returns whether that reaches a match.
+    fn add(&self, states: &mut States, pc: usize, start: bool, end: bool) -> bool {
+        let mut stack = std::mem::take(&mut states.stack);
+        stack.push(pc);
+        let mut matched = false;
+        while let Some(pc) = stack.pop() {
+            if !states.insert(pc) {
+                continue;
+            }
+            match self.program[pc] {
+                Inst::Char(_) => {}
+                Inst::Split(a, b) => stack.extend([b, a]),
+                Inst::Jump(to) => stack.push(to),
+                Inst::Start if start => stack.push(pc + 1),
+                Inst::End if end => stack.push(pc + 1),
+                Inst::Start | Inst::End => {}
+                Inst::Match => {
+                    matched = true;
+                    break;
+                }
+            }
+        }
+        stack.clear();
+        states.stack = stack;
+        matched
+    }
+}
+
+/// The states live at one position in the text, each once.
+struct States {
+    list: Vec<usize>,
+    seen: Vec<bool>,
+    stack: Vec<usize>, // kept to save allocating one for every add
+}
+
+impl States {
+    fn new(n: usize) -> States {
+        States {
+            list: vec![],
+            seen: vec![false; n],
+            stack: vec![],
+        }
+    }
+
+    fn insert(&mut self, pc: usize) -> bool {
+        if self.seen[pc] {
+            return false;
+        }
+        self.seen[pc] = true;
+        self.list.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        for &pc in &self.list {
+            self.seen[pc] = false;
+        }
+        self.list.clear();
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), String> {
+    if program.len() > MAX_STATES {
+        return Err("the pattern is too big".to_string());
+    }
+    match node {
+        Node::Empty => {}
+        Node::Char(class) => program.push(Inst::Char(class.clone())),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program)?;
+            }
+        }
+        Node::Alternate(nodes) => {
+            // a split to each but the last, each jumping past the rest.
+            let mut jumps = vec![];
+            for (i, node) in nodes.iter().enumerate() {
+                if i + 1 < nodes.len() {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(node, program)?;
+                    jumps.push(program.len());
+                    program.push(Inst::Jump(0));
+                    let next = program.len();
+                    program[split] = Inst::Split(split + 1, next);
+                } else {
+                    compile(node, program)?;
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat(node, min, max) => {
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(node, program)?;
+                    program.push(Inst::Jump(split));
+                    let end = program.len();
+                    program[split] = Inst::Split(split + 1, end);
+                }
+                Some(max) => {
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(node, program)?;
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.at += c.is_some() as usize;
+        c
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concatenation()?];
+        while self.peek() == Some('|') {
+            self.at += 1;
+            branches.push(self.concatenation()?);
+        }
+        Ok(match branches.len() {
+            1 => branches.pop().unwrap(),
+            _ => Node::Alternate(branches),
+        })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.repetition(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn repetition(&mut self, mut node: Node) -> Result<Node, String> {
+        loop {
+            let (min, max) = match self.peek() {
+                Some('{') => match self.counts() {
+                    Some(counts) => counts,
+                    None => return Ok(node), // a plain {
+                },
+                Some(c @ ('*' | '+' | '?')) => {
+                    self.at += 1;
+                    match c {
+                        '*' => (0, None),
+                        '+' => (1, None),
+                        _ => (0, Some(1)),
+                    }
+                }
+                _ => return Ok(node),
+            };
+            if matches!(node, Node::Start | Node::End | Node::Empty) {
+                return Err("nothing to repeat".to_string());
+            }
+            if let Some(max) = max.filter(|&max| max < min) {
+                return Err(format!("{{{min},{max}}} counts down"));
+            }
+            if min.max(max.unwrap_or(0)) > MAX_REPEAT {
+                return Err(format!("can't repeat more than {MAX_REPEAT} times"));
+            }
+            // lazy and greedy repetitions match the same.
+            if self.peek() == Some('?') {
+                self.at += 1;
+            }
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+    }
+
+    /// The counts of a {n}, {n,} or {n,m}, taking it, or None if what's
+    /// here isn't one.
+    fn counts(&mut self) -> Option<(u32, Option<u32>)> {
+        let close = self.chars[self.at..].iter().position(|&c| c == '}')?;
+        let inside: String = self.chars[self.at + 1..self.at + close].iter().collect();
+        let counts = match inside.split_once(',') {
+            None => {
+                let n = inside.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        self.at += close + 1;
+        Some(counts)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next().unwrap() {
+            '(' => {
+                if self.chars[self.at..].starts_with(&['?', ':']) {
+                    self.at += 2;
+                }
+                let node = self.alternation()?;
+                match self.next() {
+                    Some(')') => Ok(node),
+                    _ => Err("unmatched (".to_string()),
+                }
+            }
+            '[' => self.class().map(Node::Char),
+            '.' => Ok(Node::Char(Class::Any)),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '\\' => self.escape().map(Node::Char),
+            '*' | '+' | '?' => Err("nothing to repeat".to_string()),
+            c => Ok(Node::Char(Class::Range(c, c))),
+        }
+    }
+
+    /// The class of what follows a backslash.
+    fn escape(&mut self) -> Result<Class, String> {
+        let c = self.next().ok_or("a \\ at the end")?;
+        Ok(match c {
+            'd' => Class::Digit,
+            'w' => Class::Word,
+            's' => Class::Space,
+            'D' => Class::Not(Box::new(Class::Digit)),
+            'W' => Class::Not(Box::new(Class::Word)),
+            'S' => Class::Not(Box::new(Class::Space)),
+            'n' => Class::Range('\n', '\n'),
+            't' => Class::Range('\t', '\t'),
+            c if c.is_ascii_alphanumeric() => return Err(format!("\\{c} isn't supported")),
+            c => Class::Range(c, c),
+        })
+    }
+
+    /// A bracketed class, after the [.
+    fn class(&mut self) -> Result<Class, String> {
+        let negated = self.peek() == Some('^');
+        self.at += negated as usize;
+        let mut classes = vec![];
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("unmatched [")?;
+            let low = match c {
+                ']' if !first => break,
+                '\\' => match self.escape()? {
+                    Class::Range(c, _) => c,
+                    class => {
+                        classes.push(class);
+                        first = false;
+                        continue;
+                    }
+                },
+                c => c,
+            };
+            first = false;
+            // a range, unless the - is last.
+            if self.peek() == Some('-') && self.chars.get(self.at + 1).is_some_and(|&c| c != ']') {
+                self.at += 1;
+                let high = match self.next().unwrap() {
+                    '\\' => match self.escape()? {
+                        Class::Range(c, _) => c,
+                        _ => return Err("a class can't end a range".to_string()),
+                    },
+                    c => c,
+                };
+                if high < low {
+                    return Err(format!("the range {low}-{high} is backwards"));
+                }
+                classes.push(Class::Range(low, high));
+            } else {
+                classes.push(Class::Range(low, low));
+            }
+        }
+        let class = Class::Union(classes);
+        Ok(match negated {
+            true => Class::Not(Box::new(class)),
+            false => class,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn matches_anywhere_unless_anchored() {
+        assert!(matches(r"\.iso$", "/srv/images/debian-12.iso"));
+        assert!(!matches(r"\.iso$", "/srv/images/debian-12.iso.part"));
+        assert!(matches(r"^/home/[^/]+/\.cache/", "/home/kim/.cache/pip"));
+        assert!(!matches(r"^/home/[^/]+/\.cache/", "/home/kim/src/.cache/x"));
+        assert!(matches("", "anything"));
+        assert!(matches("^$", ""));
+        assert!(!matches("^$", "x"));
+    }
+
+    #[test]
+    fn handles_repetition_alternation_and_classes() {
+        assert!(matches(r"core\.\d+$", "/var/crash/core.12345"));
+        assert!(!matches(r"core\.\d+$", "/var/crash/core.x"));
+        assert!(matches(r"\.(jpe?g|png)$", "a/b.jpeg"));
+        assert!(matches(r"\.(jpe?g|png)$", "a/b.png"));
+        assert!(!matches(r"\.(jpe?g|png)$", "a/b.gif"));
+        assert!(matches(r"^a{2,3}$", "aaa"));
+        assert!(!matches(r"^a{2,3}$", "aaaa"));
+        assert!(matches(r"^a{2,}$", "aaaaa"));
+        assert!(matches(r"^x{3}$", "xxx"));
+        assert!(matches(r"a{,", "a{,"), "not a repetition");
+        assert!(matches(r"[a-c-]x", "-x"));
+        assert!(matches(r"[]]", "]"));
+        assert!(matches(r"[\d_]{2}", "a_1"));
+        assert!(matches(r"(?:ab)+c", "xababc"));
+        assert!(matches("(?i)readme", "README.md"));
+        assert!(!matches("readme", "README.md"));
+        assert!(matches("(?i)[a-z]+\\.MD", "readme.md"));
+        // the kind of pattern that takes backtracking matchers forever.
+        let text = "a".repeat(40);
+        assert!(!matches("^(a*)*b$", &text));
+    }
+
+    #[test]
+    fn refuses_what_it_cant_match() {
+        for bad in [
+            "(", "a)", "[a", "*a", "a{3,1}", r"\b", "[z-a]", "a{5000}", "\\",
+        ] {
+            assert!(Regex::new(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Working out whether something can be deleted before trying, so the
+//! reason it can't is shown up front rather than after a confirmation, and
+//! then deleting it.
+
+use crate::fsstat;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
+
+// from linux/fs.h.
+const FS_IMMUTABLE_FL: libc::c_long = 0x10;
+const FS_APPEND_FL: libc::c_long = 0x20;
+
+/// Why deleting `path` would fail, if it would: it or its directory has
+/// the immutable or append-only flag, the filesystem is read-only, or the
+/// directory isn't ours to change. `names` gives owners' names by uid.
+/// Only `path` itself is looked at, not everything under it.
+pub fn obstacle(path: &Path, names: &HashMap<u32, String>) -> Option<String> {
+    let parent = path.parent()?;
+    let owner = |uid: u32| names.get(&uid).cloned().unwrap_or(uid.to_string());
+    for (p, what) in [(path, "it's"), (parent, "its directory is")] {
+        match flags(p) {
+            Some(f) if f & FS_IMMUTABLE_FL != 0 => return Some(format!("{what} immutable")),
+            Some(f) if f & FS_APPEND_FL != 0 => return Some(format!("{what} append-only")),
+            _ => {}
+        }
+    }
+    if fsstat::read_only(parent).unwrap_or(false) {
+        return Some("read-only filesystem".to_string());
+    }
+    let dir = fs::metadata(parent).ok()?;
+    let c_parent = CString::new(parent.as_os_str().as_bytes()).ok()?;
+    if unsafe { libc::access(c_parent.as_ptr(), libc::W_OK | libc::X_OK) } != 0 {
+        return Some(format!("its directory is owned by {}", owner(dir.uid())));
+    }
+    // in a sticky directory like /tmp, only owners may delete.
+    let me = unsafe { libc::geteuid() };
+    let file = fs::symlink_metadata(path).ok()?;
+    if dir.mode() & libc::S_ISVTX != 0 && me != 0 && me != file.uid() && me != dir.uid() {
+        return Some(format!(
+            "owned by {} in a sticky directory",
+            owner(file.uid())
+        ));
+    }
+    None
+}
+
+/// The inode flags of `path`, as lsattr shows them. None where they can't
+/// be read, e.g. on filesystems without them.
+fn flags(path: &Path) -> Option<libc::c_long> {
+    let file = File::options()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+    let mut flags: libc::c_long = 0;
+    match unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } {
+        0 => Some(flags),
+        _ => None,
+    }
+}
+
+/// Deletes `path`, with everything in it if it's a directory. Nothing is
+/// deleted if it's a mount point or has another filesystem mounted inside,
+/// which remove_dir_all would empty along with the rest.
+pub fn delete(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return fs::remove_file(path);
+    }
+    let parent = path.parent().map(fs::metadata).transpose()?;
+    if parent.is_some_and(|p| p.dev() != metadata.dev()) {
+        return Err(io::Error::other("it's a mount point"));
+    }
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let inner = entry.metadata()?;
+            if !inner.is_dir() {
+                continue;
+            }
+            if inner.dev() != metadata.dev() {
+                let e = format!("{} is mounted inside it", entry.path().display());
+                return Err(io::Error::other(e));
+            }
+            dirs.push(entry.path());
+        }
+    }
+    fs::remove_dir_all(path)
+}
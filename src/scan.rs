@@ -0,0 +1,917 @@
+use crate::commaify;
+use crate::error::{Error, Result};
+use crate::exclude::Excludes;
+use crate::gitignore::Ignores;
+use crate::tree::{Info, Tree};
+use crate::xattr;
+use crossbeam_deque::{Steal, Worker};
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use std::{fs, io, mem, thread};
+
+/// A file or directory as reported by a source, before it's placed in a tree.
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub disk: u64, // what it takes up, where the source can tell, or else its size
+    pub is_dir: bool,
+    pub mtime: i64,
+    pub uid: u32,
+}
+
+/// Where a scan gets its entries from: the local filesystem, or in future an
+/// archive, a remote host or an object store. Sources only list directories;
+/// walking them in parallel and streaming results back is shared by all of
+/// them in scan().
+pub trait ScanSource: Send + Sync + 'static {
+    /// Describes the root the scan starts from, which may be a lone file.
+    fn root(&self, root: &Path) -> io::Result<Entry>;
+
+    /// Lists the children of `dir` that belong in the tree. Directories among
+    /// them are listed in turn. An outer error skips `dir`'s contents entirely;
+    /// inner ones skip a single entry. Both are counted as scan errors.
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<Entry>>>;
+
+    /// How many directories may be listed at once.
+    fn threads(&self) -> usize {
+        16
+    }
+
+    /// How long listing a single directory may take before it's skipped.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// How long a worker rests after handing over a batch of results that
+    /// took `worked` to gather.
+    fn rest(&self, worked: Duration) -> Duration {
+        let _ = worked;
+        Duration::ZERO
+    }
+}
+
+/// How a LocalSource counts what it finds.
+#[derive(Clone, Default)]
+pub struct Options {
+    /// Round each file up to this allocation unit.
+    pub block_size: Option<u64>,
+    /// Leave out the contents of directories tagged with CACHEDIR.TAG.
+    pub exclude_caches: bool,
+    /// Skip directories that take longer than this to list. Without it only
+    /// network filesystems, which can hang, are given two minutes.
+    pub timeout: Option<Duration>,
+    /// Use fewer threads and rest between batches, leaving the disks to
+    /// whatever else the machine is doing.
+    pub gentle: bool,
+    /// Keep the workers busy for at most this many percent of one core's
+    /// time, counting time spent waiting on the disk as well.
+    pub cpu_budget: Option<u32>,
+    /// How many directories to list at once, instead of one per CPU, or
+    /// fewer on network filesystems.
+    pub threads: Option<usize>,
+    /// List with a single thread, going through each directory in name
+    /// order, so that scans of an unchanged tree turn out the same.
+    pub deterministic: bool,
+    /// Bind mounts and other second views of a filesystem, each with where
+    /// its contents can be found already. They're left empty so nothing is
+    /// counted twice.
+    pub aliases: HashMap<PathBuf, PathBuf>,
+    /// Leave out whatever these match, relative to the root being scanned.
+    pub excludes: Excludes,
+    /// Also leave out what .gitignore and other ignore files say to, each
+    /// for the directory it's in and those below.
+    pub respect_gitignore: bool,
+    /// Count the space taken by extended attributes, ACLs among them, on
+    /// top of files' contents.
+    pub xattrs: bool,
+    /// Count a file with several hard links once for every link, as if each
+    /// were a copy, instead of only the first one found.
+    pub count_links: bool,
+}
+
+// files with fewer bytes than this unallocated aren't worth pointing out.
+const SPARSE_MIN: u64 = 1 << 20;
+
+// the start of a valid CACHEDIR.TAG, see https://bford.info/cachedir/
+const CACHEDIR_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// The local filesystem, staying on the root's device and skipping symlinks.
+pub struct LocalSource {
+    root: PathBuf,
+    root_device: u64,
+    network: bool, // a remote filesystem, which we go easy on
+    options: Options,
+    slack: AtomicU64,
+    calls: AtomicU64,                   // made to the filesystem, see calls()
+    mounts: Mutex<Vec<PathBuf>>,        // directories skipped for being on another device
+    caches: Mutex<Vec<PathBuf>>,        // directories tagged as caches
+    sparse: Mutex<Vec<(PathBuf, u64)>>, // files with holes, and how many bytes of them are holes
+    links: Mutex<HashMap<(u64, u64), PathBuf>>, // files with several links, and the one counted
+    ignores: Mutex<HashMap<PathBuf, Ignores>>, // by the directories with ignore files of their own
+}
+
+impl LocalSource {
+    pub fn new(root: &Path, options: Options) -> io::Result<LocalSource> {
+        let mut ignores = HashMap::new();
+        if let (true, Some(parent)) = (options.respect_gitignore, root.parent()) {
+            ignores.insert(parent.to_path_buf(), Ignores::above(parent));
+        }
+        Ok(LocalSource {
+            root: root.to_path_buf(),
+            root_device: root.metadata()?.dev(),
+            network: is_network(root)?,
+            options,
+            slack: AtomicU64::new(0),
+            calls: AtomicU64::new(0),
+            mounts: Mutex::new(vec![]),
+            caches: Mutex::new(vec![]),
+            sparse: Mutex::new(vec![]),
+            links: Mutex::new(HashMap::new()),
+            ignores: Mutex::new(ignores),
+        })
+    }
+
+    /// Directories holding a valid CACHEDIR.TAG, whose contents can be
+    /// regenerated and so are safe to delete.
+    pub fn caches(&self) -> Vec<PathBuf> {
+        let mut caches = self.caches.lock().unwrap().clone();
+        caches.sort();
+        caches
+    }
+
+    /// Files that take up noticeably less space than their size says, being
+    /// sparse or compressed by the filesystem, with the difference.
+    pub fn sparse(&self) -> Vec<(PathBuf, u64)> {
+        let mut sparse = self.sparse.lock().unwrap().clone();
+        sparse.sort();
+        sparse
+    }
+
+    /// Where this source was scanned from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Directories that were skipped because something else is mounted there.
+    pub fn skipped_mounts(&self) -> Vec<PathBuf> {
+        let mut mounts = self.mounts.lock().unwrap().clone();
+        mounts.sort();
+        mounts
+    }
+
+    /// Whether the root is on a network filesystem, and so scanned gently.
+    pub fn is_network(&self) -> bool {
+        self.network
+    }
+
+    /// Bytes lost to rounding files up to the block size so far.
+    pub fn slack(&self) -> u64 {
+        self.slack.load(Ordering::Relaxed)
+    }
+
+    /// Calls made to the filesystem so far: one for each directory listed,
+    /// however many reads that takes, and one for each entry looked at, its
+    /// extended attributes read or cache tag checked.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// What extended attributes on `path` add to its size, if they're counted.
+    fn xattrs(&self, path: &Path) -> u64 {
+        match self.options.xattrs {
+            true => {
+                self.calls.fetch_add(1, Ordering::Relaxed);
+                xattr::size(path)
+            }
+            false => 0,
+        }
+    }
+
+    /// Whether `path` is the link its file is counted under: the first one
+    /// found, unless every link is counted. Asking again, when a directory
+    /// is measured afresh, gives the same answer.
+    fn counted(&self, path: &Path, metadata: &fs::Metadata) -> bool {
+        if self.options.count_links || metadata.is_dir() || metadata.nlink() <= 1 {
+            return true;
+        }
+        let mut links = self.links.lock().unwrap();
+        let first = links
+            .entry((metadata.dev(), metadata.ino()))
+            .or_insert_with(|| path.to_path_buf());
+        first == path
+    }
+
+    /// The ignore files in effect in `dir`, reading its own. Its parent's
+    /// have been read already, having been listed first.
+    fn ignores(&self, dir: &Path) -> Ignores {
+        let inherited = {
+            let ignores = self.ignores.lock().unwrap();
+            let mut parents = dir.ancestors().skip(1);
+            parents
+                .find_map(|d| ignores.get(d))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let own = inherited.enter(dir);
+        let mut ignores = self.ignores.lock().unwrap();
+        match own {
+            Some(own) => {
+                ignores.insert(dir.to_path_buf(), own.clone());
+                own
+            }
+            None => {
+                // it may be measured afresh after its ignore files are deleted.
+                ignores.remove(dir);
+                inherited
+            }
+        }
+    }
+
+    /// Rounds a file size up to the allocation unit of the filesystem, if given.
+    fn allocated(&self, size: u64) -> u64 {
+        match self.options.block_size {
+            Some(block_size) => size.div_ceil(block_size) * block_size,
+            None => size,
+        }
+    }
+
+    /// Recomputes the size of `path` straight from disk, counting the same way
+    /// a scan does, or its disk usage if not `apparent`. Returns None if it no
+    /// longer exists.
+    pub fn size_on_disk(&self, path: &Path, apparent: bool) -> Option<u64> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        if !self.counted(path, &metadata) {
+            return Some(0);
+        }
+        let mut total = match apparent {
+            // directories only have a size of their own for their attributes.
+            true if metadata.is_dir() => self.xattrs(path),
+            true => self.allocated(metadata.size()) + self.xattrs(path),
+            false => metadata.blocks() * 512,
+        };
+        if !metadata.is_dir() {
+            return Some(total);
+        }
+        let mut stack = vec![path.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            for entry in self
+                .read_dir(&dir)
+                .unwrap_or_default()
+                .into_iter()
+                .flatten()
+            {
+                total += if apparent { entry.size } else { entry.disk };
+                if entry.is_dir {
+                    stack.push(entry.path);
+                }
+            }
+        }
+        Some(total)
+    }
+}
+
+impl ScanSource for LocalSource {
+    fn timeout(&self) -> Option<Duration> {
+        // a huge directory on a slow local disk is still getting somewhere,
+        // so it's only given up on when asked to.
+        match self.options.timeout {
+            Some(timeout) => Some(timeout),
+            None => self.network.then_some(Duration::from_secs(120)),
+        }
+    }
+
+    fn threads(&self) -> usize {
+        if self.options.deterministic {
+            return 1;
+        }
+        // every outstanding request is load on someone else's server.
+        let threads = match self.options.threads {
+            Some(n) => n.max(1),
+            None if self.options.gentle => 2,
+            None if self.network => 4,
+            None => thread::available_parallelism().map_or(16, |n| n.get()),
+        };
+        let budget = self.options.cpu_budget.map(|p| p.div_ceil(100).max(1));
+        threads.min(budget.map_or(usize::MAX, |n| n as usize))
+    }
+
+    fn rest(&self, worked: Duration) -> Duration {
+        // each worker gets an even share of the budget.
+        let share = match self.options.cpu_budget {
+            Some(p) => p.max(1) as f64 / 100.0 / self.threads() as f64,
+            None => 1.0,
+        };
+        let share = if self.options.gentle {
+            share.min(0.5)
+        } else {
+            share
+        };
+        worked.mul_f64((1.0 / share - 1.0).max(0.0))
+    }
+
+    fn root(&self, root: &Path) -> io::Result<Entry> {
+        let metadata = root.metadata()?;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        Ok(Entry {
+            path: root.to_path_buf(),
+            size: self.xattrs(root)
+                + if metadata.is_dir() {
+                    metadata.size()
+                } else {
+                    self.allocated(metadata.size())
+                },
+            disk: metadata.blocks() * 512,
+            is_dir: metadata.is_dir(),
+            mtime: metadata.mtime(),
+            uid: metadata.uid(),
+        })
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<Entry>>> {
+        let mut entries = vec![];
+        // an alias asked for by name is scanned like any other directory.
+        if dir != self.root && self.options.aliases.contains_key(dir) {
+            return Ok(entries);
+        }
+        let ignores = self.options.respect_gitignore.then(|| self.ignores(dir));
+        let mut listing: Vec<_> = retry_stale(|| fs::read_dir(dir))?.collect();
+        let stats = listing.iter().filter(|e| e.is_ok()).count() as u64;
+        self.calls.fetch_add(1 + stats, Ordering::Relaxed);
+        if self.options.deterministic {
+            listing.sort_by_key(|e| e.as_ref().ok().map(|e| e.file_name()));
+        }
+        for entry in listing {
+            let stat = |e: fs::DirEntry| retry_stale(|| e.metadata()).map(|m| (e, m));
+            let (entry, metadata) = match entry.and_then(stat) {
+                Ok(x) => x,
+                Err(e) => {
+                    entries.push(Err(e));
+                    continue;
+                }
+            };
+
+            // skip symlinks and files in different devices, remembering mount
+            // points so they can be reported.
+            if metadata.is_symlink() {
+                continue;
+            }
+            let excludes = &self.options.excludes;
+            if !excludes.is_empty() {
+                let path = entry.path();
+                let relative = path.strip_prefix(&self.root).unwrap_or(&path);
+                if excludes.excludes(relative, metadata.is_dir()) {
+                    continue;
+                }
+            }
+            if let Some(ignores) = &ignores {
+                if ignores.ignores(&entry.path(), metadata.is_dir()) {
+                    continue;
+                }
+            }
+            if self.root_device != metadata.dev() {
+                if metadata.is_dir() {
+                    let mut mounts = self.mounts.lock().unwrap();
+                    if !mounts.contains(&entry.path()) {
+                        mounts.push(entry.path());
+                    }
+                }
+                continue;
+            }
+
+            if !self.counted(&entry.path(), &metadata) {
+                // another link to a file that's already been counted.
+                entries.push(Ok(Entry {
+                    path: entry.path(),
+                    size: 0,
+                    disk: 0,
+                    is_dir: false,
+                    mtime: metadata.mtime(),
+                    uid: metadata.uid(),
+                }));
+                continue;
+            }
+            let size = if metadata.is_dir() {
+                0
+            } else {
+                let size = self.allocated(metadata.size());
+                self.slack
+                    .fetch_add(size - metadata.size(), Ordering::Relaxed);
+                let holes = size.saturating_sub(metadata.blocks() * 512);
+                if holes >= SPARSE_MIN {
+                    self.sparse.lock().unwrap().push((entry.path(), holes));
+                }
+                size
+            };
+            entries.push(Ok(Entry {
+                size: size + self.xattrs(&entry.path()),
+                disk: metadata.blocks() * 512,
+                path: entry.path(),
+                is_dir: metadata.is_dir(),
+                mtime: metadata.mtime(),
+                uid: metadata.uid(),
+            }));
+        }
+        let tag = entries
+            .iter()
+            .flatten()
+            .find(|e| !e.is_dir && e.path.file_name().is_some_and(|n| n == "CACHEDIR.TAG"));
+        if tag.is_some() {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+        }
+        if tag.is_some_and(|tag| is_cachedir_tag(&tag.path)) {
+            self.caches.lock().unwrap().push(dir.to_path_buf());
+            if self.options.exclude_caches {
+                // keep just the tag, like tar --exclude-caches.
+                entries.retain(|e| {
+                    e.as_ref()
+                        .is_ok_and(|e| e.path.file_name().is_some_and(|n| n == "CACHEDIR.TAG"))
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// statfs(2) magic numbers of filesystems that live on another machine.
+const NETWORK_MAGIC: [u32; 8] = [
+    0x6969,     // nfs
+    0x517b,     // smb
+    0xff534d42, // cifs
+    0xfe534d42, // smb2
+    0x65735546, // fuse, e.g. sshfs
+    0x01021997, // 9p
+    0x00c36400, // ceph
+    0x5346414f, // afs
+];
+
+fn is_network(path: &Path) -> io::Result<bool> {
+    let c_path = std::ffi::CString::new(path.as_os_str().as_encoded_bytes())?;
+    let mut stat = mem::MaybeUninit::<libc::statfs>::uninit();
+    if unsafe { libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    Ok(NETWORK_MAGIC.contains(&(stat.f_type as u32)))
+}
+
+/// Runs `f`, retrying a few times with backoff while it fails with ESTALE,
+/// which NFS returns when a file handle has gone out of date under us.
+fn retry_stale<T>(mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delay = Duration::from_millis(50);
+    for _ in 0..3 {
+        match f() {
+            Err(e) if e.raw_os_error() == Some(libc::ESTALE) => thread::sleep(delay),
+            result => return result,
+        }
+        delay *= 4;
+    }
+    f()
+}
+
+fn is_cachedir_tag(path: &Path) -> bool {
+    let mut start = [0; CACHEDIR_SIGNATURE.len()];
+    fs::File::open(path)
+        .and_then(|mut f| io::Read::read_exact(&mut f, &mut start))
+        .is_ok()
+        && start == CACHEDIR_SIGNATURE
+}
+
+/// A scan running on background threads. Entries are streamed back in batches
+/// and the channel disconnects once every worker has finished.
+pub struct Scan {
+    pub rx: mpsc::Receiver<Vec<Info>>,
+    pub errors: Arc<AtomicU64>, // directories and entries that couldn't be read
+    pub timed_out: Arc<Mutex<Vec<PathBuf>>>, // directories skipped for taking too long
+    pub control: Control,
+    started: Instant,
+}
+
+/// The state of one scan's workers, shared with whoever controls them.
+struct Gate {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    cancelled: AtomicBool,
+    done: AtomicBool,
+    found: AtomicU64,
+    errors: Arc<AtomicU64>,
+    workers: Vec<Counters>,
+}
+
+/// Live counterparts of WorkerStats, updated by the worker itself.
+#[derive(Default)]
+struct Counters {
+    listed: AtomicU64,
+    steals: AtomicU64,
+    stolen: AtomicU64,
+    queue: AtomicUsize,
+    idle_us: AtomicU64,
+    finished: AtomicBool,
+}
+
+/// What one worker thread has been up to, for spotting uneven work.
+#[derive(Clone, Debug, Default)]
+pub struct WorkerStats {
+    pub listed: u64,    // directories listed
+    pub steals: u64,    // attempts to take work from other workers
+    pub stolen: u64,    // attempts that came away with directories
+    pub queue: usize,   // directories waiting in its own queue
+    pub idle: Duration, // time spent doing anything but listing
+    pub finished: bool, // out of work, so it has exited
+}
+
+impl Gate {
+    /// Blocks while paused. False once the scan has been cancelled.
+    fn proceed(&self) -> bool {
+        let mut paused = self.paused.lock().unwrap();
+        while *paused && !self.cancelled.load(Ordering::Relaxed) {
+            paused = self.resumed.wait(paused).unwrap();
+        }
+        !self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// A handle for pausing, resuming or cancelling a running scan and watching
+/// its progress from another thread. Workers finish the directory they're on
+/// before pausing or stopping.
+#[derive(Clone)]
+pub struct Control {
+    gates: Vec<Arc<Gate>>, // several for merged scans
+}
+
+impl Control {
+    pub fn pause(&self) {
+        for gate in &self.gates {
+            *gate.paused.lock().unwrap() = true;
+        }
+    }
+
+    pub fn resume(&self) {
+        for gate in &self.gates {
+            *gate.paused.lock().unwrap() = false;
+            gate.resumed.notify_all();
+        }
+    }
+
+    /// Stops the scan, leaving whatever has been found so far.
+    pub fn cancel(&self) {
+        for gate in &self.gates {
+            gate.cancelled.store(true, Ordering::Relaxed);
+            let _guard = gate.paused.lock().unwrap();
+            gate.resumed.notify_all();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.gates.iter().any(|g| *g.paused.lock().unwrap())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.gates
+            .iter()
+            .any(|g| g.cancelled.load(Ordering::Relaxed))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.gates.iter().all(|g| g.done.load(Ordering::Relaxed))
+    }
+
+    /// Entries found so far.
+    pub fn found(&self) -> u64 {
+        self.gates
+            .iter()
+            .map(|g| g.found.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Directories and entries that couldn't be read so far.
+    pub fn errors(&self) -> u64 {
+        self.gates
+            .iter()
+            .map(|g| g.errors.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// A snapshot of every worker thread's counters.
+    pub fn workers(&self) -> Vec<WorkerStats> {
+        let workers = self.gates.iter().flat_map(|g| &g.workers);
+        workers
+            .map(|c| WorkerStats {
+                listed: c.listed.load(Ordering::Relaxed),
+                steals: c.steals.load(Ordering::Relaxed),
+                stolen: c.stolen.load(Ordering::Relaxed),
+                queue: c.queue.load(Ordering::Relaxed),
+                idle: Duration::from_micros(c.idle_us.load(Ordering::Relaxed)),
+                finished: c.finished.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+impl Scan {
+    /// Blocks until the scan is finished, printing progress along the way.
+    pub fn wait(self) -> Tree {
+        let mut result = vec![];
+        for mut batch in self.rx.iter() {
+            let before = result.len();
+            result.append(&mut batch);
+            if before / 10_000 != result.len() / 10_000 {
+                println!(" indexed {}\x1b[F", commaify(result.len()));
+            }
+        }
+
+        let elapsed = self.started.elapsed();
+        println!(
+            "{} items indexed in {:.2?}",
+            commaify(result.len()),
+            elapsed
+        );
+        let errors = self.errors.load(Ordering::Relaxed);
+        if errors > 0 {
+            println!("{} entries couldn't be read", commaify(errors));
+        }
+        for path in self.timed_out.lock().unwrap().iter() {
+            println!("gave up on {} after it stopped responding", path.display());
+        }
+        Tree { data: result }
+    }
+
+    /// Blocks until the scan is finished, quietly.
+    pub fn collect(self) -> Tree {
+        Tree {
+            data: self.rx.iter().flatten().collect(),
+        }
+    }
+
+    /// Combines several scans into one that finishes once all of them have.
+    pub fn merge(scans: Vec<Scan>) -> Scan {
+        let (tx, rx) = mpsc::channel();
+        let errors = Arc::new(AtomicU64::new(0));
+        let timed_out = Arc::new(Mutex::new(vec![]));
+        let started = scans.iter().map(|s| s.started).min();
+        let control = Control {
+            gates: scans.iter().flat_map(|s| s.control.gates.clone()).collect(),
+        };
+        for scan in scans {
+            let (tx, errors, timed_out) = (tx.clone(), errors.clone(), timed_out.clone());
+            thread::spawn(move || {
+                for batch in scan.rx.iter() {
+                    let _ = tx.send(batch);
+                }
+                // workers count their errors before their last send.
+                errors.fetch_add(scan.errors.load(Ordering::Relaxed), Ordering::Relaxed);
+                let paths = scan.timed_out.lock().unwrap().clone();
+                timed_out.lock().unwrap().extend(paths);
+            });
+        }
+        Scan {
+            rx,
+            errors,
+            timed_out,
+            control,
+            started: started.unwrap_or_else(Instant::now),
+        }
+    }
+
+    /// A scan that's already done, of entries that came from somewhere else,
+    /// such as an export read back in.
+    pub fn of(entries: Vec<Info>) -> Scan {
+        let (tx, rx) = mpsc::channel();
+        let _ = tx.send(entries);
+        Scan {
+            rx,
+            errors: Arc::new(AtomicU64::new(0)),
+            timed_out: Arc::new(Mutex::new(vec![])),
+            control: Control { gates: vec![] },
+            started: Instant::now(),
+        }
+    }
+}
+
+/// What a worker is up to, shared with the watchdog.
+#[derive(Default)]
+struct Slot {
+    busy: Option<(PathBuf, Instant)>, // the directory being listed, and since when
+    batch: Vec<Info>,                 // entries not yet sent
+    abandoned: bool,                  // taking too long; its results will be dropped
+}
+
+fn info(entry: Entry, depth: usize) -> Info {
+    Info {
+        depth,
+        path: entry.path,
+        size: entry.size,
+        disk: entry.disk,
+        is_dir: entry.is_dir,
+        mtime: entry.mtime,
+        uid: entry.uid,
+    }
+}
+
+/// Walks `root` with a pool of work-stealing threads, listing directories
+/// through `source`.
+pub fn scan<S: ScanSource>(source: Arc<S>, root: &Path) -> Result<Scan> {
+    let now = Instant::now();
+
+    let num_threads = source.threads();
+    let workers: Vec<_> = (0..num_threads)
+        .map(|_| Worker::<(PathBuf, usize)>::new_lifo())
+        .collect();
+    let stealers: Vec<_> = workers.iter().map(|w| w.stealer()).collect();
+
+    // workers report to a forwarder rather than straight to `rx`, so that a
+    // worker stuck listing a directory can be given up on without the
+    // channel staying open forever.
+    let (tx, internal) = mpsc::channel::<Vec<Info>>();
+    let (out, rx) = mpsc::channel::<Vec<Info>>();
+    let root_entry = source
+        .root(root)
+        .map_err(|e| Error::Root(root.to_path_buf(), e))?;
+    // directories are queued with their depth, so their entries' depths
+    // don't need working out from their paths.
+    let depth = root.components().count();
+    if root_entry.is_dir {
+        workers[0].push((PathBuf::from(root), depth));
+    }
+    let _ = tx.send(vec![info(root_entry, depth)]);
+    let errors = Arc::new(AtomicU64::new(0));
+    let timed_out = Arc::new(Mutex::new(vec![]));
+    let gate = Arc::new(Gate {
+        paused: Mutex::new(false),
+        resumed: Condvar::new(),
+        cancelled: AtomicBool::new(false),
+        done: AtomicBool::new(false),
+        found: AtomicU64::new(1),
+        errors: errors.clone(),
+        workers: (0..num_threads).map(|_| Counters::default()).collect(),
+    });
+    let live = Arc::new(AtomicUsize::new(num_threads));
+    let slots: Arc<Vec<Mutex<Slot>>> =
+        Arc::new((0..num_threads).map(|_| Mutex::default()).collect());
+
+    for (i, worker) in workers.into_iter().enumerate() {
+        let tx = tx.clone();
+        let source = source.clone();
+        let errors = errors.clone();
+        let live = live.clone();
+        let slots = slots.clone();
+        let gate = gate.clone();
+        let mut stealers = stealers.clone();
+        stealers.remove(i); // remove our own stealer
+        stealers.rotate_right(i); // so no one stealer is swamped
+
+        thread::spawn(move || {
+            let slot = &slots[i];
+            let counters = &gate.workers[i];
+            let mut last_sent = Instant::now();
+            let mut idle_since = Instant::now();
+
+            while gate.proceed() {
+                let path = worker
+                    .pop() // try to take from local stack
+                    .or_else(|| {
+                        for s in &stealers {
+                            // loop until steal is not Steal::Retry. taking
+                            // half of another queue at once keeps workers
+                            // on very wide trees from stealing constantly.
+                            loop {
+                                counters.steals.fetch_add(1, Ordering::Relaxed);
+                                match s.steal_batch_and_pop(&worker) {
+                                    Steal::Success(path) => {
+                                        counters.stolen.fetch_add(1, Ordering::Relaxed);
+                                        return Some(path);
+                                    }
+                                    Steal::Empty => break,
+                                    Steal::Retry => {}
+                                }
+                            }
+                        }
+                        None // if all stealers are empty, then exit thread.
+                    });
+
+                let Some((path, depth)) = path else {
+                    break;
+                };
+
+                let idle = idle_since.elapsed().as_micros() as u64;
+                counters.idle_us.fetch_add(idle, Ordering::Relaxed);
+                slot.lock().unwrap().busy = Some((path.clone(), Instant::now()));
+                let listing = source.read_dir(&path);
+                idle_since = Instant::now();
+                counters.listed.fetch_add(1, Ordering::Relaxed);
+                let mut slot = slot.lock().unwrap();
+                slot.busy = None;
+                if slot.abandoned {
+                    return; // the watchdog has already handed over our batch.
+                }
+
+                // sometimes listing a directory fails with permission error or
+                // whatever, in which case we count it and move on.
+                match listing {
+                    Ok(entries) => {
+                        // counted once per directory rather than per entry,
+                        // which adds up in directories of millions of files.
+                        let (mut found, mut failed) = (0, 0);
+                        for entry in entries {
+                            let Ok(entry) = entry else {
+                                failed += 1;
+                                continue;
+                            };
+                            if entry.is_dir {
+                                worker.push((entry.path.clone(), depth + 1));
+                            }
+                            slot.batch.push(info(entry, depth + 1));
+                            found += 1;
+                        }
+                        gate.found.fetch_add(found, Ordering::Relaxed);
+                        errors.fetch_add(failed, Ordering::Relaxed);
+                    }
+                    Err(_) => {
+                        errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                counters.queue.store(worker.len(), Ordering::Relaxed);
+
+                // hand results over every now and then so progress can be shown.
+                if slot.batch.len() >= 4096 || last_sent.elapsed() > Duration::from_millis(100) {
+                    let _ = tx.send(mem::take(&mut slot.batch));
+                    drop(slot);
+                    thread::sleep(source.rest(last_sent.elapsed()));
+                    last_sent = Instant::now();
+                }
+            }
+            let _ = tx.send(mem::take(&mut slot.lock().unwrap().batch));
+            let idle = idle_since.elapsed().as_micros() as u64;
+            counters.idle_us.fetch_add(idle, Ordering::Relaxed);
+            counters.queue.store(0, Ordering::Relaxed);
+            counters.finished.store(true, Ordering::Relaxed);
+            live.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    if let Some(timeout) = source.timeout() {
+        let (tx, errors, live) = (tx.clone(), errors.clone(), live.clone());
+        let timed_out = timed_out.clone();
+        thread::spawn(move || {
+            // a directory stuck in the kernel (a hung NFS server, a dead FUSE
+            // daemon) can't be interrupted, so its worker is left behind and
+            // the rest of the scan carries on without that subtree.
+            let period = (timeout / 4).min(Duration::from_secs(1));
+            while live.load(Ordering::Relaxed) > 0 {
+                thread::sleep(period);
+                for slot in slots.iter() {
+                    let mut slot = slot.lock().unwrap();
+                    let Some((path, since)) = &slot.busy else {
+                        continue;
+                    };
+                    if slot.abandoned || since.elapsed() < timeout {
+                        continue;
+                    }
+                    timed_out.lock().unwrap().push(path.clone());
+                    errors.fetch_add(1, Ordering::Relaxed);
+                    slot.abandoned = true;
+                    let _ = tx.send(mem::take(&mut slot.batch));
+                    live.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let control = Control {
+        gates: vec![gate.clone()],
+    };
+    thread::spawn(move || loop {
+        match internal.recv_timeout(Duration::from_millis(10)) {
+            Ok(batch) => {
+                let _ = out.send(batch);
+            }
+            // once every worker has finished or been given up on, anything
+            // they sent is already queued.
+            Err(RecvTimeoutError::Timeout) if live.load(Ordering::Relaxed) == 0 => {
+                for batch in internal.try_iter() {
+                    let _ = out.send(batch);
+                }
+                gate.done.store(true, Ordering::Relaxed);
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                gate.done.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+    });
+
+    Ok(Scan {
+        rx,
+        errors,
+        timed_out,
+        control,
+        started: now,
+    })
+}
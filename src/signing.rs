@@ -0,0 +1,82 @@
+//! Signing exported scans with a machine's SSH key, so reports gathered
+//! from many servers can be checked and attributed where they're collected.
+//! The signing itself is left to ssh-keygen, which every server has.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What signatures are made for, so one made for anything else, like a git
+/// commit, can't be passed off as a signed scan.
+const NAMESPACE: &str = "adansonia";
+
+/// Where the signature for `file` goes, next to it.
+pub fn signature(file: &Path) -> PathBuf {
+    let mut sig = file.as_os_str().to_owned();
+    sig.push(".sig");
+    PathBuf::from(sig)
+}
+
+/// Signs `file` with the private key `key` (e.g. the host's
+/// /etc/ssh/ssh_host_ed25519_key), writing the signature beside it.
+pub fn sign(file: &Path, key: &Path) -> io::Result<PathBuf> {
+    // ssh-keygen won't overwrite an old signature.
+    let sig = signature(file);
+    match std::fs::remove_file(&sig) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    run(Command::new("ssh-keygen")
+        .args(["-q", "-Y", "sign", "-n", NAMESPACE, "-f"])
+        .arg(key)
+        .arg(file)
+        .stdin(Stdio::null()))?;
+    Ok(sig)
+}
+
+/// Checks the signature beside `file` against the keys in `allowed`, an
+/// allowed_signers file as ssh-keygen(1) describes, giving the name of the
+/// signer it was made by.
+pub fn verify(file: &Path, allowed: &Path) -> io::Result<String> {
+    let sig = signature(file);
+    if !sig.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} isn't signed, there's no {}",
+                file.display(),
+                sig.display()
+            ),
+        ));
+    }
+    let found = run(Command::new("ssh-keygen")
+        .args(["-Y", "find-principals", "-f"])
+        .arg(allowed)
+        .arg("-s")
+        .arg(&sig))
+    .map_err(|_| io::Error::other("it wasn't signed by any of the allowed signers"))?;
+    let signer = found.lines().next().unwrap_or_default().to_string();
+    run(Command::new("ssh-keygen")
+        .args(["-Y", "verify", "-n", NAMESPACE, "-I", &signer, "-f"])
+        .arg(allowed)
+        .arg("-s")
+        .arg(&sig)
+        .stdin(File::open(file)?))
+    .map_err(|_| io::Error::other(format!("it was changed after {signer} signed it")))?;
+    Ok(signer)
+}
+
+/// Runs ssh-keygen, giving what it prints or the first thing it complained about.
+fn run(command: &mut Command) -> io::Result<String> {
+    let output = command.output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::other("this needs ssh-keygen installed"),
+        _ => e,
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let why = stderr.lines().next().unwrap_or("ssh-keygen failed");
+        return Err(io::Error::other(why.to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,243 @@
+use crate::tree::{Info, Tree};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::{io, mem};
+
+/// Keeps a tree to roughly a given number of entries in memory by moving the
+/// contents of directories that aren't being looked at into a temporary file,
+/// and reading them back when they're visited.
+///
+/// Spilled directories stay in the tree with their full size, so listings of
+/// their parents are unaffected. Space in the file isn't reused; a subtree that
+/// is paged in and later spilled again is simply appended.
+pub struct Spill {
+    file: File,
+    end: u64,
+    limit: usize,
+    spilled: BTreeMap<PathBuf, (u64, usize, bool)>, // offset, length and `swapped` of each subtree
+    swapped: bool, // whether the tree's sizes have been swapped an odd number of times
+}
+
+impl Spill {
+    pub fn new(limit: usize) -> io::Result<Spill> {
+        // named for the user too, since the temporary directory is shared and
+        // process ids are only unique within a pid namespace. readable by us
+        // alone, as it holds the names of everything scanned.
+        let uid = unsafe { libc::geteuid() };
+        let name = format!("adansonia-spill-{uid}-{}", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+        // unlinked straight away, so it disappears however we exit.
+        fs::remove_file(&path)?;
+        Ok(Spill {
+            file,
+            end: 0,
+            limit,
+            spilled: BTreeMap::new(),
+            swapped: false,
+        })
+    }
+
+    /// Number of directories whose contents are currently on disk.
+    pub fn len(&self) -> usize {
+        self.spilled.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spilled.is_empty()
+    }
+
+    /// Notes that the tree's sizes were swapped with `Tree::swap_sizes`, so
+    /// subtrees spilled before are swapped too as they're read back.
+    pub fn swap_sizes(&mut self) {
+        self.swapped = !self.swapped;
+    }
+
+    /// Moves subtrees to disk, biggest first, until the tree is within the
+    /// limit. Nothing on the way to `hot` is spilled.
+    pub fn shrink(&mut self, tree: &mut Tree, hot: &Path) -> io::Result<()> {
+        if tree.data.len() <= self.limit {
+            return Ok(());
+        }
+        // small enough pieces that we don't spill far more than needed.
+        let chunk = (self.limit / 8).max(2);
+        let mut units = vec![];
+        let mut stack: Vec<PathBuf> = tree
+            .data
+            .first()
+            .map(|x| x.path.clone())
+            .into_iter()
+            .collect();
+        while let Some(dir) = stack.pop() {
+            for child in tree.children(&dir).filter(|x| x.is_dir) {
+                let n = tree.subtree(&child.path).len();
+                let splittable = n > chunk && tree.children(&child.path).any(|x| x.is_dir);
+                if hot.starts_with(&child.path) || splittable {
+                    stack.push(child.path.clone());
+                } else if n > 1 {
+                    units.push((n, child.path.clone()));
+                }
+            }
+        }
+        units.sort_by_key(|&(n, _)| Reverse(n));
+
+        for (_, dir) in units {
+            if tree.data.len() <= self.limit {
+                break;
+            }
+            let bytes = encode(&tree.detach(&dir));
+            self.file.write_all_at(&bytes, self.end)?;
+            self.spilled
+                .insert(dir, (self.end, bytes.len(), self.swapped));
+            self.end += bytes.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Reads back every spilled subtree on the way to `p`, so that it and its
+    /// children are in the tree again. Returns whether anything was read.
+    pub fn page_in(&mut self, tree: &mut Tree, p: &Path) -> io::Result<bool> {
+        let mut found = false;
+        // outermost first: a subtree may have been spilled from inside one
+        // that was spilled later.
+        let ancestors: Vec<&Path> = p.ancestors().collect();
+        for dir in ancestors.into_iter().rev() {
+            let Some(&(offset, len, swapped)) = self.spilled.get(dir) else {
+                continue;
+            };
+            let mut bytes = vec![0; len];
+            self.file.read_exact_at(&mut bytes, offset)?;
+            let mut entries = decode(&bytes);
+            if swapped != self.swapped {
+                for x in &mut entries {
+                    mem::swap(&mut x.size, &mut x.disk);
+                }
+            }
+            tree.attach(dir, entries);
+            self.spilled.remove(dir);
+            found = true;
+        }
+        Ok(found)
+    }
+}
+
+// each entry is its path length (u32), the path, its size (u64), its disk
+// usage (u64), its mtime (i64), its owner (u32) and whether it's a
+// directory, all little-endian.
+fn encode(entries: &[Info]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for x in entries {
+        let path = x.path.as_os_str().as_bytes();
+        bytes.extend((path.len() as u32).to_le_bytes());
+        bytes.extend(path);
+        bytes.extend(x.size.to_le_bytes());
+        bytes.extend(x.disk.to_le_bytes());
+        bytes.extend(x.mtime.to_le_bytes());
+        bytes.extend(x.uid.to_le_bytes());
+        bytes.push(x.is_dir as u8);
+    }
+    bytes
+}
+
+fn decode(mut bytes: &[u8]) -> Vec<Info> {
+    let mut entries = vec![];
+    while !bytes.is_empty() {
+        let (len, rest) = bytes.split_at(4);
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        let (path, rest) = rest.split_at(len);
+        let (size, rest) = rest.split_at(8);
+        let (disk, rest) = rest.split_at(8);
+        let (mtime, rest) = rest.split_at(8);
+        let (uid, rest) = rest.split_at(4);
+        let path = PathBuf::from(OsStr::from_bytes(path));
+        entries.push(Info {
+            depth: path.components().count(),
+            path,
+            size: u64::from_le_bytes(size.try_into().unwrap()),
+            disk: u64::from_le_bytes(disk.try_into().unwrap()),
+            is_dir: rest[0] != 0,
+            mtime: i64::from_le_bytes(mtime.try_into().unwrap()),
+            uid: u32::from_le_bytes(uid.try_into().unwrap()),
+        });
+        bytes = &rest[1..];
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Tree {
+        let mut data = vec![];
+        for dir in ["/r", "/r/a", "/r/a/x", "/r/b", "/r/c"] {
+            data.push((dir.to_string(), true));
+        }
+        for i in 0..20 {
+            data.push((format!("/r/a/x/{i}.f"), false));
+            data.push((format!("/r/b/{i}.f"), false));
+            data.push((format!("/r/c/{i}.f"), false));
+        }
+        let mut tree = Tree {
+            data: data
+                .into_iter()
+                .map(|(p, is_dir)| Info {
+                    depth: Path::new(&p).components().count(),
+                    path: PathBuf::from(p),
+                    size: if is_dir { 0 } else { 10 },
+                    disk: if is_dir { 0 } else { 4096 },
+                    is_dir,
+                    mtime: 0,
+                    uid: 0,
+                })
+                .collect(),
+        };
+        tree.preprocess();
+        tree
+    }
+
+    #[test]
+    fn shrink_then_page_in_round_trips() {
+        let original = tree();
+        let mut tree = tree();
+        let mut spill = Spill::new(30).unwrap();
+        spill.shrink(&mut tree, Path::new("/r/c")).unwrap();
+
+        assert!(tree.data.len() <= 30);
+        assert!(!spill.is_empty());
+        // sizes are untouched and the hot directory is still browsable.
+        for x in &tree.data {
+            assert_eq!(x.size, original.size(&x.path), "{:?}", x.path);
+        }
+        assert_eq!(tree.children(Path::new("/r/c")).count(), 20);
+
+        spill.page_in(&mut tree, Path::new("/r/a/x")).unwrap();
+        spill.page_in(&mut tree, Path::new("/r/b")).unwrap();
+        assert!(spill.is_empty());
+        let paths = |t: &Tree| t.data.iter().map(|x| x.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&tree), paths(&original));
+    }
+
+    #[test]
+    fn subtrees_spilled_before_a_swap_come_back_swapped() {
+        let mut tree = tree();
+        let mut spill = Spill::new(30).unwrap();
+        spill.shrink(&mut tree, Path::new("/r/c")).unwrap();
+        tree.swap_sizes();
+        spill.swap_sizes();
+
+        spill.page_in(&mut tree, Path::new("/r/b")).unwrap();
+        let file = tree.find(Path::new("/r/b/0.f")).unwrap();
+        assert_eq!((file.size, file.disk), (4096, 10));
+    }
+}
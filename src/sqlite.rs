@@ -0,0 +1,536 @@
+//! A minimal SQLite writer for exporting scans, so that writing a database
+//! doesn't need SQLite: the tables and their indexes are each a b-tree of
+//! rows already in order, built from the leaves up. See
+//! https://www.sqlite.org/fileformat.html for the layout. Querying one
+//! still takes the sqlite3 command.
+
+use crate::output;
+use crate::tree::Info;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const PAGE: usize = 4096;
+// the most of a payload kept on a table leaf or an index page, the rest
+// going to overflow pages.
+const TABLE_MAX: usize = PAGE - 35;
+const INDEX_MAX: usize = (PAGE - 12) * 64 / 255 - 23;
+// written into the header as the version that last wrote the file.
+const SQLITE_VERSION: u32 = 3_045_000;
+
+// b-tree page types.
+const INDEX_INTERIOR: u8 = 2;
+const TABLE_INTERIOR: u8 = 5;
+const INDEX_LEAF: u8 = 10;
+const TABLE_LEAF: u8 = 13;
+
+const SCAN: &str = "CREATE TABLE scan (root TEXT NOT NULL, scanned INTEGER NOT NULL)";
+const ENTRIES: &str = "CREATE TABLE entries (
+  path TEXT PRIMARY KEY,
+  parent TEXT,
+  name TEXT NOT NULL,
+  is_dir INTEGER NOT NULL,
+  size INTEGER NOT NULL,
+  mtime INTEGER NOT NULL,
+  owner TEXT NOT NULL,
+  depth INTEGER NOT NULL
+)";
+const PARENT: &str = "CREATE INDEX entries_parent ON entries (parent)";
+
+enum Value<'a> {
+    Null,
+    Int(i64),
+    Text(&'a str),
+}
+
+/// Writes `entries`, scanned from `root` at `scanned`, as a SQLite database
+/// with an `entries` table and a `scan` table recording where and when.
+/// Directory sizes include everything below them, so `parent` is all
+/// that's needed to query the tree:
+///
+/// ```sql
+/// SELECT name, size FROM entries WHERE parent = '/home' ORDER BY size DESC;
+/// ```
+pub fn write(
+    out: &mut (impl Write + Seek),
+    root: &Path,
+    entries: &[Info],
+    scanned: i64,
+    owner: impl Fn(u32) -> String,
+) -> io::Result<()> {
+    let mut db = Pages { out, next: 1 };
+    db.write(&[0; PAGE])?; // the schema, once the other tables' roots are known
+    let scan = record(&[Value::Text(&root.to_string_lossy()), Value::Int(scanned)]);
+    let scan = db.table([scan].into_iter())?;
+
+    let paths: Vec<String> = entries
+        .iter()
+        .map(|x| x.path.to_string_lossy().into_owned())
+        .collect();
+    let parents: Vec<Option<String>> = entries
+        .iter()
+        .map(|x| match x.path.parent() {
+            Some(p) if x.path != root => Some(p.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let rows = entries.iter().enumerate().map(|(i, x)| {
+        record(&[
+            Value::Text(&paths[i]),
+            parents[i].as_deref().map_or(Value::Null, Value::Text),
+            Value::Text(&output::name(x)),
+            Value::Int(x.is_dir as i64),
+            Value::Int(x.size as i64),
+            Value::Int(x.mtime),
+            Value::Text(&owner(x.uid)),
+            Value::Int(x.depth as i64),
+        ])
+    });
+    let table = db.table(rows)?;
+
+    // indexes hold their column and the rowid, in order of both. text is
+    // compared bytewise, with NULL first.
+    let mut by_path: Vec<usize> = (0..entries.len()).collect();
+    by_path.sort_by(|&a, &b| paths[a].as_bytes().cmp(paths[b].as_bytes()));
+    let keys = by_path
+        .iter()
+        .map(|&i| record(&[Value::Text(&paths[i]), Value::Int(i as i64 + 1)]));
+    let primary = db.index(keys)?;
+    let mut by_parent: Vec<usize> = (0..entries.len()).collect();
+    by_parent.sort_by(|&a, &b| {
+        let parent = |i: usize| parents[i].as_ref().map(|p| p.as_bytes());
+        parent(a).cmp(&parent(b)).then(a.cmp(&b))
+    });
+    let keys = by_parent.iter().map(|&i| {
+        let parent = parents[i].as_deref().map_or(Value::Null, Value::Text);
+        record(&[parent, Value::Int(i as i64 + 1)])
+    });
+    let parent = db.index(keys)?;
+
+    let schema = [
+        ("table", "scan", "scan", scan, Some(SCAN)),
+        ("table", "entries", "entries", table, Some(ENTRIES)),
+        (
+            "index",
+            "sqlite_autoindex_entries_1",
+            "entries",
+            primary,
+            None,
+        ),
+        ("index", "entries_parent", "entries", parent, Some(PARENT)),
+    ];
+    let mut cells = vec![];
+    for (rowid, (kind, name, table, root, sql)) in schema.into_iter().enumerate() {
+        let record = record(&[
+            Value::Text(kind),
+            Value::Text(name),
+            Value::Text(table),
+            Value::Int(root as i64),
+            sql.map_or(Value::Null, Value::Text),
+        ]);
+        let mut cell = vec![];
+        varint(&mut cell, record.len() as u64);
+        varint(&mut cell, rowid as u64 + 1);
+        cell.extend(record);
+        cells.push(cell);
+    }
+    let mut first = page(TABLE_LEAF, &cells, None, 100);
+    first[..100].copy_from_slice(&header(db.next - 1));
+    db.out.seek(SeekFrom::Start(0))?;
+    db.out.write_all(&first)?;
+    db.out.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
+/// The database header, which starts the first page.
+fn header(pages: u32) -> [u8; 100] {
+    let mut h = [0; 100];
+    h[..16].copy_from_slice(b"SQLite format 3\0");
+    h[16..18].copy_from_slice(&(PAGE as u16).to_be_bytes());
+    h[18] = 1; // rollback journal for writing
+    h[19] = 1; // and for reading
+    h[21] = 64; // payload fractions, which must be these
+    h[22] = 32;
+    h[23] = 32;
+    h[24..28].copy_from_slice(&1u32.to_be_bytes()); // change counter
+    h[28..32].copy_from_slice(&pages.to_be_bytes());
+    h[40..44].copy_from_slice(&1u32.to_be_bytes()); // schema cookie
+    h[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    h[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    h[92..96].copy_from_slice(&1u32.to_be_bytes()); // the page count is as of change 1
+    h[96..100].copy_from_slice(&SQLITE_VERSION.to_be_bytes());
+    h
+}
+
+/// Pages written one after another, numbered from 1.
+struct Pages<'a, W> {
+    out: &'a mut W,
+    next: u32,
+}
+
+impl<W: Write> Pages<'_, W> {
+    fn write(&mut self, page: &[u8]) -> io::Result<u32> {
+        self.out.write_all(page)?;
+        self.next += 1;
+        Ok(self.next - 1)
+    }
+
+    /// The part of `payload` that goes in a cell, with what doesn't fit in
+    /// `max` bytes written to overflow pages.
+    fn payload(&mut self, payload: &[u8], max: usize) -> io::Result<Vec<u8>> {
+        let kept = local(payload.len(), max);
+        let mut cell = payload[..kept].to_vec();
+        if kept < payload.len() {
+            cell.extend(self.next.to_be_bytes());
+            let chunks: Vec<&[u8]> = payload[kept..].chunks(PAGE - 4).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let mut page = vec![0; PAGE];
+                if i + 1 < chunks.len() {
+                    page[..4].copy_from_slice(&(self.next + 1).to_be_bytes());
+                }
+                page[4..4 + chunk.len()].copy_from_slice(chunk);
+                self.write(&page)?;
+            }
+        }
+        Ok(cell)
+    }
+
+    /// Writes a table of `rows`, with rowids from 1, returning its root.
+    fn table(&mut self, rows: impl Iterator<Item = Vec<u8>>) -> io::Result<u32> {
+        let (mut children, mut keys) = (vec![], vec![]);
+        let mut cells = vec![];
+        let mut rowid = 0;
+        for row in rows {
+            rowid += 1;
+            let mut cell = vec![];
+            varint(&mut cell, row.len() as u64);
+            varint(&mut cell, rowid);
+            cell.extend(self.payload(&row, TABLE_MAX)?);
+            if !fits(&cells, &cell, 8) {
+                children.push(self.write(&page(TABLE_LEAF, &cells, None, 0))?);
+                // the last rowid to the left.
+                let mut key = vec![];
+                varint(&mut key, rowid - 1);
+                keys.push(key);
+                cells.clear();
+            }
+            cells.push(cell);
+        }
+        children.push(self.write(&page(TABLE_LEAF, &cells, None, 0))?);
+        self.interior(TABLE_INTERIOR, children, keys)
+    }
+
+    /// Writes an index of `keys`, given in order, returning its root.
+    fn index(&mut self, keys: impl ExactSizeIterator<Item = Vec<u8>>) -> io::Result<u32> {
+        let (mut children, mut between) = (vec![], vec![]);
+        let mut cells = vec![];
+        let n = keys.len();
+        for (i, key) in keys.enumerate() {
+            let mut cell = vec![];
+            varint(&mut cell, key.len() as u64);
+            cell.extend(self.payload(&key, INDEX_MAX)?);
+            if fits(&cells, &cell, 8) {
+                cells.push(cell);
+                continue;
+            }
+            // unlike in a table, the key between two pages is in neither.
+            // the last one can't be, or the page after would be empty.
+            let (up, next) = match i + 1 == n {
+                true => (cells.pop().unwrap(), vec![cell]),
+                false => (cell, vec![]),
+            };
+            children.push(self.write(&page(INDEX_LEAF, &cells, None, 0))?);
+            between.push(up);
+            cells = next;
+        }
+        children.push(self.write(&page(INDEX_LEAF, &cells, None, 0))?);
+        self.interior(INDEX_INTERIOR, children, between)
+    }
+
+    /// Writes the interior pages above `children`, the pages of a level with
+    /// `keys` between each one and the next, returning the root.
+    fn interior(
+        &mut self,
+        kind: u8,
+        mut children: Vec<u32>,
+        mut keys: Vec<Vec<u8>>,
+    ) -> io::Result<u32> {
+        while children.len() > 1 {
+            let (mut up, mut between) = (vec![], vec![]);
+            let mut cells = vec![];
+            let mut i = 0;
+            while i < keys.len() {
+                let cell = [&children[i].to_be_bytes()[..], &keys[i]].concat();
+                if fits(&cells, &cell, 12) {
+                    cells.push(cell);
+                    i += 1;
+                    continue;
+                }
+                // the child that doesn't fit goes to the right of the rest,
+                // with the key after it a level up, unless that's the last
+                // key, which the next page needs.
+                if i + 1 == keys.len() {
+                    cells.pop();
+                    i -= 1;
+                }
+                up.push(self.write(&page(kind, &cells, Some(children[i]), 0))?);
+                between.push(keys[i].clone());
+                cells.clear();
+                i += 1;
+            }
+            up.push(self.write(&page(kind, &cells, Some(children[i]), 0))?);
+            (children, keys) = (up, between);
+        }
+        Ok(children[0])
+    }
+}
+
+/// Whether `cell` fits on a page with `cells` after a page header of
+/// `header` bytes.
+fn fits(cells: &[Vec<u8>], cell: &[u8], header: usize) -> bool {
+    let used: usize = cells.iter().map(|c| c.len() + 2).sum();
+    header + used + cell.len() + 2 <= PAGE
+}
+
+/// Lays out a b-tree page of `kind` holding `cells`, in order, with
+/// `right` the child after them on an interior page. Its header starts at
+/// `start`, after the database header on the first page.
+fn page(kind: u8, cells: &[Vec<u8>], right: Option<u32>, start: usize) -> Vec<u8> {
+    let mut page = vec![0; PAGE];
+    page[start] = kind;
+    page[start + 3..start + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    let mut pointers = start + 8;
+    if let Some(right) = right {
+        page[start + 8..start + 12].copy_from_slice(&right.to_be_bytes());
+        pointers += 4;
+    }
+    // cells fill the page from the end.
+    let mut content = PAGE;
+    for (i, cell) in cells.iter().enumerate() {
+        content -= cell.len();
+        page[content..content + cell.len()].copy_from_slice(cell);
+        page[pointers + 2 * i..pointers + 2 * i + 2]
+            .copy_from_slice(&(content as u16).to_be_bytes());
+    }
+    page[start + 5..start + 7].copy_from_slice(&(content as u16).to_be_bytes());
+    page
+}
+
+/// How much of a payload of `size` bytes is kept in its cell, when at most
+/// `max` can be.
+fn local(size: usize, max: usize) -> usize {
+    let min = (PAGE - 12) * 32 / 255 - 23;
+    let k = min + size.saturating_sub(min) % (PAGE - 4);
+    match size {
+        _ if size <= max => size,
+        _ if k <= max => k,
+        _ => min,
+    }
+}
+
+/// A record: a header of the values' types, then the values.
+fn record(values: &[Value]) -> Vec<u8> {
+    let (mut types, mut body): (Vec<u8>, Vec<u8>) = (vec![], vec![]);
+    for value in values {
+        match value {
+            Value::Null => types.push(0),
+            Value::Int(0) => types.push(8),
+            Value::Int(1) => types.push(9),
+            &Value::Int(n) => {
+                let len = [1, 2, 3, 4, 6, 8]
+                    .into_iter()
+                    .find(|&len| {
+                        len == 8 || (-(1 << (8 * len - 1))..1 << (8 * len - 1)).contains(&n)
+                    })
+                    .unwrap();
+                types.push(match len {
+                    6 => 5,
+                    8 => 6,
+                    len => len as u8,
+                });
+                body.extend(&n.to_be_bytes()[8 - len..]);
+            }
+            Value::Text(s) => {
+                varint(&mut types, 13 + 2 * s.len() as u64);
+                body.extend(s.as_bytes());
+            }
+        }
+    }
+    // the header's size counts itself.
+    let mut size = types.len() + 1;
+    while size - types.len() < varint_len(size as u64) {
+        size += 1;
+    }
+    let mut record = vec![];
+    varint(&mut record, size as u64);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+/// SQLite's variable-length integers: big-endian, seven bits a byte with
+/// the top bit saying more follow, and a ninth byte, if needed, of eight.
+fn varint(out: &mut Vec<u8>, v: u64) {
+    if v >> 56 != 0 {
+        let mut bytes = [0; 9];
+        bytes[8] = v as u8;
+        for (i, byte) in bytes[..8].iter_mut().enumerate() {
+            *byte = 0x80 | (v >> (8 + 7 * (7 - i))) as u8 & 0x7f;
+        }
+        out.extend(bytes);
+        return;
+    }
+    let len = varint_len(v);
+    for i in (0..len).rev() {
+        let more = if i > 0 { 0x80 } else { 0 };
+        out.push(more | (v >> (7 * i)) as u8 & 0x7f);
+    }
+}
+
+fn varint_len(v: u64) -> usize {
+    match v >> 56 {
+        0 => (64 - v.leading_zeros() as usize).div_ceil(7).max(1),
+        _ => 9,
+    }
+}
+
+/// Runs `sql` against `db`, printing the results as a table. This takes the
+/// sqlite3 command, which is looked for first so its absence isn't taken
+/// for a failed query.
+pub fn query(db: &Path, sql: &str) -> io::Result<()> {
+    let found = Command::new("sqlite3")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if found.is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "query runs the sqlite3 command, which isn't installed; install it, e.g. the sqlite3 package",
+        ));
+    }
+    if !db.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no database {}", db.display()),
+        ));
+    }
+    let status = Command::new("sqlite3")
+        .args(["-header", "-column", "-readonly"])
+        .arg(db)
+        .arg(sql)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other("the query failed"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn info(path: &str, size: u64, is_dir: bool) -> Info {
+        Info {
+            depth: Path::new(path).components().count(),
+            path: PathBuf::from(path),
+            size,
+            disk: 0,
+            is_dir,
+            mtime: 1_700_000_000,
+            uid: 1000,
+        }
+    }
+
+    #[test]
+    fn encodes_varints() {
+        let encoded = |v| {
+            let mut out = vec![];
+            varint(&mut out, v);
+            assert_eq!(out.len(), varint_len(v));
+            out
+        };
+        assert_eq!(encoded(0), [0]);
+        assert_eq!(encoded(127), [0x7f]);
+        assert_eq!(encoded(128), [0x81, 0]);
+        assert_eq!(encoded(16383), [0xff, 0x7f]);
+        assert_eq!(encoded(u64::MAX), [0xff; 9]);
+    }
+
+    #[test]
+    fn lays_out_the_header_and_schema() {
+        let entries = [info("/r", 5, true), info("/r/a", 5, false)];
+        let mut out = io::Cursor::new(vec![]);
+        write(&mut out, Path::new("/r"), &entries, 100, |uid| {
+            format!("u{uid}")
+        })
+        .unwrap();
+        let out = out.into_inner();
+
+        assert!(out.starts_with(b"SQLite format 3\0"));
+        assert_eq!(out.len() % PAGE, 0);
+        let pages = u32::from_be_bytes(out[28..32].try_into().unwrap());
+        assert_eq!(pages as usize, out.len() / PAGE);
+        // the schema's leaf, after the header, with a cell for each table
+        // and index.
+        assert_eq!(out[100], TABLE_LEAF);
+        assert_eq!(u16::from_be_bytes([out[103], out[104]]), 4);
+        assert!(out.windows(ENTRIES.len()).any(|w| w == ENTRIES.as_bytes()));
+    }
+
+    // sqlite3 checks the file is sound and reads it back, if it's here.
+    #[test]
+    fn sqlite3_reads_what_was_written() {
+        if Command::new("sqlite3").arg("-version").output().is_err() {
+            eprintln!("skipping: sqlite3 isn't installed");
+            return;
+        }
+        // enough for interior pages, and paths long enough to overflow.
+        let mut entries = vec![info("/r", 0, true)];
+        for i in 0..300 {
+            entries.push(info(&format!("/r/d{i:03}"), 0, true));
+            for j in 0..20 {
+                let long = if j == 0 {
+                    "x".repeat(5000 + i)
+                } else {
+                    String::new()
+                };
+                entries.push(info(&format!("/r/d{i:03}/f{j:02}{long}"), j, false));
+            }
+        }
+        let mut tree = crate::tree::Tree { data: entries };
+        tree.preprocess();
+        let path = std::env::temp_dir().join(format!("adansonia-sqlite-{}.db", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        write(&mut file, Path::new("/r"), &tree.data, 100, |uid| {
+            format!("u{uid}")
+        })
+        .unwrap();
+        drop(file);
+
+        let sql = "PRAGMA integrity_check;
+            SELECT count(*), sum(size) FROM entries;
+            SELECT root, scanned FROM scan;
+            SELECT name, size, owner FROM entries WHERE parent = '/r/d123' ORDER BY size DESC LIMIT 1;
+            SELECT count(*) FROM entries WHERE parent IS NULL;
+            SELECT length(path) FROM entries WHERE path > '/r/d299/f00' ORDER BY path LIMIT 1;";
+        let output = Command::new("sqlite3")
+            .arg(&path)
+            .arg(sql)
+            .output()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let got = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(
+            got,
+            format!(
+                "ok\n6301|{}\n/r|100\nf19|19|u1000\n1\n{}\n",
+                tree.data.iter().map(|x| x.size).sum::<u64>(),
+                "/r/d299/f00".len() + 5299
+            ),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
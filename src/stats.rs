@@ -0,0 +1,134 @@
+use crate::civil_from_days;
+use crate::tree::Info;
+use std::collections::BTreeMap;
+
+/// Upper bounds of the file size classes: powers of 16 from 16 bytes to a
+/// tebibyte. A last class holds everything bigger.
+pub const SIZE_CLASSES: [u64; 10] = [
+    1 << 4,
+    1 << 8,
+    1 << 12,
+    1 << 16,
+    1 << 20,
+    1 << 24,
+    1 << 28,
+    1 << 32,
+    1 << 36,
+    1 << 40,
+];
+
+/// Upper bounds of the fan-out classes, by number of direct children.
+pub const FAN_OUT_CLASSES: [u64; 6] = [1, 10, 100, 1_000, 10_000, 100_000];
+
+/// The shape of a subtree.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// Files at each depth below the root of the subtree, starting at 1.
+    pub depth: Vec<u64>,
+    /// Files and their total size in each of SIZE_CLASSES, plus one more.
+    pub files: Vec<u64>,
+    pub bytes: Vec<u64>,
+    /// Directories in each of FAN_OUT_CLASSES, plus one more.
+    pub fan_out: Vec<u64>,
+    /// Bytes of files last modified in each year, oldest first.
+    pub years: Vec<(i64, u64)>,
+}
+
+fn class(bounds: &[u64], n: u64) -> usize {
+    bounds.partition_point(|&b| b <= n)
+}
+
+impl Stats {
+    /// Expects `subtree` as returned by Tree::subtree(): a directory followed
+    /// by its descendants in path order.
+    pub fn new(subtree: &[Info]) -> Stats {
+        let mut stats = Stats {
+            depth: vec![],
+            files: vec![0; SIZE_CLASSES.len() + 1],
+            bytes: vec![0; SIZE_CLASSES.len() + 1],
+            fan_out: vec![0; FAN_OUT_CLASSES.len() + 1],
+            years: vec![],
+        };
+        let Some(base) = subtree.first().map(|x| x.depth) else {
+            return stats;
+        };
+
+        // directories still open on the way down to the current entry, with
+        // how many children they've had so far.
+        let mut open: Vec<(usize, u64)> = vec![];
+        let mut years = BTreeMap::new();
+        for x in subtree {
+            while open.last().is_some_and(|&(depth, _)| depth >= x.depth) {
+                let (_, children) = open.pop().unwrap();
+                stats.fan_out[class(&FAN_OUT_CLASSES, children)] += 1;
+            }
+            if let Some((_, children)) = open.last_mut() {
+                *children += 1;
+            }
+            if x.is_dir {
+                open.push((x.depth, 0));
+                continue;
+            }
+            let depth = x.depth - base;
+            if stats.depth.len() < depth {
+                stats.depth.resize(depth, 0);
+            }
+            if depth > 0 {
+                stats.depth[depth - 1] += 1;
+            }
+            let size_class = class(&SIZE_CLASSES, x.size);
+            stats.files[size_class] += 1;
+            stats.bytes[size_class] += x.size;
+            let (year, _, _) = civil_from_days(x.mtime.div_euclid(86400));
+            *years.entry(year).or_default() += x.size;
+        }
+        for (_, children) in open {
+            stats.fan_out[class(&FAN_OUT_CLASSES, children)] += 1;
+        }
+        stats.years = years.into_iter().collect();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn info(path: &str, size: u64, is_dir: bool, mtime: i64) -> Info {
+        let path = PathBuf::from(path);
+        Info {
+            depth: path.components().count(),
+            path,
+            size,
+            disk: 0,
+            is_dir,
+            mtime,
+            uid: 0,
+        }
+    }
+
+    #[test]
+    fn counts_depths_sizes_and_fan_out() {
+        let subtree = [
+            info("/r", 0, true, 0),
+            info("/r/a", 0, true, 0),
+            info("/r/a/1", 5, false, 0),
+            info("/r/a/2", 20, false, 1_000_000_000),
+            info("/r/a/3", 300, false, -1),
+            info("/r/e", 0, true, 0),
+            info("/r/z", 1 << 41, false, 1_000_000_000),
+        ];
+        let stats = Stats::new(&subtree);
+        assert_eq!(stats.depth, [1, 3]);
+        assert_eq!(stats.files[..3], [1, 1, 1]);
+        assert_eq!(stats.files[SIZE_CLASSES.len()], 1);
+        assert_eq!(stats.bytes[1], 20);
+        // /r/e is empty, /r/a and /r have 3 children each.
+        assert_eq!(stats.fan_out[..2], [1, 2]);
+        assert_eq!(
+            stats.years,
+            [(1969, 300), (1970, 5), (2001, 20 + (1 << 41))]
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Compact summaries of a scan for `adansonia push`, to feed dashboards
+//! that collect from a whole fleet.
+//!
+//! A summary is sent as one JSON object:
+//!
+//! ```json
+//! {
+//!   "schema": 1,
+//!   "host": "web1",
+//!   "root": "/srv",
+//!   "scanned": 1760486400,
+//!   "total": 52428800,
+//!   "files": 1200,
+//!   "dirs": 85,
+//!   "previous": 1760400000,
+//!   "delta": 1048576,
+//!   "top": [{"path": "/srv/www", "size": 41943040, "delta": 524288}]
+//! }
+//! ```
+//!
+//! Sizes are in bytes and times in seconds since the epoch. `top` holds the
+//! biggest directories anywhere under the root, biggest first, so nested
+//! ones can appear along with their parents. `previous` is when the last
+//! summary of the same root was pushed from this machine, and each `delta`
+//! is the change in size since then; they're null for the first push, and
+//! a directory's delta is null if it wasn't in the top last time.
+
+use crate::tree::Tree;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever a field changes meaning or goes away.
+pub const SCHEMA: u32 = 1;
+
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub host: String,
+    pub root: PathBuf,
+    pub scanned: i64,
+    pub total: u64,
+    pub files: usize,
+    pub dirs: usize,
+    pub top: Vec<(PathBuf, u64)>,
+}
+
+/// What was pushed last time, to work out deltas against.
+#[derive(Debug, Default, PartialEq)]
+pub struct Previous {
+    pub scanned: i64,
+    pub sizes: HashMap<PathBuf, u64>, // the root's and the top directories'
+}
+
+/// Sums up what's under `root`, keeping its `n` biggest directories.
+pub fn summarize(tree: &Tree, root: &Path, host: &str, scanned: i64, n: usize) -> Summary {
+    let entries = tree.subtree(root);
+    let dirs = entries.iter().filter(|i| i.is_dir && i.path != root);
+    let mut top: Vec<_> = dirs.clone().map(|i| (i.path.clone(), i.size)).collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(n);
+    Summary {
+        host: host.to_string(),
+        root: root.to_path_buf(),
+        scanned,
+        total: tree.size(root),
+        files: entries.iter().filter(|i| !i.is_dir).count(),
+        dirs: dirs.count(),
+        top,
+    }
+}
+
+impl Summary {
+    /// The summary as JSON, in the schema above.
+    pub fn json(&self, previous: Option<&Previous>) -> String {
+        let delta = |path: &Path, size: u64| match previous.and_then(|p| p.sizes.get(path)) {
+            Some(&before) => (size as i128 - before as i128).to_string(),
+            None => "null".to_string(),
+        };
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            r#"{{"schema": {SCHEMA}, "host": {}, "root": {}, "scanned": {}, "total": {}, "files": {}, "dirs": {}, "previous": {}, "delta": {}, "top": ["#,
+            string(&self.host),
+            string(&self.root.to_string_lossy()),
+            self.scanned,
+            self.total,
+            self.files,
+            self.dirs,
+            previous.map_or("null".to_string(), |p| p.scanned.to_string()),
+            delta(&self.root, self.total),
+        );
+        for (n, (path, size)) in self.top.iter().enumerate() {
+            let _ = write!(
+                out,
+                r#"{}{{"path": {}, "size": {size}, "delta": {}}}"#,
+                if n == 0 { "" } else { ", " },
+                string(&path.to_string_lossy()),
+                delta(path, *size),
+            );
+        }
+        out.push_str("]}\n");
+        out
+    }
+
+    /// What to remember of this summary for the next one: a line with when
+    /// it was made, then a line of size and path for the root and each of
+    /// the top directories.
+    pub fn record(&self) -> String {
+        let mut out = format!(
+            "{}\n{}\t{}\n",
+            self.scanned,
+            self.total,
+            self.root.display()
+        );
+        for (path, size) in &self.top {
+            let _ = writeln!(out, "{size}\t{}", path.display());
+        }
+        out
+    }
+}
+
+impl Previous {
+    /// Reads what `Summary::record` wrote, if it's intact.
+    pub fn parse(text: &str) -> Option<Previous> {
+        let mut lines = text.lines();
+        let scanned = lines.next()?.parse().ok()?;
+        let sizes = lines
+            .map(|line| {
+                let (size, path) = line.split_once('\t')?;
+                Some((PathBuf::from(path), size.parse().ok()?))
+            })
+            .collect::<Option<_>>()?;
+        Some(Previous { scanned, sizes })
+    }
+}
+
+/// `s` as a JSON string.
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Info;
+
+    fn tree() -> Tree {
+        let info = |path: &str, size, is_dir| Info {
+            path: PathBuf::from(path),
+            depth: path.matches('/').count(),
+            size,
+            disk: 0,
+            is_dir,
+            mtime: 0,
+            uid: 0,
+        };
+        let mut tree = Tree {
+            data: vec![
+                info("/srv", 0, true),
+                info("/srv/www", 0, true),
+                info("/srv/www/index.html", 300, false),
+                info("/srv/www/img", 0, true),
+                info("/srv/www/img/logo.png", 200, false),
+                info("/srv/db", 0, true),
+                info("/srv/db/data", 400, false),
+            ],
+        };
+        tree.preprocess();
+        tree
+    }
+
+    #[test]
+    fn keeps_the_biggest_directories() {
+        let summary = summarize(&tree(), Path::new("/srv"), "web1", 100, 2);
+        assert_eq!(summary.total, 900);
+        assert_eq!((summary.files, summary.dirs), (3, 3));
+        assert_eq!(
+            summary.top,
+            [
+                (PathBuf::from("/srv/www"), 500),
+                (PathBuf::from("/srv/db"), 400)
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_deltas_against_the_last_push() {
+        let summary = summarize(&tree(), Path::new("/srv"), "web1", 100, 2);
+        assert_eq!(
+            summary.json(None),
+            r#"{"schema": 1, "host": "web1", "root": "/srv", "scanned": 100, "total": 900, "files": 3, "dirs": 3, "previous": null, "delta": null, "top": [{"path": "/srv/www", "size": 500, "delta": null}, {"path": "/srv/db", "size": 400, "delta": null}]}"#.to_string() + "\n"
+        );
+        let mut previous = Previous::parse(&summary.record()).unwrap();
+        assert_eq!(previous.scanned, 100);
+        previous.sizes.insert(PathBuf::from("/srv"), 1000);
+        previous.sizes.remove(Path::new("/srv/db"));
+        let json = summary.json(Some(&previous));
+        assert!(json.contains(r#""previous": 100, "delta": -100,"#));
+        assert!(json.contains(r#""size": 500, "delta": 0}"#));
+        assert!(json.contains(r#""size": 400, "delta": null}"#));
+        assert_eq!(string("a\"b\\\n"), r#""a\"b\\\u000a""#);
+    }
+}
@@ -0,0 +1,98 @@
+//! Size limits given with --fail-if, so scripts can tell from the exit
+//! status alone whether a directory has outgrown what it should hold.
+
+use crate::tree::Tree;
+use bytesize::ByteSize;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A limit on one path's size, written `PATH>SIZE` to fail when it's
+/// bigger or `PATH<SIZE` to fail when it's smaller, e.g. `/var/log>10GB`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Threshold {
+    pub path: PathBuf,
+    pub over: bool, // fails when the size is over the limit, rather than under
+    pub size: u64,
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Threshold, String> {
+        // sizes don't hold either sign, though paths might.
+        let Some(at) = s.rfind(['>', '<']) else {
+            return Err("expected PATH>SIZE or PATH<SIZE, e.g. /var/log>10GB".to_string());
+        };
+        let (path, size) = (s[..at].trim(), s[at + 1..].trim());
+        if path.is_empty() {
+            return Err("there's no path before the comparison".to_string());
+        }
+        let size = ByteSize::from_str(size).map_err(|e| format!("bad size {size:?}: {e}"))?;
+        Ok(Threshold {
+            path: PathBuf::from(path),
+            over: &s[at..=at] == ">",
+            size: size.as_u64(),
+        })
+    }
+}
+
+impl Threshold {
+    /// What's wrong, if the path's size in `tree` is on the wrong side of
+    /// the limit. Paths not in the tree count as empty.
+    pub fn crossed(&self, tree: &Tree) -> Option<String> {
+        let size = tree.size(&self.path);
+        let crossed = match self.over {
+            true => size > self.size,
+            false => size < self.size,
+        };
+        crossed.then(|| {
+            format!(
+                "{} is {}, {} {}",
+                self.path.display(),
+                ByteSize(size),
+                if self.over { "over" } else { "under" },
+                ByteSize(self.size)
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Info;
+
+    #[test]
+    fn limits_are_checked_either_way() {
+        let limit: Threshold = "/var/log > 1KB".parse().unwrap();
+        assert_eq!(
+            limit,
+            Threshold {
+                path: PathBuf::from("/var/log"),
+                over: true,
+                size: 1000
+            }
+        );
+        assert!("/var/log".parse::<Threshold>().is_err());
+        assert!(">1KB".parse::<Threshold>().is_err());
+        assert!("/var/log>lots".parse::<Threshold>().is_err());
+
+        let tree = Tree {
+            data: vec![Info {
+                path: PathBuf::from("/var/log"),
+                depth: 2,
+                size: 2000,
+                disk: 0,
+                is_dir: true,
+                mtime: 0,
+                uid: 0,
+            }],
+        };
+        assert!(limit.crossed(&tree).is_some());
+        let floor: Threshold = "/var/log<1KB".parse().unwrap();
+        assert_eq!(floor.crossed(&tree), None);
+        // a path that isn't there is empty, so under any floor.
+        let missing: Threshold = "/srv<1B".parse().unwrap();
+        assert!(missing.crossed(&tree).is_some());
+    }
+}
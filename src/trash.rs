@@ -0,0 +1,127 @@
+//! Moving things to the trash rather than deleting them, the way file
+//! managers do, following the freedesktop.org trash spec so they can be
+//! restored from any of them.
+
+use crate::dirs;
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// Moves `path` to the trash can on its filesystem: the home one if that's
+/// where it is, otherwise one at the top of the filesystem it's on. Returns
+/// where it went.
+pub fn trash(path: &Path) -> io::Result<PathBuf> {
+    let dev = fs::symlink_metadata(path)?.dev();
+    let parent = path.parent().ok_or(io::Error::other("it's the root"))?;
+    if fs::metadata(parent)?.dev() != dev {
+        return Err(io::Error::other("it's a mount point"));
+    }
+    let home = dirs::trash_dir();
+    let (can, top) = match home {
+        Some(home) if device(&home) == Some(dev) => (home, None),
+        _ => {
+            let top = parent
+                .ancestors()
+                .take_while(|p| fs::metadata(p).is_ok_and(|m| m.dev() == dev))
+                .last()
+                .unwrap_or(parent);
+            (topdir_can(top)?, Some(top))
+        }
+    };
+    if can.starts_with(path) {
+        return Err(io::Error::other("the trash can is inside it"));
+    }
+    for dir in ["files", "info"] {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(can.join(dir))?;
+    }
+
+    // the info file is made first, to claim the name.
+    let name = path.file_name().ok_or(io::Error::other("it has no name"))?;
+    let (info, to, mut file) = (1..)
+        .find_map(|n| {
+            let mut name = name.to_os_string();
+            if n > 1 {
+                name.push(format!(".{n}"));
+            }
+            let mut info = OsString::from(&name);
+            info.push(".trashinfo");
+            let (info, to) = (can.join("info").join(info), can.join("files").join(name));
+            if fs::symlink_metadata(&to).is_ok() {
+                return None;
+            }
+            match File::options().write(true).create_new(true).open(&info) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                opened => Some(opened.map(|file| (info, to, file))),
+            }
+        })
+        .unwrap()?;
+    // paths in a trash can at the top of a filesystem are relative to it,
+    // so they still hold if it's mounted somewhere else.
+    let original = top.map_or(path, |top| path.strip_prefix(top).unwrap());
+    let written = write!(
+        file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        crate::percent_encode(original),
+        local_time()
+    );
+    if let Err(e) = written.and_then(|()| fs::rename(path, &to)) {
+        let _ = fs::remove_file(&info);
+        return Err(e);
+    }
+    Ok(to)
+}
+
+/// The trash can for a filesystem whose top is `top`: our directory in a
+/// shared, sticky .Trash there if an administrator set one up, or else a
+/// .Trash-$uid of our own.
+fn topdir_can(top: &Path) -> io::Result<PathBuf> {
+    let uid = unsafe { libc::geteuid() };
+    let shared = top.join(".Trash");
+    // a symlink or a directory anyone can empty can't be trusted.
+    if fs::symlink_metadata(&shared).is_ok_and(|m| m.is_dir() && m.mode() & libc::S_ISVTX != 0) {
+        let can = shared.join(uid.to_string());
+        let made = DirBuilder::new().mode(0o700).create(&can);
+        if made.is_ok() || fs::symlink_metadata(&can).is_ok_and(|m| m.is_dir() && m.uid() == uid) {
+            return Ok(can);
+        }
+    }
+    let can = top.join(format!(".Trash-{uid}"));
+    match DirBuilder::new().mode(0o700).create(&can) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    match fs::symlink_metadata(&can)? {
+        m if m.is_dir() && m.uid() == uid => Ok(can),
+        _ => Err(io::Error::other(format!("{} isn't ours", can.display()))),
+    }
+}
+
+/// The device of `path`, or of its closest existing ancestor if it hasn't
+/// been made yet.
+fn device(path: &Path) -> Option<u64> {
+    path.ancestors()
+        .find_map(|p| fs::metadata(p).ok())
+        .map(|m| m.dev())
+}
+
+/// The time now, local and without a zone, as trash info files have it,
+/// e.g. `2024-12-22T13:37:00`.
+fn local_time() -> String {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
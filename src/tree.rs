@@ -0,0 +1,553 @@
+use rayon::slice::ParallelSliceMut;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+pub struct Info {
+    pub path: PathBuf,
+    pub depth: usize, // number of path components
+    pub size: u64,
+    pub disk: u64, // what it takes up on disk, st_blocks * 512
+    pub is_dir: bool,
+    pub mtime: i64, // seconds since the epoch
+    pub uid: u32,   // owner
+}
+
+/// Every entry of a scan in one flat vector. Once preprocessed it's sorted by
+/// path, so each directory is immediately followed by its descendants, and
+/// directory sizes include everything beneath them.
+pub struct Tree {
+    pub data: Vec<Info>,
+}
+
+/// What a tree takes up in memory, in bytes, for keeping an eye on it with
+/// huge trees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Memory {
+    pub index: u64, // the vector of entries, spare capacity included
+    pub paths: u64, // the paths' own allocations
+}
+
+impl Tree {
+    pub fn memory(&self) -> Memory {
+        Memory {
+            index: (self.data.capacity() * std::mem::size_of::<Info>()) as u64,
+            paths: self.data.iter().map(|x| x.path.capacity() as u64).sum(),
+        }
+    }
+
+    /// Adds each entry's size to its parent directory, bottom up. Expects the
+    /// data to be sorted by path.
+    pub fn accumulate(&mut self) {
+        let max_depth = self.data.iter().map(|x| x.depth).max().unwrap_or(0);
+        let mut sums = vec![(0, 0); max_depth + 1];
+        let mut prev_depth = 0;
+        for i in (0..self.data.len()).rev() {
+            let depth = self.data[i].depth;
+            if depth < prev_depth {
+                self.data[i].size += sums[prev_depth].0;
+                self.data[i].disk += sums[prev_depth].1;
+                sums[prev_depth] = (0, 0);
+            }
+            sums[depth].0 += self.data[i].size;
+            sums[depth].1 += self.data[i].disk;
+            prev_depth = depth;
+        }
+    }
+
+    /// Trades every entry's size for its disk usage and back, so everything
+    /// that goes by `size` counts the other way.
+    pub fn swap_sizes(&mut self) {
+        for x in &mut self.data {
+            std::mem::swap(&mut x.size, &mut x.disk);
+        }
+    }
+
+    pub fn preprocess(&mut self) {
+        self.data.par_sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        self.accumulate();
+    }
+
+    pub fn find(&self, p: &Path) -> Option<&Info> {
+        self.data
+            .binary_search_by(|x| x.path.as_path().cmp(p))
+            .ok()
+            .map(|i| &self.data[i])
+    }
+
+    /// The directory at the end of the chain of directories below `p` that
+    /// each hold a single directory and nothing else, like a/b/c in Java
+    /// source trees. `p` itself if it holds anything else.
+    pub fn chain_end(&self, p: &Path) -> Option<&Info> {
+        let mut end = self.find(p)?;
+        loop {
+            let mut children = self.children(&end.path);
+            match (children.next(), children.next()) {
+                (Some(only), None) if only.is_dir => end = only,
+                _ => return Some(end),
+            }
+        }
+    }
+
+    pub fn size(&self, p: &Path) -> u64 {
+        self.find(p).map_or(0, |x| x.size)
+    }
+
+    /// The entry at `p` followed by all of its descendants.
+    ///
+    /// Paths compare component by component, so `/a/b/c` sorts before `/a/b2`
+    /// and `/a/b.txt` even though it doesn't byte-wise, which keeps every
+    /// subtree contiguous. `starts_with` is component-aware too, so `/a/b2`
+    /// isn't mistaken for part of `/a/b`.
+    pub fn subtree(&self, p: &Path) -> &[Info] {
+        self.range(p).map_or(&[], |range| &self.data[range])
+    }
+
+    fn range(&self, p: &Path) -> Option<Range<usize>> {
+        let start = self
+            .data
+            .binary_search_by(|x| x.path.as_path().cmp(p))
+            .ok()?;
+        let len = self.data[start..].partition_point(|x| x.path.starts_with(p));
+        Some(start..start + len)
+    }
+
+    /// The direct children of `p`, in path order. Each child's own subtree is
+    /// skipped with a binary search, so this costs O(children * log n) rather
+    /// than walking everything below `p`.
+    pub fn children<'a>(&'a self, p: &Path) -> impl Iterator<Item = &'a Info> + 'a {
+        let subtree = self.subtree(p);
+        let depth = subtree.first().map_or(0, |x| x.depth + 1);
+        let mut i = 1; // skip `p` itself
+        std::iter::from_fn(move || {
+            let child = subtree.get(i)?;
+            i += subtree[i..].partition_point(|x| x.path.starts_with(&child.path));
+            Some(child)
+        })
+        // a partial tree (mid-scan) may be missing intermediate directories.
+        .filter(move |x| x.depth == depth)
+    }
+
+    /// Removes the entry at `p` and everything below it, taking its size off
+    /// every ancestor. Returns the size removed.
+    pub fn remove(&mut self, p: &Path) -> Option<u64> {
+        let range = self.range(p)?;
+        let Info { size, disk, .. } = self.data[range.start];
+        self.data.drain(range);
+        self.add_to_ancestors(p, size.wrapping_neg(), disk.wrapping_neg());
+        Some(size)
+    }
+
+    /// Removes the entry at `p` and everything below it like remove(), handing
+    /// it back as a tree graft() can put back. Put several back newest first,
+    /// since a later cut can hold the place of an earlier one.
+    pub fn cut(&mut self, p: &Path) -> Option<Tree> {
+        let sub = Tree {
+            data: self.subtree(p).to_vec(),
+        };
+        self.remove(p)?;
+        Some(sub)
+    }
+
+    /// Moves the entry at `p` and everything below it to `to`, as a rename
+    /// would, taking its size off its old ancestors and adding it to its new
+    /// ones. If `to`'s directory isn't in the tree, it's just removed.
+    /// Returns the size moved.
+    pub fn rename(&mut self, p: &Path, to: &Path) -> Option<u64> {
+        let mut moved = self.subtree(p).to_vec();
+        let size = self.remove(p)?;
+        if to.parent().is_some_and(|dir| self.find(dir).is_some()) {
+            // descendants keep their order under the new name.
+            for x in &mut moved {
+                x.path = match x.path.strip_prefix(p).unwrap() {
+                    rest if rest.as_os_str().is_empty() => to.to_path_buf(),
+                    rest => to.join(rest),
+                };
+                x.depth = x.path.components().count();
+            }
+            self.graft(Tree { data: moved });
+        }
+        Some(size)
+    }
+
+    /// Inserts a separately scanned and preprocessed tree, replacing whatever
+    /// was at its root before, and adds its total to every ancestor.
+    pub fn graft(&mut self, sub: Tree) {
+        let Some(root) = sub.data.first() else {
+            return;
+        };
+        let (path, size, disk) = (root.path.clone(), root.size, root.disk);
+        self.remove(&path);
+        let at = self.data.partition_point(|x| x.path < path);
+        self.data.splice(at..at, sub.data);
+        self.add_to_ancestors(&path, size, disk);
+    }
+
+    /// Takes everything below `p` out of the tree, leaving `p` itself and all
+    /// sizes as they are. attach() puts it back.
+    pub fn detach(&mut self, p: &Path) -> Vec<Info> {
+        match self.range(p) {
+            Some(range) => self.data.drain(range.start + 1..range.end).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Reinserts descendants of `p` taken out by detach().
+    pub fn attach(&mut self, p: &Path, descendants: Vec<Info>) {
+        if let Some(range) = self.range(p) {
+            self.data.splice(range.end..range.end, descendants);
+        }
+    }
+
+    fn add_to_ancestors(&mut self, p: &Path, size: u64, disk: u64) {
+        for ancestor in p.ancestors().skip(1) {
+            if let Ok(i) = self
+                .data
+                .binary_search_by(|x| x.path.as_path().cmp(ancestor))
+            {
+                self.data[i].size = self.data[i].size.wrapping_add(size);
+                self.data[i].disk = self.data[i].disk.wrapping_add(disk);
+            }
+        }
+    }
+
+    /// The direct children of `p`, largest first.
+    pub fn get(&self, p: &Path) -> Vec<Info> {
+        let mut items: Vec<Info> = self.children(p).cloned().collect();
+        items.sort_by_key(|x| std::cmp::Reverse(x.size));
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift, so the generated trees are reproducible without a dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    // names that share prefixes, to catch ordering mistakes like /a/b vs /a/b2.
+    const NAMES: [&str; 8] = ["a", "b", "b2", "b.txt", "b c", "ab", "a-b", "\u{e9}"];
+
+    /// A random tree under `/r`, unsorted, with sizes on files only besides
+    /// the root's own size.
+    fn synthetic(rng: &mut Rng) -> Vec<Info> {
+        let root = PathBuf::from("/r");
+        let mut data = vec![Info {
+            depth: root.components().count(),
+            path: root.clone(),
+            size: rng.below(10),
+            disk: 4096,
+            is_dir: true,
+            mtime: 0,
+            uid: 0,
+        }];
+        let mut dirs = vec![root];
+        for _ in 0..rng.below(200) {
+            let parent = dirs[rng.below(dirs.len() as u64) as usize].clone();
+            let path = parent.join(NAMES[rng.below(NAMES.len() as u64) as usize]);
+            if data.iter().any(|x| x.path == path) {
+                continue;
+            }
+            let is_dir = rng.below(3) == 0;
+            if is_dir {
+                dirs.push(path.clone());
+            }
+            data.push(Info {
+                depth: path.components().count(),
+                path,
+                size: if is_dir { 0 } else { rng.below(1 << 20) },
+                disk: rng.below(1 << 20) / 4096 * 4096,
+                is_dir,
+                mtime: 0,
+                uid: 0,
+            });
+        }
+        // shuffle, like results arriving from several workers.
+        for i in (1..data.len()).rev() {
+            data.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+        data
+    }
+
+    fn each_tree(f: impl Fn(Vec<Info>, Tree)) {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..300 {
+            let original = synthetic(&mut rng);
+            let mut tree = Tree {
+                data: original.clone(),
+            };
+            tree.preprocess();
+            f(original, tree);
+        }
+    }
+
+    #[test]
+    fn root_is_sum_of_everything() {
+        each_tree(|original, tree| {
+            let total: u64 = original.iter().map(|x| x.size).sum();
+            assert_eq!(tree.size(Path::new("/r")), total);
+        });
+    }
+
+    #[test]
+    fn directories_sum_their_descendants() {
+        each_tree(|original, tree| {
+            for dir in original.iter().filter(|x| x.is_dir) {
+                let expected: u64 = original
+                    .iter()
+                    .filter(|x| x.path.starts_with(&dir.path))
+                    .map(|x| x.size)
+                    .sum();
+                assert_eq!(tree.size(&dir.path), expected, "{:?}", dir.path);
+            }
+        });
+    }
+
+    #[test]
+    fn swapping_counts_disk_usage_instead() {
+        each_tree(|original, mut tree| {
+            tree.swap_sizes();
+            let total: u64 = original.iter().map(|x| x.disk).sum();
+            assert_eq!(tree.size(Path::new("/r")), total);
+            tree.swap_sizes();
+            let total: u64 = original.iter().map(|x| x.size).sum();
+            assert_eq!(tree.size(Path::new("/r")), total);
+        });
+    }
+
+    #[test]
+    fn get_returns_exactly_the_direct_children() {
+        each_tree(|original, tree| {
+            for dir in original.iter().filter(|x| x.is_dir) {
+                let mut expected: Vec<&Path> = original
+                    .iter()
+                    .filter(|x| x.path.parent() == Some(&dir.path))
+                    .map(|x| x.path.as_path())
+                    .collect();
+                let children = tree.get(&dir.path);
+                let mut got: Vec<&Path> = children.iter().map(|x| x.path.as_path()).collect();
+                expected.sort();
+                got.sort();
+                assert_eq!(got, expected, "{:?}", dir.path);
+            }
+        });
+    }
+
+    #[test]
+    fn get_is_largest_first() {
+        each_tree(|_, tree| {
+            let children = tree.get(Path::new("/r"));
+            assert!(children.windows(2).all(|w| w[0].size >= w[1].size));
+        });
+    }
+
+    fn fixed(paths: &[&str]) -> Tree {
+        let mut tree = Tree {
+            data: paths
+                .iter()
+                .map(|p| Info {
+                    path: PathBuf::from(p),
+                    depth: Path::new(p).components().count(),
+                    size: 1,
+                    disk: 4,
+                    is_dir: !p.contains('.'),
+                    mtime: 0,
+                    uid: 0,
+                })
+                .collect(),
+        };
+        tree.preprocess();
+        tree
+    }
+
+    fn paths<'a>(infos: impl IntoIterator<Item = &'a Info>) -> Vec<&'a str> {
+        infos
+            .into_iter()
+            .map(|x| x.path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sibling_prefixes_are_not_descendants() {
+        // byte-wise, "/a/b.txt" and "/a/b-c" sort between "/a/b" and "/a/b/c".
+        let tree = fixed(&[
+            "/a",
+            "/a/b",
+            "/a/b/c",
+            "/a/b/c/d.x",
+            "/a/b.txt",
+            "/a/b-c",
+            "/a/b2",
+            "/a/b2/e.x",
+            "/a/bb",
+        ]);
+        assert_eq!(
+            paths(tree.subtree(Path::new("/a/b"))),
+            ["/a/b", "/a/b/c", "/a/b/c/d.x"]
+        );
+        assert_eq!(
+            paths(tree.subtree(Path::new("/a/b2"))),
+            ["/a/b2", "/a/b2/e.x"]
+        );
+        assert_eq!(paths(tree.children(Path::new("/a/b"))), ["/a/b/c"]);
+        assert_eq!(
+            paths(tree.children(Path::new("/a"))),
+            ["/a/b", "/a/b-c", "/a/b.txt", "/a/b2", "/a/bb"]
+        );
+        assert_eq!(tree.size(Path::new("/a/b")), 3);
+        assert_eq!(tree.size(Path::new("/a")), 9);
+    }
+
+    #[test]
+    fn chains_end_at_the_first_directory_holding_more() {
+        let tree = fixed(&["/a", "/a/b", "/a/b/c", "/a/b/c/d.x", "/a/b/c/e", "/a/f.x"]);
+        assert_eq!(
+            tree.chain_end(Path::new("/a/b")).unwrap().path,
+            Path::new("/a/b/c")
+        );
+        assert_eq!(
+            tree.chain_end(Path::new("/a")).unwrap().path,
+            Path::new("/a")
+        );
+        assert!(tree.chain_end(Path::new("/nope")).is_none());
+    }
+
+    #[test]
+    fn subtree_of_a_file_is_just_the_file() {
+        let tree = fixed(&["/a", "/a/b.x", "/a/b.xy"]);
+        assert_eq!(paths(tree.subtree(Path::new("/a/b.x"))), ["/a/b.x"]);
+        assert_eq!(tree.children(Path::new("/a/b.x")).count(), 0);
+    }
+
+    #[test]
+    fn subtree_is_contiguous_and_complete() {
+        each_tree(|original, tree| {
+            for dir in original.iter().filter(|x| x.is_dir) {
+                let expected = original
+                    .iter()
+                    .filter(|x| x.path.starts_with(&dir.path))
+                    .count();
+                let subtree = tree.subtree(&dir.path);
+                assert_eq!(subtree.len(), expected, "{:?}", dir.path);
+                assert!(subtree.iter().all(|x| x.path.starts_with(&dir.path)));
+            }
+        });
+    }
+
+    #[test]
+    fn cuts_grafted_back_newest_first_restore_the_tree() {
+        each_tree(|original, mut tree| {
+            let before = tree.data.clone();
+            let mut cuts = vec![];
+            // a directory, then its parent, which no longer holds it.
+            for dir in original.iter().filter(|x| x.is_dir && x.depth > 3).take(1) {
+                cuts.extend(tree.cut(&dir.path));
+                cuts.extend(tree.cut(dir.path.parent().unwrap()));
+            }
+            for sub in cuts.into_iter().rev() {
+                tree.graft(sub);
+            }
+            assert!(tree.data.windows(2).all(|w| w[0].path < w[1].path));
+            assert_eq!(tree.data.len(), before.len());
+            for (got, want) in tree.data.iter().zip(&before) {
+                assert_eq!(
+                    (&got.path, got.size, got.disk),
+                    (&want.path, want.size, want.disk)
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn remove_and_graft_keep_ancestors_consistent() {
+        let mut tree = fixed(&["/a", "/a/b", "/a/b/c", "/a/b/c/d.x", "/a/b/e.x", "/a/f.x"]);
+        assert_eq!(tree.size(Path::new("/a")), 6);
+
+        assert_eq!(tree.remove(Path::new("/a/b/c")), Some(2));
+        assert!(tree.find(Path::new("/a/b/c/d.x")).is_none());
+        assert_eq!(tree.size(Path::new("/a/b")), 2);
+        assert_eq!(tree.size(Path::new("/a")), 4);
+
+        tree.graft(fixed(&["/a/b/c", "/a/b/c/g.x", "/a/b/c/h.x"]));
+        assert_eq!(tree.size(Path::new("/a/b/c")), 3);
+        assert_eq!(tree.size(Path::new("/a/b")), 5);
+        assert_eq!(tree.size(Path::new("/a")), 7);
+        assert_eq!(
+            paths(tree.children(Path::new("/a/b/c"))),
+            ["/a/b/c/g.x", "/a/b/c/h.x"]
+        );
+        assert!(tree.data.windows(2).all(|w| w[0].path < w[1].path));
+        tree.swap_sizes();
+        assert_eq!(tree.size(Path::new("/a")), 28);
+
+        assert_eq!(tree.remove(Path::new("/a/missing")), None);
+    }
+
+    #[test]
+    fn rename_moves_sizes_between_ancestors() {
+        let mut tree = fixed(&[
+            "/a",
+            "/a/b",
+            "/a/b/c.x",
+            "/a/b/d",
+            "/a/b/d/e.x",
+            "/t",
+            "/t/f",
+        ]);
+        assert_eq!(tree.rename(Path::new("/a/b"), Path::new("/t/f/b")), Some(4));
+        assert_eq!(tree.size(Path::new("/a")), 1);
+        assert_eq!(tree.size(Path::new("/t")), 6);
+        assert_eq!(
+            paths(tree.subtree(Path::new("/t/f/b"))),
+            ["/t/f/b", "/t/f/b/c.x", "/t/f/b/d", "/t/f/b/d/e.x"]
+        );
+        assert_eq!(tree.find(Path::new("/t/f/b/d/e.x")).unwrap().depth, 6);
+        assert!(tree.data.windows(2).all(|w| w[0].path < w[1].path));
+
+        // somewhere the tree doesn't reach, it's as good as removed.
+        assert_eq!(
+            tree.rename(Path::new("/t/f/b"), Path::new("/elsewhere/b")),
+            Some(4)
+        );
+        assert_eq!(tree.size(Path::new("/t")), 2);
+        assert!(tree.find(Path::new("/elsewhere/b")).is_none());
+        assert_eq!(
+            tree.rename(Path::new("/a/missing"), Path::new("/t/x")),
+            None
+        );
+    }
+
+    #[test]
+    fn memory_counts_entries_and_paths() {
+        let tree = fixed(&["/a", "/a/b.x"]);
+        let memory = tree.memory();
+        assert!(memory.index >= 2 * std::mem::size_of::<Info>() as u64);
+        assert!(memory.paths >= "/a/a/b.x".len() as u64);
+        assert_eq!(
+            Tree { data: vec![] }.memory(),
+            Memory { index: 0, paths: 0 }
+        );
+    }
+
+    #[test]
+    fn get_of_missing_path_is_empty() {
+        each_tree(|_, tree| {
+            assert!(tree.get(Path::new("/r/missing")).is_empty());
+            assert!(tree.get(Path::new("/elsewhere")).is_empty());
+        });
+    }
+}
@@ -0,0 +1,129 @@
+//! Virtual machine disk images: how big the guest thinks the disk is
+//! against how much of it the host has allocated, read from the image
+//! headers the way `qemu-img info` does.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// The extensions images are looked for under, so that listing a directory
+/// doesn't mean opening every file in it.
+pub const EXTENSIONS: [&str; 7] = ["qcow2", "qcow", "vmdk", "vdi", "vhd", "img", "raw"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub format: &'static str,
+    pub virtual_size: u64, // the size of the disk the guest sees
+    pub allocated: u64,    // what it takes up on the host
+}
+
+impl Image {
+    /// Whether the image is heavily over-provisioned, so could grow far
+    /// past what it uses now, or fully allocated, so could be made sparse.
+    pub fn flag(&self) -> Option<&'static str> {
+        if self.allocated.saturating_mul(10) < self.virtual_size {
+            Some("over")
+        } else if self.allocated >= self.virtual_size / 100 * 95 {
+            Some("full")
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the image at `path`, if it is one. Files with an image extension
+/// but no header anybody recognises are taken for raw images.
+pub fn inspect(path: &Path) -> io::Result<Option<Image>> {
+    let mut file = File::open(path)?;
+    let meta = file.metadata()?;
+    let mut head = [0; 512];
+    let n = read_full(&mut file, &mut head)?;
+    let mut found = parse(&head[..n]);
+    // a fixed VHD only has its footer, at the end.
+    if found.is_none() && meta.len() >= 512 {
+        file.seek(SeekFrom::End(-512))?;
+        let n = read_full(&mut file, &mut head)?;
+        found = parse(&head[..n]).filter(|&(format, _)| format == "vhd");
+    }
+    let raw = path.extension().is_some_and(|e| e == "img" || e == "raw");
+    let (format, virtual_size) = match found {
+        Some(found) => found,
+        None if raw => ("raw", meta.len()),
+        None => return Ok(None),
+    };
+    Ok(Some(Image {
+        format,
+        virtual_size,
+        allocated: meta.blocks() * 512,
+    }))
+}
+
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..])? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    Ok(n)
+}
+
+/// The format and virtual size given by an image header.
+fn parse(head: &[u8]) -> Option<(&'static str, u64)> {
+    let be = |at: usize| Some(u64::from_be_bytes(head.get(at..at + 8)?.try_into().ok()?));
+    let le = |at: usize| Some(u64::from_le_bytes(head.get(at..at + 8)?.try_into().ok()?));
+    match head.get(..8)? {
+        [b'Q', b'F', b'I', 0xfb, ..] => Some(("qcow2", be(24)?)),
+        // sparse extents; capacity is in sectors.
+        [b'K', b'D', b'M', b'V', ..] => Some(("vmdk", le(12)?.checked_mul(512)?)),
+        b"conectix" => Some(("vhd", be(48)?)),
+        _ if head.get(64..68) == Some(&[0x7f, 0x10, 0xda, 0xbe]) => Some(("vdi", le(368)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(magic: &[u8], at: usize, size: &[u8]) -> Vec<u8> {
+        let mut head = vec![0; 512];
+        head[..magic.len()].copy_from_slice(magic);
+        head[at..at + size.len()].copy_from_slice(size);
+        head
+    }
+
+    #[test]
+    fn reads_virtual_sizes_from_headers() {
+        let gb = 1u64 << 30;
+        let qcow2 = header(b"QFI\xfb\0\0\0\x03", 24, &(20 * gb).to_be_bytes());
+        assert_eq!(parse(&qcow2), Some(("qcow2", 20 * gb)));
+        let vmdk = header(b"KDMV", 12, &(2 * gb / 512).to_le_bytes());
+        assert_eq!(parse(&vmdk), Some(("vmdk", 2 * gb)));
+        let vhd = header(b"conectix", 48, &gb.to_be_bytes());
+        assert_eq!(parse(&vhd), Some(("vhd", gb)));
+        let mut vdi = header(
+            b"<<< Oracle VM VirtualBox Disk Image >>>\n",
+            368,
+            &gb.to_le_bytes(),
+        );
+        vdi[64..68].copy_from_slice(&[0x7f, 0x10, 0xda, 0xbe]);
+        assert_eq!(parse(&vdi), Some(("vdi", gb)));
+        assert_eq!(parse(b"QFI"), None);
+        assert_eq!(parse(&[0; 512]), None);
+    }
+
+    #[test]
+    fn flags_over_provisioned_and_full_images() {
+        let image = |virtual_size, allocated| Image {
+            format: "raw",
+            virtual_size,
+            allocated,
+        };
+        assert_eq!(image(100 << 30, 1 << 30).flag(), Some("over"));
+        assert_eq!(image(100 << 30, 50 << 30).flag(), None);
+        assert_eq!(image(100 << 30, 100 << 30).flag(), Some("full"));
+    }
+}
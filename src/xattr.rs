@@ -0,0 +1,49 @@
+//! Extended attributes, where ACLs, SELinux labels and Samba's Windows
+//! metadata are kept. They take up space of their own, which can add up on
+//! file servers.
+
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// The names of the attributes on `path`, not following symlinks, with
+/// the size of each one's value.
+pub fn list(path: &Path) -> io::Result<Vec<(OsString, usize)>> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut names = vec![0u8; 1024];
+    let len = loop {
+        let len =
+            unsafe { libc::llistxattr(c_path.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+        match len {
+            0.. => break len as usize,
+            _ if io::Error::last_os_error().raw_os_error() == Some(libc::ERANGE) => {
+                names.resize(names.len() * 4, 0);
+            }
+            _ => return Err(io::Error::last_os_error()),
+        }
+    };
+    let mut attributes = vec![];
+    for name in names[..len].split(|&c| c == 0).filter(|n| !n.is_empty()) {
+        let c_name = CString::new(name)?;
+        // with no buffer, the size of the value is all that's returned.
+        let size =
+            unsafe { libc::lgetxattr(c_path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        attributes.push((OsStr::from_bytes(name).to_owned(), size as usize));
+    }
+    Ok(attributes)
+}
+
+/// Roughly what the attributes on `path` take up: their names and values.
+/// Filesystems store them more or less compactly, but none for free.
+pub fn size(path: &Path) -> u64 {
+    list(path).map_or(0, |attributes| {
+        attributes
+            .iter()
+            .map(|(name, size)| (name.len() + size) as u64)
+            .sum()
+    })
+}
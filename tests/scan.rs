@@ -0,0 +1,624 @@
+//! Builds small directory trees on disk and checks what the scanner makes of
+//! them.
+
+use adansonia::exclude::Excludes;
+use adansonia::scan::{self, Entry, LocalSource, Options, ScanSource};
+use adansonia::tree::Tree;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fs, io, thread};
+
+/// A scratch directory that's removed again when dropped.
+struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Fixture {
+        let root = std::env::temp_dir().join(format!("adansonia-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Fixture {
+            root: root.canonicalize().unwrap(),
+        }
+    }
+
+    fn dir(&self, path: &str) -> &Fixture {
+        fs::create_dir_all(self.root.join(path)).unwrap();
+        self
+    }
+
+    fn file(&self, path: impl AsRef<Path>, size: usize) -> &Fixture {
+        fs::write(self.root.join(path), vec![b'x'; size]).unwrap();
+        self
+    }
+
+    fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Scans the fixture, returning the preprocessed tree and the error count.
+    fn scan(&self) -> (Tree, u64) {
+        let source = Arc::new(LocalSource::new(&self.root, Options::default()).unwrap());
+        let scan = scan::scan(source, &self.root).unwrap();
+        let errors = scan.errors.clone();
+        let mut tree = scan.wait();
+        tree.preprocess();
+        (tree, errors.load(Ordering::Relaxed))
+    }
+
+    /// Size of everything under the root, not counting the root's own inode.
+    fn contents(&self, tree: &Tree) -> u64 {
+        tree.size(&self.root) - self.root.metadata().unwrap().len()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        // make sure anything we locked ourselves out of can be removed.
+        for entry in fs::read_dir(&self.root).into_iter().flatten().flatten() {
+            let _ = fs::set_permissions(entry.path(), fs::Permissions::from_mode(0o755));
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn running_as_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[test]
+fn sums_nested_files() {
+    let f = Fixture::new("nested");
+    f.dir("a/b/c")
+        .file("a/one", 100)
+        .file("a/b/two", 200)
+        .file("a/b/c/three", 300)
+        .file("top", 7);
+    let (tree, errors) = f.scan();
+
+    assert_eq!(errors, 0);
+    assert_eq!(tree.data.len(), 1 + 3 + 4);
+    assert_eq!(tree.size(&f.path("a/b/c")), 300);
+    assert_eq!(tree.size(&f.path("a/b")), 500);
+    assert_eq!(tree.size(&f.path("a")), 600);
+    assert_eq!(f.contents(&tree), 607);
+
+    let children: Vec<_> = tree.get(&f.root).into_iter().map(|x| x.path).collect();
+    assert_eq!(children, vec![f.path("a"), f.path("top")]);
+}
+
+#[test]
+fn empty_directory() {
+    let f = Fixture::new("empty");
+    let (tree, errors) = f.scan();
+    assert_eq!(errors, 0);
+    assert_eq!(tree.data.len(), 1);
+    assert_eq!(f.contents(&tree), 0);
+    assert!(tree.get(&f.root).is_empty());
+}
+
+#[test]
+fn symlinks_are_skipped() {
+    let f = Fixture::new("symlinks");
+    f.dir("real").file("real/data", 1000);
+    symlink(f.path("real"), f.path("link-to-dir")).unwrap();
+    symlink(f.path("real/data"), f.path("link-to-file")).unwrap();
+    symlink(f.path("nowhere"), f.path("dangling")).unwrap();
+    let (tree, errors) = f.scan();
+
+    assert_eq!(errors, 0);
+    assert!(tree.find(&f.path("link-to-dir")).is_none());
+    assert!(tree.find(&f.path("link-to-file")).is_none());
+    assert!(tree.find(&f.path("dangling")).is_none());
+    assert_eq!(f.contents(&tree), 1000);
+}
+
+#[test]
+fn hardlinks_are_counted_once() {
+    let f = Fixture::new("hardlinks");
+    f.dir("a").dir("b").file("a/data", 4000);
+    fs::hard_link(f.path("a/data"), f.path("b/data")).unwrap();
+    let source = Arc::new(LocalSource::new(&f.root, Options::default()).unwrap());
+    let mut tree = scan::scan(source.clone(), &f.root).unwrap().wait();
+    tree.preprocess();
+
+    // whichever link was found first carries the size.
+    let (a, b) = (tree.size(&f.path("a")), tree.size(&f.path("b")));
+    assert_eq!((a.min(b), a.max(b)), (0, 4000));
+    assert_eq!(f.contents(&tree), 4000);
+    // and still does when measured again.
+    assert_eq!(source.size_on_disk(&f.path("a/data"), true), Some(a));
+    assert_eq!(source.size_on_disk(&f.path("b"), true), Some(b));
+}
+
+#[test]
+fn hardlinks_are_counted_per_link_when_asked() {
+    let f = Fixture::new("hardlinks-each");
+    f.dir("a").dir("b").file("a/data", 4000);
+    fs::hard_link(f.path("a/data"), f.path("b/data")).unwrap();
+    let options = Options {
+        count_links: true,
+        ..Options::default()
+    };
+    let source = Arc::new(LocalSource::new(&f.root, options).unwrap());
+    let mut tree = scan::scan(source, &f.root).unwrap().wait();
+    tree.preprocess();
+
+    assert_eq!(tree.size(&f.path("a")), 4000);
+    assert_eq!(tree.size(&f.path("b")), 4000);
+    assert_eq!(f.contents(&tree), 8000);
+}
+
+#[test]
+fn unreadable_directories_are_counted_as_errors() {
+    if running_as_root() {
+        return; // root can read anything, so there's nothing to test.
+    }
+    let f = Fixture::new("denied");
+    f.dir("open")
+        .dir("locked")
+        .file("open/a", 10)
+        .file("locked/b", 20);
+    fs::set_permissions(f.path("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+    let (tree, errors) = f.scan();
+
+    assert_eq!(errors, 1);
+    assert!(tree.find(&f.path("locked")).is_some());
+    assert!(tree.find(&f.path("locked/b")).is_none());
+    assert_eq!(f.contents(&tree), 10);
+}
+
+#[test]
+fn non_utf8_names() {
+    let f = Fixture::new("nonutf8");
+    let name = OsStr::from_bytes(b"caf\xe9");
+    f.dir("d").file(Path::new("d").join(name), 42);
+    let (tree, errors) = f.scan();
+
+    assert_eq!(errors, 0);
+    let entry = tree.find(&f.path("d").join(name)).unwrap();
+    assert_eq!(entry.size, 42);
+    assert!(!entry.is_dir);
+    assert_eq!(tree.size(&f.path("d")), 42);
+}
+
+#[test]
+fn wide_and_deep_trees() {
+    let f = Fixture::new("shape");
+    for i in 0..500 {
+        f.file(format!("wide{i}"), i);
+    }
+    let deep: PathBuf = (0..64).map(|i| format!("d{i}")).collect();
+    f.dir(deep.to_str().unwrap()).file(deep.join("bottom"), 99);
+    let (tree, errors) = f.scan();
+
+    assert_eq!(errors, 0);
+    assert_eq!(tree.data.len(), 1 + 500 + 64 + 1);
+    assert_eq!(f.contents(&tree), (0..500).sum::<u64>() + 99);
+    assert_eq!(tree.size(&f.path("d0")), 99);
+    assert!(tree
+        .data
+        .iter()
+        .all(|i| i.depth == i.path.components().count()));
+}
+
+#[test]
+fn a_file_as_root_is_a_single_entry() {
+    let f = Fixture::new("fileroot");
+    f.file("lone", 123);
+    let path = f.path("lone");
+    let source = Arc::new(LocalSource::new(&path, Options::default()).unwrap());
+    let scan = scan::scan(source, &path).unwrap();
+    let errors = scan.errors.clone();
+    let tree = scan.wait();
+
+    assert_eq!(errors.load(Ordering::Relaxed), 0);
+    assert_eq!(tree.data.len(), 1);
+    assert!(!tree.data[0].is_dir);
+    assert_eq!(tree.data[0].size, 123);
+}
+
+#[test]
+fn merged_scans_cover_every_root() {
+    let f = Fixture::new("merged");
+    f.dir("a")
+        .dir("b")
+        .file("a/x", 10)
+        .file("b/y", 20)
+        .file("z", 30);
+    let scans = ["a", "b", "z"]
+        .map(|p| {
+            let path = f.path(p);
+            let source = Arc::new(LocalSource::new(&path, Options::default()).unwrap());
+            scan::scan(source, &path).unwrap()
+        })
+        .into();
+    let mut tree = scan::Scan::merge(scans).wait();
+    tree.preprocess();
+
+    assert_eq!(tree.data.len(), 5);
+    assert_eq!(
+        tree.size(&f.path("b")) - f.path("b").metadata().unwrap().len(),
+        20
+    );
+    assert_eq!(tree.size(&f.path("z")), 30);
+}
+
+#[test]
+fn cache_directories_are_flagged_and_can_be_excluded() {
+    let f = Fixture::new("caches");
+    f.dir("cache")
+        .dir("fake")
+        .file("cache/blob", 1000)
+        .file("fake/blob", 10);
+    fs::write(
+        f.path("cache/CACHEDIR.TAG"),
+        "Signature: 8a477f597d28d172789f06886806bc55\n# a cache\n",
+    )
+    .unwrap();
+    fs::write(f.path("fake/CACHEDIR.TAG"), "not a signature").unwrap();
+    let tag = f.path("cache/CACHEDIR.TAG").metadata().unwrap().len();
+
+    for exclude_caches in [false, true] {
+        let options = Options {
+            exclude_caches,
+            ..Options::default()
+        };
+        let source = Arc::new(LocalSource::new(&f.root, options).unwrap());
+        let mut tree = scan::scan(source.clone(), &f.root).unwrap().wait();
+        tree.preprocess();
+
+        assert_eq!(source.caches(), [f.path("cache")]);
+        let expected = if exclude_caches { tag } else { tag + 1000 };
+        assert_eq!(tree.size(&f.path("cache")), expected);
+        assert!(tree.find(&f.path("cache/blob")).is_some() != exclude_caches);
+    }
+}
+
+#[test]
+fn filesystem_calls_are_counted() {
+    let f = Fixture::new("calls");
+    f.dir("a").file("a/x", 1).file("a/y", 1).file("z", 1);
+    let source = Arc::new(LocalSource::new(&f.root, Options::default()).unwrap());
+    scan::scan(source.clone(), &f.root).unwrap().wait();
+    // the root's stat, then a listing and a stat for each entry, twice.
+    assert_eq!(source.calls(), 1 + (1 + 2) + (1 + 2));
+}
+
+/// A made-up tree where listing one directory never comes back in time.
+struct Hanging;
+
+impl ScanSource for Hanging {
+    fn root(&self, root: &Path) -> io::Result<Entry> {
+        Ok(entry(root, true))
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<Entry>>> {
+        if dir.ends_with("stuck") {
+            thread::sleep(Duration::from_secs(5));
+        }
+        Ok(match dir.to_str().unwrap() {
+            "/r" => vec![Ok(entry("/r/ok", true)), Ok(entry("/r/stuck", true))],
+            "/r/ok" => vec![Ok(entry("/r/ok/file", false))],
+            _ => vec![],
+        })
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(Duration::from_millis(200))
+    }
+}
+
+fn entry(path: impl AsRef<Path>, is_dir: bool) -> Entry {
+    Entry {
+        path: path.as_ref().to_path_buf(),
+        size: if is_dir { 0 } else { 1 },
+        disk: if is_dir { 0 } else { 1 },
+        is_dir,
+        mtime: 0,
+        uid: 0,
+    }
+}
+
+#[test]
+fn hanging_directories_are_skipped() {
+    let started = Instant::now();
+    let scan = scan::scan(Arc::new(Hanging), Path::new("/r")).unwrap();
+    let timed_out = scan.timed_out.clone();
+    let mut tree = scan.wait();
+    tree.preprocess();
+
+    assert!(started.elapsed() < Duration::from_secs(3));
+    assert_eq!(*timed_out.lock().unwrap(), [PathBuf::from("/r/stuck")]);
+    assert_eq!(tree.size(Path::new("/r/ok")), 1);
+    assert!(tree.find(Path::new("/r/stuck")).is_some());
+}
+
+#[test]
+fn local_directories_are_only_timed_out_when_asked() {
+    let f = Fixture::new("timeout");
+    let local = LocalSource::new(&f.root, Options::default()).unwrap();
+    assert!(local.is_network() || local.timeout().is_none());
+    let options = Options {
+        timeout: Some(Duration::from_secs(5)),
+        ..Options::default()
+    };
+    let asked = LocalSource::new(&f.root, options).unwrap();
+    assert_eq!(asked.timeout(), Some(Duration::from_secs(5)));
+}
+
+#[test]
+fn cpu_budget_limits_threads_and_rests_workers() {
+    let f = Fixture::new("budget");
+    let budget = |p| {
+        let options = Options {
+            cpu_budget: Some(p),
+            threads: Some(16),
+            ..Options::default()
+        };
+        LocalSource::new(&f.root, options).unwrap()
+    };
+    let work = Duration::from_millis(100);
+
+    let half = budget(50);
+    assert_eq!(half.threads(), 1);
+    assert_eq!(half.rest(work), work);
+
+    let two = budget(200);
+    assert_eq!(two.threads(), 2);
+    assert_eq!(two.rest(work), Duration::ZERO);
+
+    let unlimited = LocalSource::new(&f.root, Options::default()).unwrap();
+    assert_eq!(unlimited.rest(work), Duration::ZERO);
+    // without a budget or a count, one thread per CPU.
+    let cpus = thread::available_parallelism().unwrap().get();
+    assert_eq!(unlimited.threads(), cpus);
+}
+
+/// A made-up tree that never ends: every directory holds two more.
+struct Endless;
+
+impl ScanSource for Endless {
+    fn root(&self, root: &Path) -> io::Result<Entry> {
+        Ok(entry(root, true))
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<io::Result<Entry>>> {
+        thread::sleep(Duration::from_millis(1));
+        Ok(vec![
+            Ok(entry(dir.join("a"), true)),
+            Ok(entry(dir.join("b"), true)),
+        ])
+    }
+}
+
+#[test]
+fn scans_can_be_paused_resumed_and_cancelled() {
+    let scan = scan::scan(Arc::new(Endless), Path::new("/r")).unwrap();
+    let control = scan.control.clone();
+
+    control.pause();
+    thread::sleep(Duration::from_millis(50)); // for listings already under way
+    let found = control.found();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(control.found(), found);
+    assert!(control.is_paused());
+
+    control.resume();
+    thread::sleep(Duration::from_millis(50));
+    assert!(control.found() > found);
+
+    control.cancel();
+    let tree = scan.wait();
+    assert!(control.is_done() && control.is_cancelled());
+    assert!(tree.data.len() as u64 <= control.found());
+}
+
+#[test]
+fn worker_stats_account_for_every_directory() {
+    let f = Fixture::new("workers");
+    for i in 0..20 {
+        f.dir(&format!("d{i}/e"));
+    }
+    let options = Options {
+        threads: Some(16),
+        ..Options::default()
+    };
+    let source = Arc::new(LocalSource::new(&f.root, options).unwrap());
+    let scan = scan::scan(source, &f.root).unwrap();
+    let control = scan.control.clone();
+    scan.wait();
+
+    let workers = control.workers();
+    assert_eq!(workers.len(), 16);
+    assert_eq!(workers.iter().map(|w| w.listed).sum::<u64>(), 1 + 20 + 20);
+    assert!(workers.iter().all(|w| w.finished && w.queue == 0));
+    assert!(workers.iter().all(|w| w.stolen <= w.steals));
+}
+
+#[test]
+fn deterministic_scans_come_out_in_the_same_order() {
+    let f = Fixture::new("deterministic");
+    for i in 0..30 {
+        f.dir(&format!("d{i}")).file(format!("d{i}/f"), i);
+        f.file(format!("f{i}"), i);
+    }
+    let options = Options {
+        deterministic: true,
+        ..Options::default()
+    };
+    let source = Arc::new(LocalSource::new(&f.root, options).unwrap());
+    assert_eq!(source.threads(), 1);
+    let order = || {
+        let tree = scan::scan(source.clone(), &f.root).unwrap().wait();
+        tree.data.into_iter().map(|x| x.path).collect::<Vec<_>>()
+    };
+
+    let first = order();
+    assert_eq!(first.len(), 1 + 30 * 3);
+    assert_eq!(first, order());
+    // the root's children come out sorted by name.
+    let children: Vec<_> = first
+        .iter()
+        .filter(|p| p.parent() == Some(&f.root))
+        .collect();
+    assert!(children.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn aliases_are_left_empty_unless_scanned_directly() {
+    let f = Fixture::new("aliases");
+    f.dir("real").file("real/f", 100);
+    f.dir("alias").file("alias/f", 100);
+    let options = Options {
+        aliases: [(f.path("alias"), f.path("real"))].into(),
+        ..Options::default()
+    };
+    let scan = |root: &Path| {
+        let source = Arc::new(LocalSource::new(root, options.clone()).unwrap());
+        let mut tree = scan::scan(source, root).unwrap().wait();
+        tree.preprocess();
+        tree
+    };
+
+    let tree = scan(&f.root);
+    assert_eq!(tree.size(&f.path("real")), 100);
+    assert_eq!(tree.size(&f.path("alias")), 0);
+    assert!(tree.find(&f.path("alias/f")).is_none());
+
+    let tree = scan(&f.path("alias"));
+    assert!(tree.find(&f.path("alias/f")).is_some());
+}
+
+#[test]
+fn exclude_files_leave_out_what_they_match() {
+    let f = Fixture::new("exclude-from");
+    f.dir("src").file("src/main.rs", 10).file("src/main.o", 20);
+    f.dir("target").file("target/bin", 30);
+    f.dir("src/target").file("src/target/x", 40);
+    let options = Options {
+        excludes: Excludes::parse("*.o\n/target/\n"),
+        ..Options::default()
+    };
+    let source = Arc::new(LocalSource::new(&f.root, options).unwrap());
+    let mut tree = scan::scan(source, &f.root).unwrap().wait();
+    tree.preprocess();
+
+    assert!(tree.find(&f.path("src/main.rs")).is_some());
+    assert!(tree.find(&f.path("src/main.o")).is_none());
+    assert!(tree.find(&f.path("target")).is_none());
+    // anchored to the root, so a target further down stays.
+    assert!(tree.find(&f.path("src/target/x")).is_some());
+}
+
+#[test]
+fn ignore_files_are_followed_when_asked() {
+    let f = Fixture::new("gitignore");
+    f.dir("repo/.git/info")
+        .dir("repo/src/gen")
+        .dir("repo/target");
+    fs::write(f.path("repo/.gitignore"), "/target/\n*.log\n").unwrap();
+    fs::write(f.path("repo/.git/info/exclude"), "notes.txt\n").unwrap();
+    fs::write(f.path("repo/src/.gitignore"), "gen/\n!keep.log\n").unwrap();
+    f.file("repo/target/bin", 30).file("repo/debug.log", 20);
+    f.file("repo/notes.txt", 10).file("repo/src/gen/x.rs", 40);
+    f.file("repo/src/main.rs", 10).file("repo/src/keep.log", 5);
+    let scan = |root: &Path| {
+        let options = Options {
+            respect_gitignore: true,
+            ..Options::default()
+        };
+        let source = Arc::new(LocalSource::new(root, options).unwrap());
+        let mut tree = scan::scan(source, root).unwrap().wait();
+        tree.preprocess();
+        tree
+    };
+
+    let tree = scan(&f.root);
+    assert!(tree.find(&f.path("repo/src/main.rs")).is_some());
+    for ignored in ["target", "debug.log", "notes.txt", "src/gen"] {
+        assert!(tree.find(&f.path("repo").join(ignored)).is_none());
+    }
+    // the deeper file overrides the one above it.
+    assert!(tree.find(&f.path("repo/src/keep.log")).is_some());
+    // starting partway down still follows the files further up.
+    f.file("repo/src/gen.log", 1);
+    let tree = scan(&f.path("repo/src"));
+    assert!(tree.find(&f.path("repo/src/gen.log")).is_none());
+    assert!(tree.find(&f.path("repo/src/keep.log")).is_some());
+}
+
+#[test]
+fn sparse_files_are_reported_with_their_holes() {
+    let f = Fixture::new("sparse");
+    f.file("dense", 4 << 20);
+    let sparse = fs::File::create(f.path("sparse")).unwrap();
+    sparse.set_len(64 << 20).unwrap();
+    let source = Arc::new(LocalSource::new(&f.root, Options::default()).unwrap());
+    let mut tree = scan::scan(source.clone(), &f.root).unwrap().wait();
+    tree.preprocess();
+
+    // sizes are still apparent sizes, the holes are reported alongside.
+    assert_eq!(tree.size(&f.path("sparse")), 64 << 20);
+    assert_eq!(source.sparse(), [(f.path("sparse"), 64 << 20)]);
+}
+
+#[test]
+fn disk_usage_is_collected_alongside_apparent_sizes() {
+    let f = Fixture::new("disk");
+    f.file("dense", 1 << 20);
+    fs::File::create(f.path("sparse"))
+        .unwrap()
+        .set_len(64 << 20)
+        .unwrap();
+    let source = Arc::new(LocalSource::new(&f.root, Options::default()).unwrap());
+    let mut tree = scan::scan(source.clone(), &f.root).unwrap().wait();
+    tree.preprocess();
+
+    let disk = |name| tree.find(&f.path(name)).unwrap().disk;
+    assert!(disk("dense") >= 1 << 20);
+    assert!(disk("sparse") < 1 << 20);
+    let root = tree.find(&f.root).unwrap();
+    assert_eq!(source.size_on_disk(&f.root, false), Some(root.disk));
+
+    let sparse = disk("sparse");
+    tree.swap_sizes();
+    assert_eq!(tree.size(&f.path("sparse")), sparse);
+}
+
+#[test]
+fn extended_attributes_are_counted_when_asked() {
+    let f = Fixture::new("xattrs");
+    f.file("labelled", 10).file("plain", 10);
+    let path = std::ffi::CString::new(f.path("labelled").into_os_string().into_encoded_bytes());
+    let value = [b'v'; 100];
+    let set = unsafe {
+        libc::setxattr(
+            path.unwrap().as_ptr(),
+            c"user.comment".as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if set != 0 {
+        return; // the filesystem holding the fixture doesn't support them
+    }
+    for (xattrs, labelled) in [(false, 10), (true, 10 + "user.comment".len() as u64 + 100)] {
+        let options = Options {
+            xattrs,
+            ..Options::default()
+        };
+        let source = Arc::new(LocalSource::new(&f.root, options).unwrap());
+        let mut tree = scan::scan(source, &f.root).unwrap().wait();
+        tree.preprocess();
+        assert_eq!(tree.size(&f.path("labelled")), labelled);
+        assert_eq!(tree.size(&f.path("plain")), 10);
+    }
+}